@@ -0,0 +1,36 @@
+create type profile_visibility as enum ('everyone', 'school', 'followers');
+
+alter table users
+    add profile_visibility profile_visibility not null default 'everyone',
+    add hide_posts         bool               not null default false,
+    add hide_likes         bool               not null default false;
+
+create table users_follows
+(
+    follower_id uuid                     not null,
+    followed_id uuid                     not null,
+    followed_at timestamp with time zone not null default (now()),
+    -- Follows of profiles visible only to followers wait for the owner to accept them
+    accepted    bool                     not null default true,
+    primary key (follower_id, followed_id)
+);
+
+create index users_follows_followed_id_idx on users_follows (followed_id);
+
+create function is_follower(owner uuid, viewer uuid) returns bool as
+$$
+select exists(select 1 from users_follows where follower_id = viewer and followed_id = owner and accepted = true)
+$$ language sql stable;
+
+-- Whether `viewer` (null when anonymous) may see the profile of `owner`.
+-- Every logged-in account belongs to the school, so `school` only needs a session.
+create function can_view_profile(owner uuid, viewer uuid) returns bool as
+$$
+select coalesce(owner = viewer, false)
+           or exists(select 1
+                     from users
+                     where id = owner
+                       and (profile_visibility = 'everyone'
+                         or (profile_visibility = 'school' and viewer is not null)
+                         or (profile_visibility = 'followers' and is_follower(owner, viewer))))
+$$ language sql stable;
@@ -0,0 +1,27 @@
+create table user_blocks
+(
+    blocker_id uuid                     not null,
+    blocked_id uuid                     not null,
+    blocked_at timestamp with time zone not null default (now()),
+    primary key (blocker_id, blocked_id)
+);
+
+create index user_blocks_blocked_id_idx on user_blocks (blocked_id);
+
+create function has_blocked(blocker uuid, blocked uuid) returns bool as
+$$
+select exists(select 1 from user_blocks where blocker_id = blocker and blocked_id = blocked)
+$$ language sql stable;
+
+-- Blocked users cannot see the profile of the blocker
+create or replace function can_view_profile(owner uuid, viewer uuid) returns bool as
+$$
+select coalesce(owner = viewer, false)
+           or (not has_blocked(owner, viewer)
+    and exists(select 1
+               from users
+               where id = owner
+                 and (profile_visibility = 'everyone'
+                   or (profile_visibility = 'school' and viewer is not null)
+                   or (profile_visibility = 'followers' and is_follower(owner, viewer)))))
+$$ language sql stable;
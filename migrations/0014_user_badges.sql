@@ -0,0 +1,9 @@
+create table user_badges
+(
+    user_id    uuid                     not null,
+    badge_id   varchar                  not null,
+    awarded_at timestamp with time zone not null default (now()),
+    -- Null when awarded automatically
+    awarded_by uuid,
+    primary key (user_id, badge_id)
+);
@@ -0,0 +1,2 @@
+create index projects_owner_updated_at_idx on projects (owner_id, updated_at desc, id desc);
+create index posts_owner_id_id_idx on posts (owner_id, id desc);
@@ -0,0 +1,38 @@
+create table classes
+(
+    id          uuid                     not null primary key,
+    name        varchar                  not null,
+    invite_code varchar                  not null unique,
+    created_by  uuid                     not null,
+    created_at  timestamp with time zone not null default (now())
+);
+
+create table class_teachers
+(
+    class_id    uuid                     not null,
+    teacher_id  uuid                     not null,
+    assigned_at timestamp with time zone not null default (now()),
+    primary key (class_id, teacher_id)
+);
+
+create index class_teachers_teacher_id_idx on class_teachers (teacher_id);
+
+create table class_students
+(
+    class_id   uuid                     not null,
+    student_id uuid                     not null,
+    joined_at  timestamp with time zone not null default (now()),
+    primary key (class_id, student_id)
+);
+
+create index class_students_student_id_idx on class_students (student_id);
+
+-- Whether the teacher teaches a class the student attends
+create function is_student_teacher(student uuid, teacher uuid) returns bool as
+$$
+select exists(select 1
+              from class_students
+                       inner join class_teachers on class_students.class_id = class_teachers.class_id
+              where class_students.student_id = student
+                and class_teachers.teacher_id = teacher)
+$$ language sql stable;
@@ -0,0 +1,2 @@
+ALTER TABLE users
+    ADD avatar_mime VARCHAR;
@@ -0,0 +1,13 @@
+-- projects.thumbnail keeps the storage key of the thumbnail
+
+create table project_images
+(
+    id         uuid                     not null primary key,
+    project_id uuid                     not null,
+    position   int                      not null,
+    caption    varchar                           default (null),
+    object_key varchar                  not null,
+    created_at timestamp with time zone not null default (now())
+);
+
+create index project_images_project_id_idx on project_images (project_id, position);
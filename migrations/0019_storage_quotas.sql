@@ -0,0 +1,18 @@
+alter table users
+    add column storage_quota bigint not null default (104857600);
+
+alter table projects
+    add column storage_quota bigint not null default (52428800);
+
+-- Every object written to the storage, used to count quotas
+create table stored_files
+(
+    object_key varchar                  not null primary key,
+    owner_id   uuid                     not null,
+    project_id uuid                              default (null),
+    size       bigint                   not null,
+    created_at timestamp with time zone not null default (now())
+);
+
+create index stored_files_owner_id_idx on stored_files (owner_id);
+create index stored_files_project_id_idx on stored_files (project_id);
@@ -0,0 +1,25 @@
+create table project_releases
+(
+    id         uuid                     not null primary key,
+    project_id uuid                     not null,
+    -- Semantic version, without a leading "v"
+    tag        varchar                  not null,
+    notes      varchar                  not null default (''),
+    created_at timestamp with time zone not null default (now()),
+    updated_at timestamp with time zone not null default (now()),
+    unique (project_id, tag)
+);
+
+create table release_assets
+(
+    id           uuid                     not null primary key,
+    release_id   uuid                     not null,
+    name         varchar                  not null,
+    object_key   varchar                  not null,
+    content_type varchar                  not null,
+    size         bigint                   not null,
+    sha256       varchar                  not null,
+    downloads    bigint                   not null default (0),
+    created_at   timestamp with time zone not null default (now()),
+    unique (release_id, name)
+);
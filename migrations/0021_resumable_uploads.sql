@@ -0,0 +1,28 @@
+-- Uploads sent in chunks, removed once attached or expired
+create table resumable_uploads
+(
+    id           uuid                     not null primary key,
+    owner_id     uuid                     not null,
+    file_name    varchar                  not null,
+    content_type varchar                  not null,
+    length       bigint                   not null,
+    received     bigint                   not null default (0),
+    -- Digest of the received bytes so far, continued with every chunk
+    sha256_state bytea                    not null default (''),
+    -- Set on commit, when the chunks are joined
+    object_key   varchar                           default (null),
+    sha256       varchar                           default (null),
+    created_at   timestamp with time zone not null default (now()),
+    expires_at   timestamp with time zone not null
+);
+
+create index resumable_uploads_expires_at_idx on resumable_uploads (expires_at);
+
+create table resumable_chunks
+(
+    upload_id  uuid    not null,
+    "offset"   bigint  not null,
+    size       bigint  not null,
+    object_key varchar not null,
+    primary key (upload_id, "offset")
+);
@@ -0,0 +1,19 @@
+-- One like per user and project instead of one per project and one per user
+drop index projects_likes_project_id_idx;
+drop index projects_likes_user_id_idx;
+
+delete
+from projects_likes a
+    using projects_likes b
+where a.ctid < b.ctid
+  and a.project_id = b.project_id
+  and a.user_id = b.user_id;
+
+alter table projects_likes
+    add primary key (project_id, user_id);
+
+create index projects_likes_user_id_idx on projects_likes (user_id);
+
+-- Counters were never maintained by the API
+update projects
+set likes = (select count(*) from projects_likes where project_id = projects.id);
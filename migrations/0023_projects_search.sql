@@ -0,0 +1,50 @@
+create extension if not exists unaccent;
+
+-- Polish has no stock stemmer, words are unaccented and the API matches them by prefix
+create text search configuration codefest_polish (copy = simple);
+alter text search configuration codefest_polish
+    alter mapping for asciiword, asciihword, hword_asciipart, word, hword, hword_part with unaccent, simple;
+
+-- Polish words are also stemmed by a hunspell dictionary installed as polish.dict and polish.affix in the tsearch_data
+-- directory of the server. Stems keep their accents, so they are searched next to the unaccented words.
+do
+$$
+begin
+    create text search dictionary polish_hunspell (template = ispell, dictfile = polish, afffile = polish);
+exception
+    when config_file_error then
+        raise notice 'Polish hunspell dictionary is not installed, words are not stemmed';
+        -- Recognizes no words
+        create text search dictionary polish_hunspell (template = simple, accept = false);
+end
+$$;
+
+-- Only words known to the dictionary are indexed
+create text search configuration codefest_polish_stem (parser = default);
+alter text search configuration codefest_polish_stem
+    add mapping for asciiword, asciihword, hword_asciipart, word, hword, hword_part with polish_hunspell;
+
+alter table projects
+    add column search tsvector generated always as (
+            setweight(to_tsvector('codefest_polish', display_name), 'A') ||
+            setweight(to_tsvector('codefest_polish_stem', display_name), 'A') ||
+            setweight(to_tsvector('codefest_polish', coalesce(description, '')), 'B') ||
+            setweight(to_tsvector('codefest_polish_stem', coalesce(description, '')), 'B') ||
+            setweight(to_tsvector('codefest_polish', content), 'C') ||
+            setweight(to_tsvector('codefest_polish_stem', content), 'C')
+        ) stored;
+
+create index projects_search_idx on projects using gin (search);
+
+-- Matches the searched word by its prefix or any of its stems
+create function search_term(prefix text, word text) returns tsquery as
+$$
+select case
+           when stems is null then to_tsquery('codefest_polish', prefix)
+           else to_tsquery('codefest_polish', prefix) || stems
+           end
+from (select nullif(array_to_string(
+        array(select quote_literal(stem) from unnest(ts_lexize('polish_hunspell', word)) stem), ' | '), '')::tsquery as stems) lexized
+$$ language sql stable;
+
+create aggregate tsquery_and_agg(tsquery) (sfunc = tsquery_and, stype = tsquery);
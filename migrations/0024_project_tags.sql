@@ -0,0 +1,91 @@
+create type tag_category as enum ('language', 'framework', 'category');
+
+create table tags
+(
+    slug       varchar                  not null primary key,
+    name       varchar                  not null,
+    category   tag_category             not null,
+    created_at timestamp with time zone not null default (now())
+);
+
+-- Other spellings of tags, including slugs of merged tags
+create table tag_aliases
+(
+    alias varchar not null primary key,
+    tag   varchar not null
+);
+
+create index tag_aliases_tag_idx on tag_aliases (tag);
+
+create table project_tags
+(
+    project_id uuid    not null,
+    tag        varchar not null,
+    primary key (project_id, tag)
+);
+
+create index project_tags_tag_idx on project_tags (tag);
+
+insert into tags (slug, name, category)
+values ('c', 'C', 'language'),
+       ('cpp', 'C++', 'language'),
+       ('csharp', 'C#', 'language'),
+       ('go', 'Go', 'language'),
+       ('java', 'Java', 'language'),
+       ('javascript', 'JavaScript', 'language'),
+       ('kotlin', 'Kotlin', 'language'),
+       ('lua', 'Lua', 'language'),
+       ('php', 'PHP', 'language'),
+       ('python', 'Python', 'language'),
+       ('rust', 'Rust', 'language'),
+       ('swift', 'Swift', 'language'),
+       ('typescript', 'TypeScript', 'language'),
+       ('angular', 'Angular', 'framework'),
+       ('django', 'Django', 'framework'),
+       ('dotnet', '.NET', 'framework'),
+       ('flask', 'Flask', 'framework'),
+       ('flutter', 'Flutter', 'framework'),
+       ('godot', 'Godot', 'framework'),
+       ('laravel', 'Laravel', 'framework'),
+       ('nextjs', 'Next.js', 'framework'),
+       ('react', 'React', 'framework'),
+       ('spring', 'Spring', 'framework'),
+       ('svelte', 'Svelte', 'framework'),
+       ('unity', 'Unity', 'framework'),
+       ('unreal', 'Unreal Engine', 'framework'),
+       ('vue', 'Vue', 'framework'),
+       ('ai', 'Sztuczna inteligencja', 'category'),
+       ('bot', 'Bot', 'category'),
+       ('desktop', 'Aplikacja desktopowa', 'category'),
+       ('embedded', 'Systemy wbudowane', 'category'),
+       ('game', 'Gra', 'category'),
+       ('mobile', 'Aplikacja mobilna', 'category'),
+       ('tool', 'Narzędzie', 'category'),
+       ('web', 'Strona internetowa', 'category');
+
+insert into tag_aliases (alias, tag)
+values ('c++', 'cpp'),
+       ('c#', 'csharp'),
+       ('golang', 'go'),
+       ('js', 'javascript'),
+       ('node', 'javascript'),
+       ('nodejs', 'javascript'),
+       ('py', 'python'),
+       ('ts', 'typescript'),
+       ('.net', 'dotnet'),
+       ('next', 'nextjs'),
+       ('next.js', 'nextjs'),
+       ('reactjs', 'react'),
+       ('vuejs', 'vue'),
+       ('ue', 'unreal'),
+       ('ue5', 'unreal'),
+       ('unreal-engine', 'unreal'),
+       ('ml', 'ai'),
+       ('si', 'ai'),
+       ('gra', 'game'),
+       ('mobilna', 'mobile'),
+       ('android', 'mobile'),
+       ('arduino', 'embedded'),
+       ('strona', 'web'),
+       ('narzedzie', 'tool'),
+       ('narzędzie', 'tool');
@@ -0,0 +1,26 @@
+create type project_role as enum ('owner', 'maintainer', 'viewer');
+
+-- Invited users become members once they accept
+create table project_members
+(
+    project_id  uuid                     not null,
+    user_id     uuid                     not null,
+    role        project_role             not null,
+    invited_by  uuid                              default (null),
+    accepted    boolean                  not null default (false),
+    created_at  timestamp with time zone not null default (now()),
+    primary key (project_id, user_id)
+);
+
+create index project_members_user_id_idx on project_members (user_id);
+
+-- The user in the project URL stays its owner
+insert into project_members (project_id, user_id, role, accepted, created_at)
+select id, owner_id, 'owner', true, created_at
+from projects;
+
+-- Accepted members can see the project while it is private
+create function is_project_member(project uuid, member uuid) returns bool as
+$$
+select exists(select 1 from project_members where project_id = project and user_id = member and accepted = true)
+$$ language sql stable;
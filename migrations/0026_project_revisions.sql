@@ -0,0 +1,14 @@
+-- Every patch of the project content records the previous values of the changed fields
+create table project_revisions
+(
+    project_id    uuid                     not null,
+    number        integer                  not null,
+    author_id     uuid                     not null,
+    fields        varchar[]                not null,
+    previous      jsonb                    not null,
+    -- Tracked fields after the patch
+    snapshot      jsonb                    not null,
+    restored_from integer                           default (null),
+    created_at    timestamp with time zone not null default (now()),
+    primary key (project_id, number)
+);
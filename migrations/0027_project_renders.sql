@@ -0,0 +1,10 @@
+-- Rendered project content, valid as long as the hash matches the content
+create table project_renders
+(
+    project_id     uuid                     not null primary key,
+    content_sha256 varchar(64)              not null,
+    html           text                     not null,
+    toc            jsonb                    not null,
+    excerpt        text                     not null,
+    rendered_at    timestamp with time zone not null default (now())
+);
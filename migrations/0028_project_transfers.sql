@@ -0,0 +1,22 @@
+-- Previous addresses of renamed and transferred projects
+create table project_redirects
+(
+    owner_id   uuid                     not null,
+    name       varchar                  not null,
+    project_id uuid                     not null,
+    created_at timestamp with time zone not null default (now()),
+    primary key (owner_id, name)
+);
+
+create index project_redirects_project_id_idx on project_redirects (project_id);
+
+-- Transfers waiting for the recipient to accept
+create table project_transfers
+(
+    project_id   uuid                     not null primary key,
+    sender_id    uuid                     not null,
+    recipient_id uuid                     not null,
+    created_at   timestamp with time zone not null default (now())
+);
+
+create index project_transfers_recipient_id_idx on project_transfers (recipient_id);
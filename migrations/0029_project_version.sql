@@ -0,0 +1,3 @@
+-- Increased with every update of the project, sent as its ETag
+alter table projects
+    add column version integer not null default 1;
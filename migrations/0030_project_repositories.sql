@@ -0,0 +1,17 @@
+-- GitHub repository details of projects, refreshed periodically
+create table project_repositories
+(
+    project_id     uuid                     not null primary key,
+    -- The project link the details were fetched for
+    url            varchar(255)             not null,
+    -- False if the repository does not exist or is not public
+    found          boolean                  not null,
+    stars          integer                  not null default 0,
+    language       varchar(64),
+    license        varchar(128),
+    last_commit_at timestamp with time zone,
+    readme_html    text,
+    fetched_at     timestamp with time zone not null default (now())
+);
+
+create index project_repositories_fetched_at_idx on project_repositories (fetched_at);
@@ -0,0 +1,22 @@
+-- Unique views of the day, viewers are kept only as a keyed hash of the user or address
+create table project_views
+(
+    project_id  uuid        not null,
+    day         date        not null,
+    viewer_hash varchar(64) not null,
+    primary key (project_id, day, viewer_hash)
+);
+
+create index project_views_day_idx on project_views (day);
+
+alter table projects
+    add column views integer not null default 0;
+
+-- Trending scores of projects open to signed in users, recalculated periodically
+create table project_trending
+(
+    project_id uuid             not null primary key,
+    score      double precision not null
+);
+
+create index project_trending_score_idx on project_trending (score desc);
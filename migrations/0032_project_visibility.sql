@@ -0,0 +1,28 @@
+-- Unlisted projects are reachable by their address but left out of listings,
+-- school projects are only shown to signed in users
+create type project_visibility as enum ('public', 'unlisted', 'school', 'private');
+
+alter table projects
+    add column visibility project_visibility not null default 'public';
+
+update projects
+set visibility = 'private'
+where private = true;
+
+alter table projects
+    drop column private;
+
+-- Whether the project is shown in listings to the viewer, members are checked separately
+create function is_project_listed(visibility project_visibility, viewer uuid) returns bool as
+$$
+select visibility = 'public' or (visibility = 'school' and viewer is not null)
+$$ language sql immutable;
+
+-- Whether the viewer can open the project by its address, teachers of the owner read every project of theirs
+create function can_view_project(project uuid, owner uuid, visibility project_visibility, viewer uuid) returns bool as
+$$
+select visibility in ('public', 'unlisted')
+           or (visibility = 'school' and viewer is not null)
+           or is_project_member(project, viewer)
+           or is_student_teacher(owner, viewer)
+$$ language sql stable;
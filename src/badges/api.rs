@@ -0,0 +1,75 @@
+use serde_json::json;
+use sqlx::PgPool;
+use tracing::{info, warn};
+use uuid::Uuid;
+use warp::{reject, reply, Reply};
+
+use crate::error::Error;
+use crate::prelude::{web_json, WebResult};
+use crate::user;
+use crate::user::api::require_staff;
+
+use super::db;
+use super::models::{find_badge, BADGES};
+
+// GET v1/badges
+pub async fn list_badges() -> WebResult<impl Reply> {
+  web_json(&BADGES)
+}
+
+// POST v1/users/USERNAME/badges/BADGE
+pub async fn award_badge(username: String, badge_id: String, auth: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  set_badge(username, badge_id, auth, db_pool, true).await
+}
+
+// DELETE v1/users/USERNAME/badges/BADGE
+pub async fn revoke_badge(username: String, badge_id: String, auth: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  set_badge(username, badge_id, auth, db_pool, false).await
+}
+
+async fn set_badge(username: String, badge_id: String, auth: Option<Uuid>, db_pool: PgPool, award: bool) -> WebResult<impl Reply> {
+  let staff = require_staff(auth, &db_pool).await?;
+
+  // Only badges without rules can be given by hand
+  let badge = find_badge(&badge_id).ok_or(Error::NotFound)?;
+  if !badge.manual {
+    return Ok(reply::with_status(
+      reply::json(&json!({ "success": false, "message": "Ta odznaka jest przyznawana automatycznie" })),
+      warp::http::StatusCode::BAD_REQUEST,
+    ));
+  }
+
+  let username = username.to_lowercase().trim().to_string();
+  let receiver = match user::db::get_user(&username, &db_pool).await {
+    Ok(receiver) => receiver.ok_or(Error::UserNotFound)?,
+    Err(err) => {
+      warn!("Failed to get user '{username}': {err}");
+      return Err(reject::custom(Error::ServerProblem));
+    }
+  };
+
+  let result = match award {
+    true => db::award_badge(&receiver.id, badge.id, Some(staff.id), &db_pool).await,
+    false => db::revoke_badge(&receiver.id, badge.id, &db_pool).await,
+  };
+  let changed = match result {
+    Ok(changed) => changed,
+    Err(err) => {
+      warn!("Failed to set badge '{}' of {} to {award}: {err}", badge.id, &receiver.id);
+      return Err(reject::custom(Error::ServerProblem));
+    }
+  };
+
+  if !changed {
+    return Ok(reply::with_status(
+      reply::json(&json!({ "success": false, "message": "Brak zmian" })),
+      warp::http::StatusCode::CONFLICT,
+    ));
+  }
+
+  info!("Badge '{}' of {} set to {award} by {}", badge.id, &receiver.name, &staff.name);
+  Ok(reply::with_status(
+    reply::json(&json!({ "success": true, "message": "Operacja zakończona pomyślnie" })),
+    warp::http::StatusCode::OK,
+  ))
+}
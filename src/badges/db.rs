@@ -0,0 +1,50 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::models::UserBadge;
+
+pub async fn get_user_badges(
+  user_id: &Uuid,
+  pool: &PgPool,
+) -> Result<Vec<UserBadge>, Box<dyn std::error::Error + Send + Sync>> {
+  let query = r"SELECT badge_id, awarded_at FROM user_badges WHERE user_id = $1 ORDER BY awarded_at";
+
+  let result: Vec<UserBadge> = sqlx::query_as(query).bind(user_id).fetch_all(pool).await?;
+
+  Ok(result)
+}
+
+// Returns false if the user already had the badge
+pub async fn award_badge(
+  user_id: &Uuid,
+  badge_id: &str,
+  awarded_by: Option<Uuid>,
+  pool: &PgPool,
+) -> Result<bool, Box<dyn std::error::Error>> {
+  let query = r"INSERT INTO user_badges (user_id, badge_id, awarded_by) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING";
+
+  let result = sqlx::query(query)
+    .bind(user_id)
+    .bind(badge_id)
+    .bind(awarded_by)
+    .execute(pool)
+    .await?;
+
+  Ok(result.rows_affected() > 0)
+}
+
+pub async fn revoke_badge(
+  user_id: &Uuid,
+  badge_id: &str,
+  pool: &PgPool,
+) -> Result<bool, Box<dyn std::error::Error>> {
+  let query = r"DELETE FROM user_badges WHERE user_id = $1 AND badge_id = $2";
+
+  let result = sqlx::query(query)
+    .bind(user_id)
+    .bind(badge_id)
+    .execute(pool)
+    .await?;
+
+  Ok(result.rows_affected() > 0)
+}
@@ -0,0 +1,5 @@
+pub mod models;
+pub mod rules;
+pub mod db;
+pub mod api;
+pub mod routes;
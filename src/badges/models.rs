@@ -0,0 +1,86 @@
+use chrono::serde::ts_milliseconds;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use super::rules::{BadgeEvent, BadgeRule};
+
+#[derive(Serialize)]
+pub struct Badge {
+  pub id: &'static str,
+  pub name: &'static str,
+  pub description: &'static str,
+  // Awarded by staff instead of rules
+  pub manual: bool,
+  #[serde(skip)]
+  pub rule: Option<BadgeRule>,
+}
+
+impl Badge {
+  const fn automatic(id: &'static str, name: &'static str, description: &'static str, rule: BadgeRule) -> Badge {
+    Badge { id, name, description, manual: false, rule: Some(rule) }
+  }
+
+  const fn manual(id: &'static str, name: &'static str, description: &'static str) -> Badge {
+    Badge { id, name, description, manual: true, rule: None }
+  }
+}
+
+const COUNT_PROJECTS: &str = r"SELECT COUNT(*) FROM projects WHERE owner_id = $1";
+const COUNT_POSTS: &str = r"SELECT COUNT(*) FROM posts WHERE owner_id = $1";
+const COUNT_TOURNAMENT_PROJECTS: &str = r"SELECT COUNT(*) FROM projects WHERE owner_id = $1 AND tournament = true";
+const COUNT_FOLLOWERS: &str = r"SELECT COUNT(*) FROM users_follows WHERE followed_id = $1 AND accepted = true";
+const COUNT_LIKES_RECEIVED: &str = r"SELECT (SELECT coalesce(sum(likes), 0) FROM posts WHERE owner_id = $1) + (SELECT coalesce(sum(likes), 0) FROM projects WHERE owner_id = $1)";
+
+pub const BADGES: &[Badge] = &[
+  Badge::automatic("first_project", "Pierwszy projekt", "Utworzono pierwszy projekt.", BadgeRule {
+    event: BadgeEvent::ProjectCreated,
+    query: COUNT_PROJECTS,
+    threshold: 1,
+  }),
+  Badge::automatic("five_projects", "Twórca", "Utworzono 5 projektów.", BadgeRule {
+    event: BadgeEvent::ProjectCreated,
+    query: COUNT_PROJECTS,
+    threshold: 5,
+  }),
+  Badge::automatic("first_post", "Pierwszy wpis", "Opublikowano pierwszy wpis.", BadgeRule {
+    event: BadgeEvent::PostCreated,
+    query: COUNT_POSTS,
+    threshold: 1,
+  }),
+  Badge::automatic("ten_likes", "10 polubień", "Otrzymano 10 polubień.", BadgeRule {
+    event: BadgeEvent::LikeReceived,
+    query: COUNT_LIKES_RECEIVED,
+    threshold: 10,
+  }),
+  Badge::automatic("hundred_likes", "100 polubień", "Otrzymano 100 polubień.", BadgeRule {
+    event: BadgeEvent::LikeReceived,
+    query: COUNT_LIKES_RECEIVED,
+    threshold: 100,
+  }),
+  Badge::automatic("ten_followers", "Popularny", "Obserwuje Cię 10 osób.", BadgeRule {
+    event: BadgeEvent::Followed,
+    query: COUNT_FOLLOWERS,
+    threshold: 10,
+  }),
+  Badge::automatic("contest_participant", "Uczestnik konkursu", "Zgłoszono projekt na konkurs.", BadgeRule {
+    event: BadgeEvent::TournamentEntered,
+    query: COUNT_TOURNAMENT_PROJECTS,
+    threshold: 1,
+  }),
+  Badge::manual("contest_finalist", "Finalista konkursu", "Projekt zakwalifikował się do finału konkursu."),
+  Badge::manual("contest_winner", "Zwycięzca konkursu", "Projekt wygrał konkurs."),
+  Badge::manual("helper", "Pomocnik", "Wyróżnienie za pomoc innym uczestnikom."),
+];
+
+pub fn find_badge(id: &str) -> Option<&'static Badge> {
+  BADGES.iter().find(|badge| badge.id == id)
+}
+
+#[derive(Serialize, Deserialize, FromRow)]
+pub struct UserBadge {
+  #[serde(rename = "id")]
+  pub badge_id: String,
+  #[serde(with = "ts_milliseconds")]
+  pub awarded_at: DateTime<Utc>,
+}
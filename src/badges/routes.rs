@@ -0,0 +1,31 @@
+use sqlx::PgPool;
+use warp::Filter;
+
+use crate::{auth::header::with_auth, db::with_db};
+
+use super::api;
+
+pub fn routes(db_pool: &PgPool) -> impl Filter<Extract=impl warp::Reply, Error=warp::Rejection> + Clone {
+  let list = warp::path!("badges")
+    .and(warp::get())
+    .and(warp::path::end())
+    .and_then(api::list_badges);
+
+  let award = warp::path!("users" / String / "badges" / String)
+    .and(warp::post())
+    .and(warp::path::end())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(api::award_badge);
+
+  let revoke = warp::path!("users" / String / "badges" / String)
+    .and(warp::delete())
+    .and(warp::path::end())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(api::revoke_badge);
+
+  list
+    .or(award)
+    .or(revoke)
+}
@@ -0,0 +1,52 @@
+use sqlx::PgPool;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::db;
+use super::models::BADGES;
+
+// Events after which the rules are evaluated for the affected user
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BadgeEvent {
+  ProjectCreated,
+  PostCreated,
+  LikeReceived,
+  Followed,
+  TournamentEntered,
+}
+
+pub struct BadgeRule {
+  pub event: BadgeEvent,
+  // Counts the user's progress, binds the user id as $1
+  pub query: &'static str,
+  pub threshold: i64,
+}
+
+pub async fn evaluate(user_id: Uuid, event: BadgeEvent, pool: &PgPool) -> Result<(), Box<dyn std::error::Error>> {
+  for badge in BADGES {
+    let rule = match &badge.rule {
+      Some(rule) if rule.event == event => rule,
+      _ => continue,
+    };
+
+    let progress: i64 = sqlx::query_scalar(rule.query)
+      .bind(user_id)
+      .fetch_one(pool)
+      .await?;
+
+    if progress >= rule.threshold && db::award_badge(&user_id, badge.id, None, pool).await? {
+      info!("Awarded badge '{}' to {}", badge.id, &user_id);
+    }
+  }
+  Ok(())
+}
+
+// Evaluates the rules later in a separate async task, so the request is not slowed down.
+pub fn spawn_evaluate(user_id: Uuid, event: BadgeEvent, pool: &PgPool) {
+  let pool = pool.clone();
+  tokio::spawn(async move {
+    if let Err(err) = evaluate(user_id, event, &pool).await {
+      warn!("Failed to evaluate badges ({event:?}) for {user_id}: {err}");
+    }
+  });
+}
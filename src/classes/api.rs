@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+
+use serde_json::json;
+use sqlx::PgPool;
+use tracing::{info, warn};
+use uuid::Uuid;
+use warp::{reject, reply, Reply};
+
+use crate::error::Error;
+use crate::prelude::{web_json, WebResult};
+use crate::user;
+use crate::user::api::require_staff;
+use crate::user::models::User;
+use crate::utils::validate_display_name;
+
+use super::db;
+use super::models::{
+  generate_invite_code, ActivityQuery, ActivityResponse, ActivityTotals, CreateClassBody, JoinClassBody, RosterResponse,
+  RosterStudent,
+};
+
+async fn get_session_user(user_uid: Option<Uuid>, db_pool: &PgPool) -> WebResult<User> {
+  let user_uid = user_uid.ok_or(Error::Unauthorized)?;
+  match user::db::get_user_by_id(&user_uid, db_pool).await {
+    Ok(user) => Ok(user.ok_or(Error::Unauthorized)?),
+    Err(err) => {
+      warn!("Failed to get user {user_uid}: {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+async fn get_user_by_name(username: &str, db_pool: &PgPool) -> WebResult<User> {
+  let username = username.to_lowercase().trim().to_string();
+  match user::db::get_user(&username, db_pool).await {
+    Ok(user) => Ok(user.ok_or(Error::UserNotFound)?),
+    Err(err) => {
+      warn!("Failed to get user '{username}': {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// Only staff and teachers assigned to the class can oversee it. Returns the class name
+async fn require_oversight(class_id: &Uuid, user_uid: Option<Uuid>, db_pool: &PgPool) -> WebResult<(User, String)> {
+  let user = get_session_user(user_uid, db_pool).await?;
+
+  let name = match db::get_class_name(class_id, db_pool).await {
+    Ok(name) => name.ok_or(Error::NotFound)?,
+    Err(err) => {
+      warn!("Failed to get class {class_id}: {err}");
+      return Err(reject::custom(Error::ServerProblem));
+    }
+  };
+
+  if user.is_staff() {
+    return Ok((user, name));
+  }
+  match db::is_class_teacher(class_id, &user.id, db_pool).await {
+    Ok(true) => Ok((user, name)),
+    Ok(false) => Err(reject::custom(Error::NoPermission)),
+    Err(err) => {
+      warn!("Failed to check teacher of class {class_id}: {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+fn message(success: bool, message: &str, status: warp::http::StatusCode) -> reply::WithStatus<reply::Json> {
+  reply::with_status(
+    reply::json(&json!({ "success": success, "message": message })),
+    status,
+  )
+}
+
+// POST v1/classes
+pub async fn create_class(auth: Option<Uuid>, body: CreateClassBody, db_pool: PgPool) -> WebResult<impl Reply> {
+  let staff = require_staff(auth, &db_pool).await?;
+
+  let name = match validate_display_name(body.name) {
+    Ok(name) => name,
+    Err(err) => return Ok(message(false, &err, warp::http::StatusCode::BAD_REQUEST)),
+  };
+
+  let id = Uuid::new_v4();
+  let invite_code = generate_invite_code();
+  if let Err(err) = db::create_class(&id, &name, &invite_code, &staff.id, &db_pool).await {
+    warn!("Failed to create class '{name}': {err}");
+    return Err(reject::custom(Error::ServerProblem));
+  }
+
+  info!("Class '{name}' ({id}) created by {}", &staff.name);
+  Ok(reply::with_status(
+    reply::json(&json!({ "success": true, "id": id, "name": name, "invite_code": invite_code })),
+    warp::http::StatusCode::CREATED,
+  ))
+}
+
+// GET v1/classes
+pub async fn list_classes(auth: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  let user = get_session_user(auth, &db_pool).await?;
+
+  match db::get_classes(&user.id, user.is_staff(), &db_pool).await {
+    Ok(classes) => web_json(&classes),
+    Err(err) => {
+      warn!("Failed to get classes of {}: {err}", &user.id);
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// POST v1/classes/ID/teachers/USERNAME
+pub async fn assign_teacher(class_id: Uuid, username: String, auth: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  set_teacher(class_id, username, auth, db_pool, true).await
+}
+
+// DELETE v1/classes/ID/teachers/USERNAME
+pub async fn unassign_teacher(class_id: Uuid, username: String, auth: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  set_teacher(class_id, username, auth, db_pool, false).await
+}
+
+async fn set_teacher(class_id: Uuid, username: String, auth: Option<Uuid>, db_pool: PgPool, assign: bool) -> WebResult<impl Reply> {
+  let staff = require_staff(auth, &db_pool).await?;
+  let teacher = get_user_by_name(&username, &db_pool).await?;
+
+  if assign && !teacher.is_teacher() {
+    return Ok(message(false, "Użytkownik nie jest nauczycielem", warp::http::StatusCode::BAD_REQUEST));
+  }
+  match db::get_class_name(&class_id, &db_pool).await {
+    Ok(Some(_)) => {}
+    Ok(None) => return Err(reject::custom(Error::NotFound)),
+    Err(err) => {
+      warn!("Failed to get class {class_id}: {err}");
+      return Err(reject::custom(Error::ServerProblem));
+    }
+  }
+
+  match db::set_teacher(&class_id, &teacher.id, assign, &db_pool).await {
+    Ok(true) => {
+      info!("Teacher {} of class {class_id} set to {assign} by {}", &teacher.name, &staff.name);
+      Ok(message(true, "Operacja zakończona pomyślnie", warp::http::StatusCode::OK))
+    }
+    Ok(false) => Ok(message(false, "Brak zmian", warp::http::StatusCode::CONFLICT)),
+    Err(err) => {
+      warn!("Failed to set teacher {} of class {class_id}: {err}", &teacher.id);
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// POST v1/classes/join
+pub async fn join_class(auth: Option<Uuid>, body: JoinClassBody, db_pool: PgPool) -> WebResult<impl Reply> {
+  let user_uid = auth.ok_or(Error::Unauthorized)?;
+  let code = body.code.trim().to_uppercase();
+
+  match db::join_class(&code, &user_uid, &db_pool).await {
+    Ok(Some((class_id, name, true))) => {
+      info!("User {user_uid} joined class '{name}' ({class_id})");
+      Ok(message(true, &format!("Dołączono do klasy {name}"), warp::http::StatusCode::OK))
+    }
+    Ok(Some((_, name, false))) => Ok(message(false, &format!("Już należysz do klasy {name}"), warp::http::StatusCode::CONFLICT)),
+    Ok(None) => Ok(message(false, "Nieprawidłowy kod klasy", warp::http::StatusCode::NOT_FOUND)),
+    Err(err) => {
+      warn!("Failed to join class by {user_uid}: {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// DELETE v1/classes/ID/students/USERNAME
+pub async fn remove_student(class_id: Uuid, username: String, auth: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  let (overseer, _) = require_oversight(&class_id, auth, &db_pool).await?;
+  let student = get_user_by_name(&username, &db_pool).await?;
+
+  match db::remove_student(&class_id, &student.id, &db_pool).await {
+    Ok(true) => {
+      info!("Student {} removed from class {class_id} by {}", &student.name, &overseer.name);
+      Ok(message(true, "Usunięto ucznia z klasy", warp::http::StatusCode::OK))
+    }
+    Ok(false) => Ok(message(false, "Uczeń nie należy do klasy", warp::http::StatusCode::NOT_FOUND)),
+    Err(err) => {
+      warn!("Failed to remove student {} from class {class_id}: {err}", &student.id);
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// GET v1/classes/ID/roster
+pub async fn get_roster(class_id: Uuid, auth: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  let (_, name) = require_oversight(&class_id, auth, &db_pool).await?;
+
+  let students = match db::get_students(&class_id, &db_pool).await {
+    Ok(students) => students,
+    Err(err) => {
+      warn!("Failed to get students of class {class_id}: {err}");
+      return Err(reject::custom(Error::ServerProblem));
+    }
+  };
+
+  let student_ids = students.iter().map(|student| student.id).collect::<Vec<_>>();
+  let projects = match db::get_projects_of_owners(&student_ids, auth, &db_pool).await {
+    Ok(projects) => projects,
+    Err(err) => {
+      warn!("Failed to get projects of class {class_id}: {err}");
+      return Err(reject::custom(Error::ServerProblem));
+    }
+  };
+
+  let mut projects_by_owner: HashMap<Uuid, Vec<_>> = HashMap::new();
+  for project in projects {
+    projects_by_owner.entry(project.owner_id).or_default().push(project);
+  }
+
+  let students = students
+    .into_iter()
+    .map(|student| RosterStudent {
+      projects: projects_by_owner.remove(&student.id).unwrap_or_default(),
+      id: student.id,
+      name: student.name,
+      display_name: student.display_name,
+      joined_at: student.joined_at,
+    })
+    .collect::<Vec<_>>();
+
+  web_json(&RosterResponse {
+    class_id,
+    name,
+    read_only: true,
+    students,
+  })
+}
+
+// GET v1/classes/ID/activity?days=DAYS
+pub async fn get_activity(class_id: Uuid, query: ActivityQuery, auth: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  let (_, name) = require_oversight(&class_id, auth, &db_pool).await?;
+  let days = query.days.unwrap_or(7).clamp(1, 365);
+
+  let students = match db::get_activity(&class_id, days, &db_pool).await {
+    Ok(students) => students,
+    Err(err) => {
+      warn!("Failed to get activity of class {class_id}: {err}");
+      return Err(reject::custom(Error::ServerProblem));
+    }
+  };
+
+  let mut totals = ActivityTotals {
+    students: students.len(),
+    ..Default::default()
+  };
+  for student in &students {
+    if student.recent_projects > 0 || student.recent_posts > 0 {
+      totals.active_students += 1;
+    }
+    totals.projects += student.projects;
+    totals.posts += student.posts;
+    totals.likes_received += student.likes_received;
+    totals.recent_projects += student.recent_projects;
+    totals.recent_posts += student.recent_posts;
+  }
+
+  web_json(&ActivityResponse {
+    class_id,
+    name,
+    days,
+    totals,
+    students,
+  })
+}
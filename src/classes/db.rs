@@ -0,0 +1,219 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::project::models::ProjectCard;
+
+use super::models::{Class, ClassStudent, StudentActivity};
+
+pub async fn create_class(
+  id: &Uuid,
+  name: &String,
+  invite_code: &String,
+  created_by: &Uuid,
+  pool: &PgPool,
+) -> Result<(), Box<dyn std::error::Error>> {
+  let query = r"INSERT INTO classes (id, name, invite_code, created_by) VALUES ($1, $2, $3, $4)";
+
+  sqlx::query(query)
+    .bind(id)
+    .bind(name)
+    .bind(invite_code)
+    .bind(created_by)
+    .execute(pool)
+    .await?;
+
+  Ok(())
+}
+
+const GET_CLASSES_QUERY: &str = r"SELECT
+  classes.id,
+  classes.name,
+  CASE WHEN $2 OR EXISTS(SELECT 1 FROM class_teachers WHERE class_id = classes.id AND teacher_id = $1) THEN classes.invite_code END AS invite_code,
+  (SELECT COUNT(*) FROM class_students WHERE class_id = classes.id) AS students,
+  coalesce((SELECT array_agg(users.name ORDER BY users.name) FROM class_teachers INNER JOIN users ON class_teachers.teacher_id = users.id WHERE class_id = classes.id), '{}') AS teachers,
+  classes.created_at
+FROM classes
+WHERE $2
+   OR EXISTS(SELECT 1 FROM class_teachers WHERE class_id = classes.id AND teacher_id = $1)
+   OR EXISTS(SELECT 1 FROM class_students WHERE class_id = classes.id AND student_id = $1)
+ORDER BY classes.name";
+
+// Staff see every class, others only classes they teach or attend
+pub async fn get_classes(
+  user_id: &Uuid,
+  is_staff: bool,
+  pool: &PgPool,
+) -> Result<Vec<Class>, Box<dyn std::error::Error>> {
+  let result: Vec<Class> = sqlx::query_as(GET_CLASSES_QUERY)
+    .bind(user_id)
+    .bind(is_staff)
+    .fetch_all(pool)
+    .await?;
+
+  Ok(result)
+}
+
+pub async fn get_class_name(
+  class_id: &Uuid,
+  pool: &PgPool,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+  let result: Option<String> = sqlx::query_scalar(r"SELECT name FROM classes WHERE id = $1")
+    .bind(class_id)
+    .fetch_optional(pool)
+    .await?;
+
+  Ok(result)
+}
+
+pub async fn is_class_teacher(
+  class_id: &Uuid,
+  teacher_id: &Uuid,
+  pool: &PgPool,
+) -> Result<bool, Box<dyn std::error::Error>> {
+  let result: bool = sqlx::query_scalar(
+    r"SELECT EXISTS(SELECT 1 FROM class_teachers WHERE class_id = $1 AND teacher_id = $2)",
+  )
+  .bind(class_id)
+  .bind(teacher_id)
+  .fetch_one(pool)
+  .await?;
+
+  Ok(result)
+}
+
+// Whether the teacher teaches a class the student attends
+pub async fn is_student_teacher(
+  student_id: &Uuid,
+  teacher_id: &Uuid,
+  pool: &PgPool,
+) -> Result<bool, Box<dyn std::error::Error>> {
+  let result: bool = sqlx::query_scalar(r"SELECT is_student_teacher($1, $2)")
+    .bind(student_id)
+    .bind(teacher_id)
+    .fetch_one(pool)
+    .await?;
+
+  Ok(result)
+}
+
+pub async fn set_teacher(
+  class_id: &Uuid,
+  teacher_id: &Uuid,
+  assign: bool,
+  pool: &PgPool,
+) -> Result<bool, Box<dyn std::error::Error>> {
+  let query = match assign {
+    true => r"INSERT INTO class_teachers (class_id, teacher_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+    false => r"DELETE FROM class_teachers WHERE class_id = $1 AND teacher_id = $2",
+  };
+
+  let result = sqlx::query(query)
+    .bind(class_id)
+    .bind(teacher_id)
+    .execute(pool)
+    .await?;
+
+  Ok(result.rows_affected() > 0)
+}
+
+// Returns the joined class and whether the student was added now
+pub async fn join_class(
+  invite_code: &String,
+  student_id: &Uuid,
+  pool: &PgPool,
+) -> Result<Option<(Uuid, String, bool)>, Box<dyn std::error::Error>> {
+  let class: Option<(Uuid, String)> = sqlx::query_as(r"SELECT id, name FROM classes WHERE invite_code = $1")
+    .bind(invite_code)
+    .fetch_optional(pool)
+    .await?;
+
+  let (class_id, name) = match class {
+    Some(class) => class,
+    None => return Ok(None),
+  };
+
+  let result = sqlx::query(r"INSERT INTO class_students (class_id, student_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+    .bind(class_id)
+    .bind(student_id)
+    .execute(pool)
+    .await?;
+
+  Ok(Some((class_id, name, result.rows_affected() > 0)))
+}
+
+pub async fn remove_student(
+  class_id: &Uuid,
+  student_id: &Uuid,
+  pool: &PgPool,
+) -> Result<bool, Box<dyn std::error::Error>> {
+  let result = sqlx::query(r"DELETE FROM class_students WHERE class_id = $1 AND student_id = $2")
+    .bind(class_id)
+    .bind(student_id)
+    .execute(pool)
+    .await?;
+
+  Ok(result.rows_affected() > 0)
+}
+
+pub async fn get_students(
+  class_id: &Uuid,
+  pool: &PgPool,
+) -> Result<Vec<ClassStudent>, Box<dyn std::error::Error>> {
+  let query = r"SELECT users.id, users.name, users.display_name, class_students.joined_at FROM class_students INNER JOIN users ON class_students.student_id = users.id WHERE class_students.class_id = $1 ORDER BY users.display_name";
+
+  let result: Vec<ClassStudent> = sqlx::query_as(query).bind(class_id).fetch_all(pool).await?;
+
+  Ok(result)
+}
+
+// Every project of the given owners, unlisted and private ones included
+pub async fn get_projects_of_owners(
+  owner_ids: &[Uuid],
+  viewer: Option<Uuid>,
+  pool: &PgPool,
+) -> Result<Vec<ProjectCard>, Box<dyn std::error::Error>> {
+  let query = r#"SELECT projects.tournament, projects.id, projects.name, projects.display_name, projects.owner_id, users.name AS owner_name,
+  projects.visibility, projects.description, '/v1/media/' || projects.thumbnail AS thumbnail, projects.likes, projects.created_at, projects.updated_at,
+  EXISTS(SELECT 1 FROM projects_likes WHERE project_id = projects.id AND $2 IS NOT NULL AND user_id = $2) as "liked"
+FROM projects INNER JOIN users ON projects.owner_id = users.id
+WHERE projects.owner_id = ANY($1) ORDER BY projects.updated_at DESC"#;
+
+  let result: Vec<ProjectCard> = sqlx::query_as(query).bind(owner_ids).bind(viewer).fetch_all(pool).await?;
+
+  Ok(result)
+}
+
+const GET_ACTIVITY_QUERY: &str = r"SELECT
+  users.id,
+  users.name,
+  users.display_name,
+  (SELECT COUNT(*) FROM projects WHERE owner_id = users.id) AS projects,
+  (SELECT COUNT(*) FROM projects WHERE owner_id = users.id AND visibility = 'private') AS private_projects,
+  (SELECT COUNT(*) FROM projects WHERE owner_id = users.id AND tournament = true) AS tournament_projects,
+  (SELECT COUNT(*) FROM posts WHERE owner_id = users.id) AS posts,
+  (SELECT coalesce(sum(likes), 0) FROM posts WHERE owner_id = users.id)
+    + (SELECT coalesce(sum(likes), 0) FROM projects WHERE owner_id = users.id) AS likes_received,
+  (SELECT COUNT(*) FROM projects WHERE owner_id = users.id AND updated_at > now() - make_interval(days => $2)) AS recent_projects,
+  (SELECT COUNT(*) FROM posts WHERE owner_id = users.id AND created_at > now() - make_interval(days => $2)) AS recent_posts,
+  greatest(
+    (SELECT max(updated_at) FROM projects WHERE owner_id = users.id),
+    (SELECT max(created_at) FROM posts WHERE owner_id = users.id)
+  ) AS last_active_at
+FROM class_students
+INNER JOIN users ON class_students.student_id = users.id
+WHERE class_students.class_id = $1
+ORDER BY users.display_name";
+
+pub async fn get_activity(
+  class_id: &Uuid,
+  days: i32,
+  pool: &PgPool,
+) -> Result<Vec<StudentActivity>, Box<dyn std::error::Error>> {
+  let result: Vec<StudentActivity> = sqlx::query_as(GET_ACTIVITY_QUERY)
+    .bind(class_id)
+    .bind(days)
+    .fetch_all(pool)
+    .await?;
+
+  Ok(result)
+}
@@ -0,0 +1,4 @@
+pub mod models;
+pub mod db;
+pub mod api;
+pub mod routes;
@@ -0,0 +1,111 @@
+use chrono::serde::{ts_milliseconds, ts_milliseconds_option};
+use chrono::{DateTime, Utc};
+use rand::prelude::SliceRandom;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::project::models::ProjectCard;
+
+// Without look-alike characters (0/O, 1/I)
+const INVITE_CODE_CHARS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+pub const INVITE_CODE_LENGTH: usize = 8;
+
+pub fn generate_invite_code() -> String {
+  let mut randomizer = rand::thread_rng();
+  (0..INVITE_CODE_LENGTH)
+    .map(|_| *INVITE_CODE_CHARS.choose(&mut randomizer).unwrap() as char)
+    .collect()
+}
+
+#[derive(Serialize, FromRow)]
+pub struct Class {
+  pub id: Uuid,
+  pub name: String,
+  // Only visible to staff and teachers of the class
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub invite_code: Option<String>,
+  pub students: i64,
+  pub teachers: Vec<String>,
+  #[serde(with = "ts_milliseconds")]
+  pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateClassBody {
+  pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct JoinClassBody {
+  pub code: String,
+}
+
+#[derive(FromRow)]
+pub struct ClassStudent {
+  pub id: Uuid,
+  pub name: String,
+  pub display_name: String,
+  pub joined_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct RosterStudent {
+  pub id: Uuid,
+  pub name: String,
+  pub display_name: String,
+  #[serde(with = "ts_milliseconds")]
+  pub joined_at: DateTime<Utc>,
+  // Includes private projects, teachers can only read them
+  pub projects: Vec<ProjectCard>,
+}
+
+#[derive(Serialize)]
+pub struct RosterResponse {
+  pub class_id: Uuid,
+  pub name: String,
+  pub read_only: bool,
+  pub students: Vec<RosterStudent>,
+}
+
+#[derive(Deserialize)]
+pub struct ActivityQuery {
+  pub days: Option<i32>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct StudentActivity {
+  pub id: Uuid,
+  pub name: String,
+  pub display_name: String,
+  pub projects: i64,
+  pub private_projects: i64,
+  pub tournament_projects: i64,
+  pub posts: i64,
+  pub likes_received: i64,
+  // Projects created or updated and posts created in the last `days`
+  pub recent_projects: i64,
+  pub recent_posts: i64,
+  #[serde(with = "ts_milliseconds_option")]
+  pub last_active_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Default)]
+pub struct ActivityTotals {
+  pub students: usize,
+  pub active_students: usize,
+  pub projects: i64,
+  pub posts: i64,
+  pub likes_received: i64,
+  pub recent_projects: i64,
+  pub recent_posts: i64,
+}
+
+#[derive(Serialize)]
+pub struct ActivityResponse {
+  pub class_id: Uuid,
+  pub name: String,
+  pub days: i32,
+  pub totals: ActivityTotals,
+  pub students: Vec<StudentActivity>,
+}
@@ -0,0 +1,79 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+use warp::Filter;
+
+use crate::{auth::header::with_auth, db::with_db};
+
+use super::api;
+
+pub fn routes(db_pool: &PgPool) -> impl Filter<Extract=impl warp::Reply, Error=warp::Rejection> + Clone {
+  let create = warp::path!("classes")
+    .and(warp::post())
+    .and(warp::path::end())
+    .and(with_auth())
+    .and(warp::body::content_length_limit(1024 * 16))
+    .and(warp::body::json())
+    .and(with_db(db_pool.clone()))
+    .and_then(api::create_class);
+
+  let list = warp::path!("classes")
+    .and(warp::get())
+    .and(warp::path::end())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(api::list_classes);
+
+  let join = warp::path!("classes" / "join")
+    .and(warp::post())
+    .and(warp::path::end())
+    .and(with_auth())
+    .and(warp::body::content_length_limit(1024 * 16))
+    .and(warp::body::json())
+    .and(with_db(db_pool.clone()))
+    .and_then(api::join_class);
+
+  let assign_teacher = warp::path!("classes" / Uuid / "teachers" / String)
+    .and(warp::post())
+    .and(warp::path::end())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(api::assign_teacher);
+
+  let unassign_teacher = warp::path!("classes" / Uuid / "teachers" / String)
+    .and(warp::delete())
+    .and(warp::path::end())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(api::unassign_teacher);
+
+  let remove_student = warp::path!("classes" / Uuid / "students" / String)
+    .and(warp::delete())
+    .and(warp::path::end())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(api::remove_student);
+
+  let roster = warp::path!("classes" / Uuid / "roster")
+    .and(warp::get())
+    .and(warp::path::end())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(api::get_roster);
+
+  let activity = warp::path!("classes" / Uuid / "activity")
+    .and(warp::get())
+    .and(warp::path::end())
+    .and(warp::query())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(api::get_activity);
+
+  create
+    .or(list)
+    .or(join)
+    .or(assign_teacher)
+    .or(unassign_teacher)
+    .or(remove_student)
+    .or(roster)
+    .or(activity)
+}
@@ -37,6 +37,10 @@ pub enum Error {
   UserExists,
   #[error("Unauthorized")]
   Unauthorized,
+  #[error("Profile Is Private")]
+  ProfileRestricted,
+  #[error("Project Was Modified")]
+  VersionConflict,
 }
 
 #[derive(Serialize, Debug)]
@@ -57,6 +61,9 @@ pub async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible>
       Error::NoPermission => (StatusCode::UNAUTHORIZED, e.to_string()),
       Error::JWTToken => (StatusCode::UNAUTHORIZED, e.to_string()),
       Error::Unauthorized => (StatusCode::UNAUTHORIZED, e.to_string()),
+      Error::ProfileRestricted => (StatusCode::FORBIDDEN, e.to_string()),
+      Error::VersionConflict => (StatusCode::PRECONDITION_FAILED, e.to_string()),
+      Error::NotFound | Error::CannotFindFile => (StatusCode::NOT_FOUND, e.to_string()),
       Error::JWTTokenCreation => (
         StatusCode::INTERNAL_SERVER_ERROR,
         "Internal Server Error".to_string(),
@@ -72,6 +79,11 @@ pub async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible>
       StatusCode::METHOD_NOT_ALLOWED,
       "Method Not Allowed".to_string(),
     )
+  } else if err.find::<warp::reject::PayloadTooLarge>().is_some() {
+    (
+      StatusCode::PAYLOAD_TOO_LARGE,
+      "Payload Too Large".to_string(),
+    )
   } else if err.find::<warp::reject::InvalidQuery>().is_some() {
     (
       StatusCode::BAD_REQUEST,
@@ -1,38 +1,411 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
 use sqlx::PgPool;
 use tracing::warn;
+use warp::http::header::{
+  ACCEPT_RANGES, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG,
+  IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, LOCATION, RANGE,
+};
+use warp::http::{HeaderMap, Response, StatusCode};
+use warp::hyper::Body;
+use uuid::Uuid;
 use warp::{reject, Reply};
 
-use db::get_user_avatar_url;
+use db::get_user_avatar;
 use error::Error;
 
-use crate::{error, WebResult};
+use crate::{error, project, WebResult};
+use crate::project::releases::{validate_tag, visible_project};
+use crate::project::models::ProjectVisibility;
+use crate::storage::{SharedStorage, StoredObject};
+use crate::upload::avatar::{avatar_object_key, pick_avatar_size};
+use crate::upload::default_avatar::{identicon_png, initials_svg, DefaultAvatarFormat};
+use crate::upload::image::mime_for_extension;
+use crate::user::models::UserAvatar;
 use crate::user::db;
 
-pub async fn get_profile(username: String, db_pool: PgPool) -> WebResult<impl Reply> {
-  let avatar = match get_user_avatar_url(&username, &db_pool).await {
-    Ok(data) => {
-      if data.is_none() {
-        // Reject because the user does not exist
-        return Err(reject::custom(Error::NotFound));
-      }
-      let data = data.unwrap();
-      if data.0.is_none() {
-        // Reject because the user has no avatar
-        return Err(reject::custom(Error::CannotFindFile));
-      }
-      data.0.unwrap()
+// The name based URL changes content on every upload
+const CACHE_BY_NAME: &str = "public, max-age=60, must-revalidate";
+// The key based URL never changes content
+const CACHE_BY_KEY: &str = "public, max-age=31536000, immutable";
+// Media of hidden projects must not be kept by shared caches
+const CACHE_PRIVATE_BY_KEY: &str = "private, max-age=31536000, immutable";
+// Assets of hidden projects are not kept by any cache
+const CACHE_NONE: &str = "private, no-store";
+const PRESIGNED_EXPIRY: Duration = Duration::from_secs(2 * 60 * 60);
+
+#[derive(Deserialize, Debug)]
+pub struct AvatarQuery {
+  pub size: Option<u32>,
+  // Used only for generated avatars
+  pub format: Option<DefaultAvatarFormat>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RangeRequest {
+  Full,
+  // Inclusive bounds
+  Partial(u64, u64),
+  Unsatisfiable,
+}
+
+// Supports a single range only, multiple ranges are answered with the whole file.
+pub fn parse_range(header: &str, length: u64) -> RangeRequest {
+  let Some(spec) = header.trim().strip_prefix("bytes=") else {
+    return RangeRequest::Full;
+  };
+  if spec.contains(',') {
+    return RangeRequest::Full;
+  }
+  let Some((start, end)) = spec.trim().split_once('-') else {
+    return RangeRequest::Full;
+  };
+
+  let range = match (start.trim(), end.trim()) {
+    ("", "") => return RangeRequest::Full,
+    // Last n bytes
+    ("", suffix) => match suffix.parse::<u64>() {
+      Ok(0) => return RangeRequest::Unsatisfiable,
+      Ok(suffix) => (length.saturating_sub(suffix), length.saturating_sub(1)),
+      Err(_) => return RangeRequest::Full,
+    },
+    (start, "") => match start.parse::<u64>() {
+      Ok(start) => (start, length.saturating_sub(1)),
+      Err(_) => return RangeRequest::Full,
+    },
+    (start, end) => match (start.parse::<u64>(), end.parse::<u64>()) {
+      (Ok(start), Ok(end)) if start <= end => (start, end.min(length.saturating_sub(1))),
+      _ => return RangeRequest::Full,
+    },
+  };
+
+  if length == 0 || range.0 >= length {
+    return RangeRequest::Unsatisfiable;
+  }
+  RangeRequest::Partial(range.0, range.1)
+}
+
+// Checks If-None-Match style lists, weak validators compare equal.
+pub fn etag_matches(header: &str, etag: &str) -> bool {
+  header
+    .split(',')
+    .map(|tag| tag.trim())
+    .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+// Checks If-Match style lists, weak validators never match
+pub fn etag_matches_strong(header: &str, etag: &str) -> bool {
+  header.split(',').map(|tag| tag.trim()).any(|tag| tag == "*" || tag == etag)
+}
+
+fn header_str(headers: &HeaderMap, name: warp::http::header::HeaderName) -> Option<&str> {
+  headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+fn is_not_modified(headers: &HeaderMap, etag: &str, modified: SystemTime) -> bool {
+  // If-None-Match takes precedence over If-Modified-Since
+  if let Some(if_none_match) = header_str(headers, IF_NONE_MATCH) {
+    return etag_matches(if_none_match, etag);
+  }
+  match header_str(headers, IF_MODIFIED_SINCE).and_then(|since| httpdate::parse_http_date(since).ok()) {
+    Some(since) => modified <= since,
+    None => false,
+  }
+}
+
+// Lets the storage serve the file by itself when it can
+async fn redirect_to_storage(object_key: &str, storage: &SharedStorage) -> Option<Response<Body>> {
+  match storage.presigned_url(object_key, PRESIGNED_EXPIRY).await {
+    Ok(Some(url)) => Some(
+      Response::builder()
+        .status(StatusCode::TEMPORARY_REDIRECT)
+        .header(LOCATION, url)
+        // Must expire before the signature does
+        .header(CACHE_CONTROL, "private, max-age=3600")
+        .body(Body::empty())
+        .unwrap(),
+    ),
+    Ok(None) => None,
+    Err(err) => {
+      warn!("Failed to presign {object_key}: {err}");
+      None
+    }
+  }
+}
+
+// Serves the stored object with cache validators and range support
+async fn serve_object(
+  object_key: &str,
+  etag: &str,
+  mime: &str,
+  headers: &HeaderMap,
+  cache_control: &str,
+  storage: &SharedStorage,
+) -> WebResult<Response<Body>> {
+  let object = match storage.head(object_key).await {
+    Ok(Some(object)) => object,
+    Ok(None) => {
+      warn!("Missing file {object_key}");
+      return Err(reject::custom(Error::CannotFindFile));
     }
+    Err(err) => {
+      warn!("Failed to read file {object_key}: {err}");
+      return Err(reject::custom(Error::ServerProblem));
+    }
+  };
+  let StoredObject { size, content_type, last_modified } = object;
+  // Fall back to the expected type when the storage does not keep one
+  let mime = content_type.as_deref().unwrap_or(mime);
+  let source = Source::Stored(object_key, storage);
+  serve_source(source, size, last_modified, etag, mime, headers, cache_control).await
+}
+
+// Where the served bytes come from
+enum Source<'a> {
+  Generated(Vec<u8>),
+  // Streamed from the storage, never loaded into memory as a whole
+  Stored(&'a str, &'a SharedStorage),
+}
+
+impl Source<'_> {
+  // The bytes from start to end, both inclusive, as a response body
+  async fn body(self, start: u64, end: u64) -> WebResult<Body> {
+    match self {
+      Source::Generated(bytes) => Ok(Body::from(bytes[start as usize..=end as usize].to_vec())),
+      Source::Stored(object_key, storage) => match storage.get_range(object_key, start, end).await {
+        Ok(stream) => Ok(Body::wrap_stream(stream)),
+        Err(err) => {
+          warn!("Failed to read file {object_key}: {err}");
+          Err(reject::custom(Error::ServerProblem))
+        }
+      },
+    }
+  }
+}
+
+// Answers with the file, or with 304 and 206 to conditional and range requests
+async fn serve_source(
+  source: Source<'_>,
+  length: u64,
+  last_modified: SystemTime,
+  etag: &str,
+  mime: &str,
+  headers: &HeaderMap,
+  cache_control: &str,
+) -> WebResult<Response<Body>> {
+  // HTTP dates have a precision of seconds
+  let modified = UNIX_EPOCH
+    + Duration::from_secs(last_modified.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs());
+
+  let response = Response::builder()
+    .header(ETAG, etag)
+    .header(LAST_MODIFIED, httpdate::fmt_http_date(modified))
+    .header(CACHE_CONTROL, cache_control)
+    .header(ACCEPT_RANGES, "bytes");
+
+  if is_not_modified(headers, etag, modified) {
+    return Ok(response.status(StatusCode::NOT_MODIFIED).body(Body::empty()).unwrap());
+  }
+
+  // A range applies only to the same version of the file
+  let range = match (header_str(headers, RANGE), header_str(headers, IF_RANGE)) {
+    (Some(range), Some(if_range)) if if_range.trim() == etag => parse_range(range, length),
+    (Some(_), Some(_)) => RangeRequest::Full,
+    (Some(range), None) => parse_range(range, length),
+    (None, _) => RangeRequest::Full,
+  };
+
+  let response = response.header(CONTENT_TYPE, mime);
+  let response = match range {
+    RangeRequest::Full if length == 0 => response
+      .status(StatusCode::OK)
+      .header(CONTENT_LENGTH, 0)
+      .body(Body::empty()),
+    RangeRequest::Full => response
+      .status(StatusCode::OK)
+      .header(CONTENT_LENGTH, length)
+      .body(source.body(0, length - 1).await?),
+    RangeRequest::Partial(start, end) => response
+      .status(StatusCode::PARTIAL_CONTENT)
+      .header(CONTENT_RANGE, format!("bytes {start}-{end}/{length}"))
+      .header(CONTENT_LENGTH, end - start + 1)
+      .body(source.body(start, end).await?),
+    RangeRequest::Unsatisfiable => response
+      .status(StatusCode::RANGE_NOT_SATISFIABLE)
+      .header(CONTENT_RANGE, format!("bytes */{length}"))
+      .body(Body::empty()),
+  };
+
+  Ok(response.unwrap())
+}
+
+async fn find_avatar(username: &String, db_pool: &PgPool) -> WebResult<UserAvatar> {
+  match get_user_avatar(username, db_pool).await {
+    Ok(Some(avatar)) => Ok(avatar),
+    // Reject because the user does not exist
+    Ok(None) => Err(reject::custom(Error::NotFound)),
     Err(err) => {
       // Encoutered an database error while fetching
       warn!("Failed to fetch user avatar url: {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// GET v1/avatars/{name}?size=x
+pub async fn get_profile(
+  username: String,
+  query: AvatarQuery,
+  headers: HeaderMap,
+  db_pool: PgPool,
+  storage: SharedStorage,
+) -> WebResult<impl Reply> {
+  let avatar = find_avatar(&username, &db_pool).await?;
+  let size = pick_avatar_size(query.size);
+  match (avatar.avatar, avatar.avatar_mime) {
+    (Some(key), Some(mime)) => {
+      let etag = format!("\"{key}-{size}\"");
+      serve_object(&avatar_object_key(&key, size, &mime), &etag, &mime, &headers, CACHE_BY_NAME, &storage).await
+    }
+    // Generated on every request, it is cheap enough
+    _ => {
+      let format = query.format.unwrap_or_default();
+      // Initials change together with the display name
+      let version = hex::encode(Sha256::digest(format!("{}:{}", avatar.id, avatar.display_name)));
+      let (bytes, mime, extension) = match format {
+        DefaultAvatarFormat::Png => match identicon_png(&avatar.id, size) {
+          Ok(bytes) => (bytes, "image/png", "png"),
+          Err(err) => {
+            warn!("Failed to generate avatar of {username}: {err}");
+            return Err(reject::custom(Error::ServerProblem));
+          }
+        },
+        DefaultAvatarFormat::Svg => (
+          initials_svg(&avatar.id, &avatar.display_name, &avatar.name, size).into_bytes(),
+          "image/svg+xml",
+          "svg",
+        ),
+      };
+      let etag = format!("\"default-{}-{size}.{extension}\"", &version[..16]);
+      let length = bytes.len() as u64;
+      let source = Source::Generated(bytes);
+      serve_source(source, length, avatar.updated_at.into(), &etag, mime, &headers, CACHE_BY_NAME).await
+    }
+  }
+}
+
+// GET v1/avatars/{name}/{key}?size=x
+pub async fn get_profile_version(
+  username: String,
+  version: String,
+  query: AvatarQuery,
+  headers: HeaderMap,
+  db_pool: PgPool,
+  storage: SharedStorage,
+) -> WebResult<impl Reply> {
+  let avatar = find_avatar(&username, &db_pool).await?;
+  let (key, mime) = match (avatar.avatar, avatar.avatar_mime) {
+    (Some(key), Some(mime)) => (key, mime),
+    _ => return Err(reject::custom(Error::NotFound)),
+  };
+  // Replaced avatars are deleted from the storage
+  if key != version {
+    return Err(reject::custom(Error::NotFound));
+  }
+
+  let size = pick_avatar_size(query.size);
+  let object_key = avatar_object_key(&key, size, &mime);
+  if let Some(redirect) = redirect_to_storage(&object_key, &storage).await {
+    return Ok(redirect);
+  }
+
+  let etag = format!("\"{key}-{size}\"");
+  serve_object(&object_key, &etag, &mime, &headers, CACHE_BY_KEY, &storage).await
+}
+
+// GET v1/media/projects/{id}/{file}
+pub async fn get_project_media(
+  project_id: Uuid,
+  file: String,
+  headers: HeaderMap,
+  user_uid: Option<Uuid>,
+  db_pool: PgPool,
+  storage: SharedStorage,
+) -> WebResult<impl Reply> {
+  // File names are generated on upload and never change
+  let mime = match file.split_once('.') {
+    Some((id, extension)) if Uuid::parse_str(id).is_ok() => mime_for_extension(extension),
+    _ => None,
+  }
+  .ok_or(Error::NotFound)?;
+
+  // Media of hidden projects are served only to those who can open the project
+  let public = match project::db::get_media_access(&project_id, user_uid, &db_pool).await {
+    Ok(Some((public, true))) => public,
+    Ok(_) => return Err(reject::custom(Error::NotFound)),
+    Err(err) => {
+      warn!("Failed to check access to media of {project_id}: {err}");
       return Err(reject::custom(Error::ServerProblem));
     }
   };
-  
-  let filepath = format!("uploads/{}", avatar);
-  // Asynchronously read the file from the disk
-  let bytes = tokio::fs::read(filepath).await.unwrap();
+  let cache_control = if public { CACHE_BY_KEY } else { CACHE_PRIVATE_BY_KEY };
+
+  let object_key = format!("projects/{project_id}/{file}");
+  if let Some(redirect) = redirect_to_storage(&object_key, &storage).await {
+    return Ok(redirect);
+  }
+
+  let etag = format!("\"{file}\"");
+  serve_object(&object_key, &etag, mime, &headers, cache_control, &storage).await
+}
+
+// Resumed downloads are not counted again
+fn counts_as_download(headers: &HeaderMap) -> bool {
+  match header_str(headers, RANGE) {
+    Some(range) => range.trim().starts_with("bytes=0-"),
+    None => !headers.contains_key(IF_NONE_MATCH) && !headers.contains_key(IF_MODIFIED_SINCE),
+  }
+}
+
+// GET v1/projects/{name}/{project}/releases/{tag}/assets/{file}
+#[allow(clippy::too_many_arguments)]
+pub async fn get_release_asset(
+  username: String,
+  project_name: String,
+  tag: String,
+  name: String,
+  user_uid: Option<Uuid>,
+  headers: HeaderMap,
+  db_pool: PgPool,
+  storage: SharedStorage,
+) -> WebResult<impl Reply> {
+  let project = visible_project(&username, &project_name, &user_uid, &db_pool).await?;
+  let tag = validate_tag(&tag).map_err(|_| Error::NotFound)?;
+
+  let count = counts_as_download(&headers);
+  let asset = match project::db::download_release_asset(&project.id, &tag, &name, count, &db_pool).await {
+    Ok(Some(asset)) => asset,
+    Ok(None) => return Err(reject::custom(Error::NotFound)),
+    Err(err) => {
+      warn!("Failed to get release asset {name}: {err}");
+      return Err(reject::custom(Error::ServerProblem));
+    }
+  };
+
+  // The storage key ends with the file name, so browsers save it under the right name
+  if let Some(redirect) = redirect_to_storage(&asset.object_key, &storage).await {
+    return Ok(redirect);
+  }
 
-  // Return the file as a response
-  Ok(warp::reply::with_header(bytes, "content-type", "image/png"))
-}
\ No newline at end of file
+  let etag = format!("\"{}\"", &asset.sha256[..32]);
+  let cache_control = if project.visibility == ProjectVisibility::Public { CACHE_BY_NAME } else { CACHE_NONE };
+  let mut response =
+    serve_object(&asset.object_key, &etag, &asset.content_type, &headers, cache_control, &storage).await?;
+  response.headers_mut().insert(
+    CONTENT_DISPOSITION,
+    format!("attachment; filename=\"{}\"", asset.name).parse().unwrap(),
+  );
+  Ok(response)
+}
@@ -0,0 +1,129 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::header::ACCEPT;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+
+use super::models::RepoMetadata;
+use super::{FetchResult, RepoFetcher, RepoPath};
+
+const API_URL: &str = "https://api.github.com";
+// Longer READMEs are cut, so the cached rows stay small
+const README_MAX_SIZE: usize = 64 * 1024;
+
+#[derive(Deserialize)]
+struct RepoResponse {
+  stargazers_count: i32,
+  language: Option<String>,
+  license: Option<LicenseResponse>,
+}
+
+#[derive(Deserialize)]
+struct LicenseResponse {
+  name: String,
+  spdx_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CommitResponse {
+  commit: CommitDetails,
+}
+
+#[derive(Deserialize)]
+struct CommitDetails {
+  committer: Option<CommitSignature>,
+}
+
+#[derive(Deserialize)]
+struct CommitSignature {
+  date: DateTime<Utc>,
+}
+
+fn truncate_readme(mut readme: String) -> String {
+  if readme.len() > README_MAX_SIZE {
+    let mut end = README_MAX_SIZE;
+    while !readme.is_char_boundary(end) {
+      end -= 1;
+    }
+    readme.truncate(end);
+  }
+  readme
+}
+
+// Builds the metadata from the responses of the repository, its latest commit and the raw README
+pub fn parse_metadata(repo: &str, commits: Option<&str>, readme: Option<String>) -> FetchResult<RepoMetadata> {
+  let repo: RepoResponse = serde_json::from_str(repo)?;
+  let last_commit_at = match commits {
+    Some(commits) => {
+      let commits: Vec<CommitResponse> = serde_json::from_str(commits)?;
+      commits.into_iter().next().and_then(|commit| commit.commit.committer).map(|committer| committer.date)
+    }
+    None => None,
+  };
+  // Licenses GitHub does not recognise have the "NOASSERTION" id
+  let license = repo.license.map(|license| match license.spdx_id {
+    Some(spdx_id) if spdx_id != "NOASSERTION" => spdx_id,
+    _ => license.name,
+  });
+
+  Ok(RepoMetadata {
+    stars: repo.stargazers_count,
+    language: repo.language,
+    license,
+    last_commit_at,
+    readme: readme.map(truncate_readme),
+  })
+}
+
+// Fetches public repositories through the GitHub REST API.
+// Without a token the API allows only 60 requests an hour.
+pub struct GitHubFetcher {
+  client: Client,
+  token: Option<String>,
+}
+
+impl GitHubFetcher {
+  pub fn new(token: &str) -> Self {
+    GitHubFetcher {
+      client: Client::builder()
+        .user_agent("ckziu-codefest-api")
+        .build()
+        .expect("Failed to create GitHub client."),
+      token: Some(token.to_string()).filter(|token| !token.is_empty()),
+    }
+  }
+
+  // Returns None if the resource does not exist
+  async fn get(&self, path: &str, accept: &str) -> FetchResult<Option<String>> {
+    let mut request = self
+      .client
+      .get(format!("{API_URL}{path}"))
+      .header(ACCEPT, accept)
+      .header("X-GitHub-Api-Version", "2022-11-28");
+    if let Some(token) = &self.token {
+      request = request.bearer_auth(token);
+    }
+
+    let response = request.send().await?;
+    match response.status() {
+      // Empty repositories have no commits
+      StatusCode::NOT_FOUND | StatusCode::CONFLICT => Ok(None),
+      status if status.is_success() => Ok(Some(response.text().await?)),
+      status => Err(format!("GitHub request {path} failed with {status}").into()),
+    }
+  }
+}
+
+#[async_trait]
+impl RepoFetcher for GitHubFetcher {
+  async fn fetch(&self, repo: &RepoPath) -> FetchResult<Option<RepoMetadata>> {
+    let path = format!("/repos/{}/{}", repo.owner, repo.name);
+    let Some(details) = self.get(&path, "application/vnd.github+json").await? else {
+      return Ok(None);
+    };
+    let commits = self.get(&format!("{path}/commits?per_page=1"), "application/vnd.github+json").await?;
+    let readme = self.get(&format!("{path}/readme"), "application/vnd.github.raw+json").await?;
+
+    parse_metadata(&details, commits.as_deref(), readme).map(Some)
+  }
+}
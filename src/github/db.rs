@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::models::{RepoMetadata, Repository};
+
+// Details of the repository the project currently links to
+pub async fn get_repository(
+  project_id: &Uuid,
+  url: &str,
+  pool: &PgPool,
+) -> Result<Option<Repository>, Box<dyn std::error::Error + Send + Sync>> {
+  let query = r"SELECT url, stars, language, license, last_commit_at, readme_html, fetched_at
+FROM project_repositories
+WHERE project_id = $1 AND url = $2 AND found = true";
+
+  let result: Option<Repository> = sqlx::query_as(query).bind(project_id).bind(url).fetch_optional(pool).await?;
+
+  Ok(result)
+}
+
+// Projects with a link which was never fetched, has changed or is older than the age, those first
+pub async fn get_outdated_repositories(
+  max_age: Duration,
+  limit: i64,
+  pool: &PgPool,
+) -> Result<Vec<(Uuid, String)>, Box<dyn std::error::Error + Send + Sync>> {
+  let query = r"SELECT projects.id, projects.github_url
+FROM projects
+    LEFT JOIN project_repositories ON project_repositories.project_id = projects.id
+WHERE projects.github_url IS NOT NULL AND projects.github_url <> ''
+  AND (project_repositories.url IS DISTINCT FROM projects.github_url
+    OR project_repositories.fetched_at < now() - make_interval(secs => $1))
+ORDER BY project_repositories.url IS DISTINCT FROM projects.github_url DESC, project_repositories.fetched_at
+LIMIT $2";
+
+  let result: Vec<(Uuid, String)> = sqlx::query_as(query)
+    .bind(max_age.as_secs() as f64)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+  Ok(result)
+}
+
+// Missing repositories are stored as well, so they are not fetched again until they get old
+pub async fn store_repository(
+  project_id: &Uuid,
+  url: &str,
+  metadata: Option<&RepoMetadata>,
+  readme_html: Option<&str>,
+  pool: &PgPool,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+  let query = r"INSERT INTO project_repositories
+    (project_id, url, found, stars, language, license, last_commit_at, readme_html, fetched_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, now())
+ON CONFLICT (project_id) DO UPDATE SET url = excluded.url,
+  found = excluded.found,
+  stars = excluded.stars,
+  language = excluded.language,
+  license = excluded.license,
+  last_commit_at = excluded.last_commit_at,
+  readme_html = excluded.readme_html,
+  fetched_at = excluded.fetched_at";
+
+  sqlx::query(query)
+    .bind(project_id)
+    .bind(url)
+    .bind(metadata.is_some())
+    .bind(metadata.map_or(0, |metadata| metadata.stars))
+    .bind(metadata.and_then(|metadata| metadata.language.as_deref()))
+    .bind(metadata.and_then(|metadata| metadata.license.as_deref()))
+    .bind(metadata.and_then(|metadata| metadata.last_commit_at))
+    .bind(readme_html)
+    .execute(pool)
+    .await?;
+
+  Ok(())
+}
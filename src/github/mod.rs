@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use self::models::RepoMetadata;
+
+pub mod client;
+pub mod db;
+pub mod models;
+pub mod refresh;
+
+pub type FetchError = Box<dyn std::error::Error + Send + Sync>;
+pub type FetchResult<T> = Result<T, FetchError>;
+pub type SharedFetcher = Arc<dyn RepoFetcher>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepoPath {
+  pub owner: String,
+  pub name: String,
+}
+
+impl RepoPath {
+  pub fn url(&self) -> String {
+    format!("https://github.com/{}/{}", self.owner, self.name)
+  }
+}
+
+// Usernames and organizations have up to 39 letters, digits or single hyphens inside
+fn is_valid_owner(owner: &str) -> bool {
+  (1..=39).contains(&owner.len())
+    && owner.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    && !owner.starts_with('-')
+    && !owner.ends_with('-')
+}
+
+fn is_valid_repo(name: &str) -> bool {
+  (1..=100).contains(&name.len())
+    && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    && name != "."
+    && name != ".."
+}
+
+// Accepts links to the repository or any of its pages, e.g. "https://github.com/owner/repo/tree/main"
+pub fn parse_repo_url(url: &str) -> Option<RepoPath> {
+  let url = url.trim();
+  let url = url.split(['?', '#']).next().unwrap_or_default();
+  let url = url
+    .strip_prefix("https://")
+    .or_else(|| url.strip_prefix("http://"))
+    .unwrap_or(url);
+  let (host, path) = url.split_once('/')?;
+  if !host.eq_ignore_ascii_case("github.com") && !host.eq_ignore_ascii_case("www.github.com") {
+    return None;
+  }
+
+  let mut segments = path.split('/').filter(|segment| !segment.is_empty());
+  let owner = segments.next()?;
+  let name = segments.next()?;
+  let name = name.strip_suffix(".git").unwrap_or(name);
+  if !is_valid_owner(owner) || !is_valid_repo(name) {
+    return None;
+  }
+  Some(RepoPath {
+    owner: owner.to_string(),
+    name: name.to_string(),
+  })
+}
+
+#[async_trait]
+pub trait RepoFetcher: Send + Sync {
+  // Returns None if the repository does not exist or is not public
+  async fn fetch(&self, repo: &RepoPath) -> FetchResult<Option<RepoMetadata>>;
+}
+
+// Links which are not GitHub repositories are not fetched
+pub async fn fetch_repository(fetcher: &dyn RepoFetcher, url: &str) -> FetchResult<Option<RepoMetadata>> {
+  match parse_repo_url(url) {
+    Some(repo) => fetcher.fetch(&repo).await,
+    None => Ok(None),
+  }
+}
+
+pub fn create_fetcher() -> SharedFetcher {
+  Arc::new(client::GitHubFetcher::new(dotenv!("GITHUB_TOKEN")))
+}
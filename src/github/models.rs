@@ -0,0 +1,32 @@
+use chrono::serde::{ts_milliseconds, ts_milliseconds_option};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::prelude::FromRow;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RepoMetadata {
+  pub stars: i32,
+  // Primary language
+  pub language: Option<String>,
+  // SPDX id of the license, or its name if it has none
+  pub license: Option<String>,
+  // Of the default branch
+  pub last_commit_at: Option<DateTime<Utc>>,
+  // Markdown
+  pub readme: Option<String>,
+}
+
+#[derive(FromRow, Serialize)]
+pub struct Repository {
+  pub url: String,
+  pub stars: i32,
+  pub language: Option<String>,
+  pub license: Option<String>,
+  #[serde(with = "ts_milliseconds_option")]
+  pub last_commit_at: Option<DateTime<Utc>>,
+  // Sanitised HTML of the README
+  pub readme_html: Option<String>,
+
+  #[serde(with = "ts_milliseconds")]
+  pub fetched_at: DateTime<Utc>,
+}
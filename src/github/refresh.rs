@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+use tracing::{info, warn};
+
+use crate::markdown::render;
+
+use super::{db, fetch_repository, RepoFetcher, SharedFetcher};
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+// Details older than this are fetched again
+const MAX_AGE: Duration = Duration::from_secs(6 * 60 * 60);
+// Fits the unauthenticated limit of 60 requests an hour, three requests per repository
+const BATCH_SIZE: i64 = 1;
+const AUTHENTICATED_BATCH_SIZE: i64 = 30;
+
+// Returns the number of refreshed repositories, failed ones are fetched again on the next run
+pub async fn refresh_repositories(
+  fetcher: &dyn RepoFetcher,
+  batch_size: i64,
+  pool: &PgPool,
+) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+  let outdated = db::get_outdated_repositories(MAX_AGE, batch_size, pool).await?;
+
+  let mut refreshed = 0;
+  for (project_id, url) in outdated {
+    let metadata = match fetch_repository(fetcher, &url).await {
+      Ok(metadata) => metadata,
+      Err(err) => {
+        warn!("Failed to fetch repository {url} of {project_id}: {err}");
+        continue;
+      }
+    };
+    let readme_html = metadata
+      .as_ref()
+      .and_then(|metadata| metadata.readme.as_deref())
+      .map(|readme| render(readme).html);
+    db::store_repository(&project_id, &url, metadata.as_ref(), readme_html.as_deref(), pool).await?;
+    refreshed += 1;
+  }
+
+  Ok(refreshed)
+}
+
+pub fn spawn_refresher(fetcher: SharedFetcher, pool: PgPool) {
+  let batch_size = match dotenv!("GITHUB_TOKEN") {
+    "" => BATCH_SIZE,
+    _ => AUTHENTICATED_BATCH_SIZE,
+  };
+  tokio::spawn(async move {
+    let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+    loop {
+      interval.tick().await;
+      match refresh_repositories(fetcher.as_ref(), batch_size, &pool).await {
+        Ok(0) => {}
+        Ok(refreshed) => info!("Refreshed {refreshed} GitHub repositories"),
+        Err(err) => warn!("Failed to refresh GitHub repositories: {err}"),
+      }
+    }
+  });
+}
@@ -1,3 +1,5 @@
+#![recursion_limit = "256"]
+
 #[macro_use]
 extern crate dotenv_codegen;
 
@@ -17,19 +19,27 @@ use crate::prelude::WebResult;
 use crate::scrap::scrap_news;
 
 mod auth;
+mod badges;
 mod cache;
+mod classes;
 mod db;
 mod error;
 mod file;
 mod gateway;
+mod github;
 mod mail;
+mod markdown;
 mod models;
+mod pagination;
 mod panel;
 pub mod posts;
 mod prelude;
 mod project;
+mod proxy;
 mod routes;
 mod scrap;
+mod storage;
+mod tags;
 mod upload;
 mod user;
 mod utils;
@@ -91,7 +101,12 @@ async fn main() -> Result<(), error::Error> {
     let db_pool = db::create_pool().await.unwrap();
     let news = Arc::new(scrap_news().await.unwrap());
 
-    let routes = routes::routes(key, news, otp_codes, db_pool);
+    let storage = storage::create_storage();
+    storage::gc::spawn_collector(storage.clone(), db_pool.clone());
+    github::refresh::spawn_refresher(github::create_fetcher(), db_pool.clone());
+    project::trending::spawn_refresher(db_pool.clone());
+
+    let routes = routes::routes(key, news, otp_codes, db_pool, storage);
     info!("Created routes");
 
     match using_tls {
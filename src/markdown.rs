@@ -0,0 +1,155 @@
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+const EXCERPT_LENGTH: usize = 200;
+// Keeps user ids from clobbering ids of the page, as on GitHub
+const ID_PREFIX: &str = "user-content-";
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct TocEntry {
+  pub level: u8,
+  // Id of the heading in the HTML
+  pub id: String,
+  pub title: String,
+}
+
+#[derive(Debug)]
+pub struct Rendered {
+  pub html: String,
+  pub toc: Vec<TocEntry>,
+  pub excerpt: String,
+}
+
+static SANITIZER: LazyLock<ammonia::Builder<'static>> = LazyLock::new(|| {
+  let mut builder = ammonia::Builder::default();
+  builder
+    .add_tags(["input"])
+    .add_tag_attributes("input", ["checked"])
+    .set_tag_attribute_value("input", "type", "checkbox")
+    .set_tag_attribute_value("input", "disabled", "")
+    .add_tag_attributes("code", ["class"])
+    .add_generic_attributes(["id"])
+    .id_prefix(Some(ID_PREFIX))
+    .link_rel(Some("noopener noreferrer nofollow"));
+  builder
+});
+
+// CommonMark with the GitHub extensions
+fn options() -> Options {
+  Options::ENABLE_TABLES
+    | Options::ENABLE_STRIKETHROUGH
+    | Options::ENABLE_TASKLISTS
+    | Options::ENABLE_FOOTNOTES
+    | Options::ENABLE_GFM
+}
+
+// Anchor of the heading, unique within the document
+pub fn heading_slug(title: &str, used: &mut HashSet<String>) -> String {
+  let mut slug = String::new();
+  for c in title.trim().to_lowercase().chars() {
+    if c.is_alphanumeric() || c == '_' {
+      slug.push(c);
+    } else if (c.is_whitespace() || c == '-') && !slug.ends_with('-') {
+      slug.push('-');
+    }
+  }
+  let slug = match slug.trim_matches('-') {
+    "" => "section".to_string(),
+    slug => slug.to_string(),
+  };
+
+  let mut unique = slug.clone();
+  let mut suffix = 1;
+  while used.contains(&unique) {
+    unique = format!("{slug}-{suffix}");
+    suffix += 1;
+  }
+  used.insert(unique.clone());
+  unique
+}
+
+// Cuts the text at a word boundary
+fn truncate_excerpt(text: &str) -> String {
+  if text.chars().count() <= EXCERPT_LENGTH {
+    return text.to_string();
+  }
+  let cut: String = text.chars().take(EXCERPT_LENGTH).collect();
+  let cut = match cut.rfind(' ') {
+    Some(space) => &cut[..space],
+    None => &cut,
+  };
+  format!("{}…", cut.trim_end_matches(|c: char| !c.is_alphanumeric()))
+}
+
+// Renders the markdown to sanitised HTML with a table of contents and a plain text excerpt
+pub fn render(source: &str) -> Rendered {
+  let mut events: Vec<Event> = Parser::new_ext(source, options()).collect();
+
+  let mut toc = Vec::new();
+  let mut used = HashSet::new();
+  let mut heading: Option<(usize, String)> = None;
+  let mut excerpt = String::new();
+  // Headings, code blocks and raw HTML are left out of the excerpt
+  let mut skipped = 0;
+  let mut inline_script = false;
+
+  for index in 0..events.len() {
+    match &events[index] {
+      Event::Start(Tag::Heading { .. }) => {
+        heading = Some((index, String::new()));
+        skipped += 1;
+      }
+      &Event::End(TagEnd::Heading(level)) => {
+        if let Some((start, title)) = heading.take() {
+          let title = title.trim().to_string();
+          let slug = heading_slug(&title, &mut used);
+          if let Event::Start(Tag::Heading { id, .. }) = &mut events[start] {
+            *id = Some(CowStr::from(slug.clone()));
+          }
+          toc.push(TocEntry { level: level as u8, id: format!("{ID_PREFIX}{slug}"), title });
+        }
+        skipped -= 1;
+      }
+      Event::Start(Tag::CodeBlock(_) | Tag::HtmlBlock) => skipped += 1,
+      Event::End(TagEnd::CodeBlock | TagEnd::HtmlBlock) => skipped -= 1,
+      Event::Text(text) | Event::Code(text) => {
+        if let Some((_, title)) = &mut heading {
+          title.push_str(text);
+        }
+        if skipped == 0 && !inline_script {
+          excerpt.push_str(text);
+        }
+      }
+      Event::InlineHtml(html) => {
+        let html = html.to_lowercase();
+        if html.starts_with("<script") || html.starts_with("<style") {
+          inline_script = true;
+        } else if html.starts_with("</script") || html.starts_with("</style") {
+          inline_script = false;
+        }
+      }
+      Event::SoftBreak | Event::HardBreak | Event::End(TagEnd::Paragraph | TagEnd::Item | TagEnd::TableCell) => {
+        excerpt.push(' ');
+      }
+      _ => {}
+    }
+  }
+
+  let mut unsafe_html = String::new();
+  html::push_html(&mut unsafe_html, events.into_iter());
+
+  Rendered {
+    html: SANITIZER.clean(&unsafe_html).to_string(),
+    toc,
+    excerpt: truncate_excerpt(&excerpt.split_whitespace().collect::<Vec<_>>().join(" ")),
+  }
+}
+
+// Identifies the source of a cached rendering
+pub fn source_hash(source: &str) -> String {
+  hex::encode(Sha256::digest(source.as_bytes()))
+}
@@ -0,0 +1,59 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+pub const MAX_PAGE_SIZE: i64 = 50;
+
+#[derive(Deserialize)]
+pub struct CursorQuery {
+  pub cursor: Option<String>,
+  pub limit: Option<i64>,
+}
+
+impl CursorQuery {
+  pub fn limit(&self) -> i64 {
+    self.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+  }
+}
+
+#[derive(Serialize)]
+pub struct Page<T> {
+  pub items: Vec<T>,
+  // Pass as `?cursor=` to get the next page. None on the last page
+  pub cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+  // Expects `limit + 1` rows, the extra row only tells there is a next page.
+  pub fn from_rows(mut rows: Vec<T>, limit: i64, cursor: impl Fn(&T) -> String) -> Page<T> {
+    let has_next = rows.len() as i64 > limit;
+    rows.truncate(limit as usize);
+    let cursor = match has_next {
+      true => rows.last().map(cursor),
+      false => None,
+    };
+    Page { items: rows, cursor }
+  }
+}
+
+// Cursor of rows ordered by a timestamp, with the id as a tiebreaker.
+pub fn encode_time_cursor(time: &DateTime<Utc>, id: &Uuid) -> String {
+  format!("{}_{}", time.timestamp_micros(), id)
+}
+
+pub fn decode_time_cursor(cursor: &str) -> Option<(DateTime<Utc>, Uuid)> {
+  let (micros, id) = cursor.split_once('_')?;
+  let time = DateTime::from_timestamp_micros(micros.parse().ok()?)?;
+  Some((time, id.parse().ok()?))
+}
+
+// Cursor of rows ordered by a counter, with the id as a tiebreaker.
+pub fn encode_count_cursor(count: i64, id: &Uuid) -> String {
+  format!("{count}_{id}")
+}
+
+pub fn decode_count_cursor(cursor: &str) -> Option<(i64, Uuid)> {
+  let (count, id) = cursor.split_once('_')?;
+  Some((count.parse().ok()?, id.parse().ok()?))
+}
@@ -8,6 +8,7 @@ use tracing::{info, warn};
 use uuid::Uuid;
 use warp::{reply, Reply};
 
+use crate::badges::rules::{spawn_evaluate, BadgeEvent};
 use crate::error::Error;
 use crate::prelude::WebResult;
 
@@ -56,22 +57,56 @@ pub struct PostOwner {
     pub display_name: String,
     pub name: String,
     pub flags: i32,
+    pub badges: Vec<String>,
+}
+
+pub type PostWithOwnerRow = (
+    i32,
+    String,
+    DateTime<Utc>,
+    i32,
+    Uuid,
+    String,
+    String,
+    i32,
+    Vec<String>,
+    bool,
+);
+
+impl From<PostWithOwnerRow> for PostWithOwner {
+    fn from(
+        (
+            id,
+            content,
+            created_at,
+            likes,
+            owner_id,
+            owner_name,
+            owner_display_name,
+            owner_flags,
+            owner_badges,
+            liked,
+        ): PostWithOwnerRow,
+    ) -> Self {
+        PostWithOwner {
+            id,
+            content,
+            owner: PostOwner {
+                id: owner_id,
+                name: owner_name,
+                display_name: owner_display_name,
+                flags: owner_flags,
+                badges: owner_badges,
+            },
+            created_at,
+            likes,
+            is_liked_by_user: liked,
+        }
+    }
 }
 
 pub async fn get_posts(user_uid: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
-    type PostTuple = (
-        i32,
-        String,
-        DateTime<Utc>,
-        i32,
-        Uuid,
-        String,
-        String,
-        i32,
-        bool,
-    );
-
-    let posts: Vec<PostTuple> = sqlx::query_as(
+    let posts: Vec<PostWithOwnerRow> = sqlx::query_as(
             r#"
             SELECT
                 posts.id,
@@ -83,11 +118,16 @@ pub async fn get_posts(user_uid: Option<Uuid>, db_pool: PgPool) -> WebResult<imp
                 users.name as "owner.name",
                 users.display_name as "owner.display_name",
                 users.flags as "owner.flags",
+                coalesce((SELECT array_agg(badge_id ORDER BY awarded_at) FROM user_badges WHERE user_id = users.id), '{}') as "owner.badges",
 
                 EXISTS(SELECT 1 FROM posts_likes WHERE post_id = posts.id AND $1 IS NOT NULL AND user_id = $1) as "liked"
 
             FROM posts
             JOIN users ON posts.owner_id = users.id
+
+            WHERE can_view_profile(users.id, $1)
+                AND (users.hide_posts = false OR users.id = $1)
+                AND NOT has_blocked($1, users.id)
             
             ORDER BY posts.created_at DESC
             "#,
@@ -102,31 +142,7 @@ pub async fn get_posts(user_uid: Option<Uuid>, db_pool: PgPool) -> WebResult<imp
 
     let posts = posts
         .into_iter()
-        .map(
-            |(
-                id,
-                content,
-                created_at,
-                likes,
-                owner_id,
-                owner_name,
-                owner_display_name,
-                owner_flags,
-                liked,
-            )| PostWithOwner {
-                id,
-                content,
-                owner: PostOwner {
-                    id: owner_id,
-                    name: owner_name,
-                    display_name: owner_display_name,
-                    flags: owner_flags,
-                },
-                created_at,
-                likes,
-                is_liked_by_user: liked,
-            },
-        )
+        .map(PostWithOwner::from)
         .collect::<Vec<_>>();
 
     Ok(json(&posts))
@@ -170,6 +186,7 @@ pub async fn create_post(
     })?;
 
     info!("Post created: {} by {}", created_post.id, user_id);
+    spawn_evaluate(user_id, BadgeEvent::PostCreated, &db_pool);
 
     // Operation success, return the created post
     Ok(reply::with_status(
@@ -206,6 +223,29 @@ pub async fn set_like_post(
         ));
     }
 
+    // Check if the post owner blocked the user
+    let (owner_id, blocked): (Uuid, bool) = sqlx::query_as(
+        r#"
+    SELECT owner_id, has_blocked(owner_id, $2) FROM posts WHERE id = $1
+    "#,
+    )
+    .bind(post_uid)
+    .bind(user_uid)
+    .fetch_one(&db_pool)
+    .await
+    .map_err(|err| {
+        warn!("Failed to check is user blocked: {err}");
+        Error::ServerProblem
+    })?;
+
+    // Reject if the user is blocked by the post owner
+    if blocked {
+        return Ok(reply::with_status(
+            reply::json(&json!({ "success": false, "message": "Nie możesz polubić tego wpisu" })),
+            warp::http::StatusCode::FORBIDDEN,
+        ));
+    }
+
     // Check if user already liked the post
     let already_liked: bool = sqlx::query_scalar(
         r#"
@@ -260,6 +300,8 @@ pub async fn set_like_post(
             warn!("Failed to increment post likes counter: {err}");
             Error::ServerProblem
         })?;
+
+        spawn_evaluate(owner_id, BadgeEvent::LikeReceived, &db_pool);
     } else {
         if !already_liked {
             return Ok(reply::with_status(
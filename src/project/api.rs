@@ -1,3 +1,5 @@
+use std::net::IpAddr;
+
 use chrono::serde::ts_milliseconds;
 use chrono::{DateTime, Datelike, Utc};
 use serde::{Deserialize, Serialize};
@@ -5,19 +7,30 @@ use sqlx::prelude::FromRow;
 use sqlx::PgPool;
 use tracing::{info, warn};
 use uuid::Uuid;
-use warp::reply::json;
+use warp::http::header::ETAG;
+use warp::reply::{json, Response};
 use warp::{reject, Reply};
 
 use project::db;
 
+use crate::badges::rules::{spawn_evaluate, BadgeEvent};
 use crate::error::Error;
+use crate::file::etag_matches_strong;
+use crate::github;
+use crate::github::models::Repository;
+use crate::markdown::{render, source_hash, Rendered, TocEntry};
 use crate::prelude::{web_err, web_json};
-use crate::project::models::Project;
+use crate::storage::quota::delete_file;
+use crate::storage::SharedStorage;
+use crate::tags::api::resolve_project_tags;
+use crate::project::members::project_access;
+use crate::project::models::{media_url, project_etag, Project, ProjectImage, ProjectMember, ProjectVisibility};
 use crate::project::responses::PostProjectBody;
-use crate::user::api::is_authorized;
-use crate::utils::{current_millis, validate_description, validate_display_name, validate_name};
+use crate::project::trending::spawn_record_view;
+use crate::utils::{current_millis, validate_description, validate_display_name, validate_github_url, validate_name};
 use crate::{error, project, WebResult};
 
+use super::db::PatchResult;
 use super::responses::PostProjectResponse;
 
 #[derive(Serialize, FromRow)]
@@ -25,27 +38,81 @@ pub struct FullProjectResponse {
     pub id: Uuid,
     pub name: String,
     pub display_name: String,
-    pub private: bool,
+    pub visibility: ProjectVisibility,
     pub owner_id: Uuid,
     pub owner_name: String,
     pub url: String,
     pub description: Option<String>,
     pub github_url: Option<String>,
     pub website_url: Option<String>,
+    pub thumbnail: Option<String>,
+    // Loaded only for a single project
+    #[sqlx(skip)]
+    pub gallery: Vec<ProjectImage>,
+    // Accepted members with their roles, loaded only for a single project
+    #[sqlx(skip)]
+    pub members: Vec<ProjectMember>,
     #[serde(with = "ts_milliseconds")]
     pub created_at: DateTime<Utc>,
     #[serde(with = "ts_milliseconds")]
     pub updated_at: DateTime<Utc>,
 
     pub likes: i32,
+    // Liked by the viewer
+    pub liked: bool,
+    pub tags: Vec<String>,
+    pub views: i32,
 
     pub content: String,
+    // Sanitised HTML of the content with its headings and a plain text excerpt, loaded only for a single project
+    #[sqlx(skip)]
+    pub content_html: String,
+    #[sqlx(skip)]
+    pub toc: Vec<TocEntry>,
+    #[sqlx(skip)]
+    pub excerpt: String,
+    // Details of the linked GitHub repository once fetched, loaded only for a single project
+    #[sqlx(skip)]
+    pub repository: Option<Repository>,
     pub tournament: bool,
 }
 
+pub(super) fn with_etag(reply: impl Reply, version: i32) -> Response {
+    let mut response = reply.into_response();
+    if let Ok(etag) = project_etag(version).parse() {
+        response.headers_mut().insert(ETAG, etag);
+    }
+    response
+}
+
+// Version the update is conditional on, rejects outdated and weak If-Match headers
+pub fn expected_version(version: i32, if_match: Option<&str>) -> Result<Option<i32>, error::Error> {
+    match if_match {
+        Some(if_match) if etag_matches_strong(if_match, &project_etag(version)) => Ok(Some(version)),
+        Some(_) => Err(error::Error::VersionConflict),
+        None => Ok(None),
+    }
+}
+
+// Renders the content unless the rendering is cached
+async fn rendered_content(project: &Project, db_pool: &PgPool) -> Rendered {
+    let hash = source_hash(&project.content);
+    match db::get_render(&project.id, &hash, db_pool).await {
+        Ok(Some(rendered)) => return rendered,
+        Ok(None) => {}
+        Err(err) => warn!("Failed to get rendered content of {}: {err}", project.id),
+    }
+
+    let rendered = render(&project.content);
+    if let Err(err) = db::store_render(&project.id, &hash, &rendered, db_pool).await {
+        warn!("Failed to store rendered content of {}: {err}", project.id);
+    }
+    rendered
+}
+
 // GET v1/projects
-pub async fn new_projects(db_pool: PgPool) -> WebResult<impl Reply> {
-    match db::get_newest_projects(&db_pool).await {
+pub async fn new_projects(user_uid: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+    match db::get_newest_projects(user_uid, &db_pool).await {
         Ok(projects) => web_json(&projects),
         Err(err) => {
             warn!("Error getting newest projects: {err}");
@@ -55,8 +122,8 @@ pub async fn new_projects(db_pool: PgPool) -> WebResult<impl Reply> {
 }
 
 // GET v1/contestprojects
-pub async fn contest_projects(db_pool: PgPool) -> WebResult<impl Reply> {
-    match db::get_contest_projects(&db_pool).await {
+pub async fn contest_projects(user_uid: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+    match db::get_contest_projects(user_uid, &db_pool).await {
         Ok(projects) => web_json(&projects),
         Err(err) => {
             warn!("Error getting contest projects: {err}");
@@ -173,58 +240,97 @@ pub async fn get_project(
     username: String,
     project_name: String,
     user_uid: Option<Uuid>,
+    addr: Option<IpAddr>,
     db_pool: PgPool,
 ) -> WebResult<impl Reply> {
-    let can_see_private = is_authorized(&user_uid, &username, &db_pool).await;
+    let (project, role) = project_access(&username, &project_name, &user_uid, &db_pool).await?;
+    // Members are not counted
+    if role.is_none() {
+        spawn_record_view(project.id, user_uid, addr, &db_pool);
+    }
 
-    match db::get_project_by_ownername_projectname(
-        &username,
-        &project_name,
-        can_see_private,
-        &db_pool,
-    )
-    .await
-    {
-        Ok(response) => {
-            if let Some(project) = response {
-                let response = FullProjectResponse {
-                    id: project.id,
-                    name: project.name,
-                    display_name: project.display_name,
-                    owner_id: project.owner_id,
-                    owner_name: username.clone(),
-                    private: project.private,
-                    description: project.description,
-                    content: project.content,
-                    github_url: project.github_url,
-                    website_url: project.website_url,
-                    likes: project.likes,
-                    created_at: project.created_at,
-                    updated_at: project.updated_at,
-                    tournament: project.tournament,
-                    url: format!("https://ckziucodefest.pl/p/{}/{}", &username, &project_name),
-                };
-                return Ok(json(&response));
-            }
+    let gallery = match db::get_gallery(&project.id, &db_pool).await {
+        Ok(gallery) => gallery,
+        Err(err) => {
+            warn!("Failed to get project gallery: {err}");
+            return Err(reject::custom(error::Error::ServerProblem));
+        }
+    };
+    let members = match db::get_members(&project.id, false, &db_pool).await {
+        Ok(members) => members,
+        Err(err) => {
+            warn!("Failed to get project members: {err}");
+            return Err(reject::custom(error::Error::ServerProblem));
         }
+    };
+    let liked = match db::is_project_liked(&project.id, &user_uid, &db_pool).await {
+        Ok(liked) => liked,
         Err(err) => {
-            warn!("Detected server problem @ DB PROJECT GET: {}", err);
+            warn!("Failed to check project like: {err}");
             return Err(reject::custom(error::Error::ServerProblem));
         }
-    }
-
-    Err(reject::custom(error::Error::ProjectNotFound))
+    };
+    let tags = match db::get_project_tags(&project.id, &db_pool).await {
+        Ok(tags) => tags,
+        Err(err) => {
+            warn!("Failed to get project tags: {err}");
+            return Err(reject::custom(error::Error::ServerProblem));
+        }
+    };
+    let repository = match project.github_url.as_deref() {
+        Some(github_url) if !github_url.is_empty() => match github::db::get_repository(&project.id, github_url, &db_pool).await {
+            Ok(repository) => repository,
+            Err(err) => {
+                warn!("Failed to get project repository: {err}");
+                return Err(reject::custom(error::Error::ServerProblem));
+            }
+        },
+        _ => None,
+    };
+    let rendered = rendered_content(&project, &db_pool).await;
+    let response = FullProjectResponse {
+        id: project.id,
+        name: project.name,
+        display_name: project.display_name,
+        owner_id: project.owner_id,
+        owner_name: username.clone(),
+        visibility: project.visibility,
+        description: project.description,
+        content: project.content,
+        content_html: rendered.html,
+        toc: rendered.toc,
+        excerpt: rendered.excerpt,
+        repository,
+        github_url: project.github_url,
+        website_url: project.website_url,
+        thumbnail: project.thumbnail.as_deref().map(media_url),
+        gallery,
+        members,
+        likes: project.likes,
+        liked,
+        tags,
+        views: project.views,
+        created_at: project.created_at,
+        updated_at: project.updated_at,
+        tournament: project.tournament,
+        url: format!("https://ckziucodefest.pl/p/{}/{}", &username, &project_name),
+    };
+    Ok(with_etag(json(&response), project.version))
 }
 
 #[derive(Deserialize)]
 pub struct PatchProject {
     pub display_name: Option<String>,
+    pub visibility: Option<ProjectVisibility>,
+    // Replaced by `visibility`, still accepted from older clients
     pub private: Option<bool>,
     pub description: Option<String>,
     pub content: Option<String>,
     pub github_url: Option<String>,
     pub website_url: Option<String>,
     pub tournament: Option<bool>,
+    // Tags or their aliases, replacing the current ones
+    pub tags: Option<Vec<String>>,
 }
 
 // DELETE v1/projects/USER_NAME/PROJECT_NAME
@@ -232,22 +338,32 @@ pub async fn delete_project(
     username: String,
     project_name: String,
     user_uid: Option<Uuid>,
+    if_match: Option<String>,
     db_pool: PgPool,
+    storage: SharedStorage,
 ) -> WebResult<impl Reply> {
-    let authorized = is_authorized(&user_uid, &username, &db_pool).await;
     // Reject unauthorized
-    if !authorized {
-        return Err(reject::custom(error::Error::Unauthorized));
-    }
-    let user_id = user_uid.unwrap();
+    user_uid.ok_or(error::Error::Unauthorized)?;
+    // Only owners can delete the project
+    let project = match project_access(&username, &project_name, &user_uid, &db_pool).await? {
+        (project, Some(role)) if role.can_manage() => project,
+        _ => return Err(reject::custom(error::Error::Unauthorized)),
+    };
+    let expected_version = expected_version(project.version, if_match.as_deref())?;
 
     // Remove from db
-    match db::delete_project(&user_id, &project_name, &db_pool).await {
-        Ok(_) => (),
+    let media = match db::delete_project(&project, expected_version, &db_pool).await {
+        Ok(Some(media)) => media,
+        Ok(None) => return Err(reject::custom(error::Error::VersionConflict)),
         Err(err) => {
             warn!("Failed to delete project: {err}");
             return Err(reject::custom(error::Error::ServerProblem));
         }
+    };
+
+    // Remove thumbnail and gallery images
+    for object_key in media {
+        delete_file(&object_key, &storage, &db_pool).await;
     }
 
     Ok(warp::reply::with_status(
@@ -261,19 +377,41 @@ pub async fn patch_project(
     username: String,
     project_name: String,
     user_uid: Option<Uuid>,
+    if_match: Option<String>,
     mut patch: PatchProject,
     db_pool: PgPool,
-) -> WebResult<impl Reply> {
-    let authorized = is_authorized(&user_uid, &username, &db_pool).await;
+) -> WebResult<Response> {
+    // Reject unauthorized
+    let author_id = user_uid.ok_or(error::Error::Unauthorized)?;
+    // Reject anyone but owners and maintainers
+    let (project, role) = match project_access(&username, &project_name, &user_uid, &db_pool).await? {
+        (project, Some(role)) if role.can_edit() => (project, role),
+        _ => return Err(reject::custom(error::Error::Unauthorized)),
+    };
+    let expected_version = expected_version(project.version, if_match.as_deref())?;
+    if let Some(private) = patch.private.take() {
+        patch.visibility = patch.visibility.or(Some(ProjectVisibility::from_private(private)));
+    }
 
-    // Reject unauthorized with project owner
-    if !authorized {
-        return Err(reject::custom(error::Error::Unauthorized));
+    // Visibility and the contest entry are up to the owners
+    if !role.can_manage() && (patch.visibility.is_some() || patch.tournament.is_some()) {
+        return Ok(json(&PostProjectResponse {
+            success: false,
+            created: false,
+            message: "Tylko właściciele projektu mogą zmienić jego widoczność i zgłoszenie na konkurs".into(),
+        }).into_response());
     }
-    let user_uid = user_uid.ok_or(error::Error::Unauthorized)?;
 
     // Check if the project is a tournament project
     if patch.tournament.unwrap_or(false) {
+        if !patch.visibility.unwrap_or(project.visibility).allows_tournament() {
+            return Ok(json(&PostProjectResponse {
+                success: false,
+                created: false,
+                message: "Tylko publiczne projekty mogą zostać zgłoszone na konkurs".into(),
+            }).into_response());
+        }
+
         // Check if the current date is after the deadline
         let now = Utc::now();
         if now.day() >= 1 && now.month() >= 6 {
@@ -281,24 +419,25 @@ pub async fn patch_project(
                 success: false,
                 created: false,
                 message: "Termin zgłaszania projektów minął 1 czerwca!".into(),
-            }));
+            }).into_response());
         }
     }
 
     // Check if there is any data to update
     if patch.display_name.is_none()
-        && patch.private.is_none()
+        && patch.visibility.is_none()
         && patch.description.is_none()
         && patch.content.is_none()
         && patch.github_url.is_none()
         && patch.website_url.is_none()
         && patch.tournament.is_none()
+        && patch.tags.is_none()
     {
         return Ok(json(&PostProjectResponse {
             success: true,
             created: false,
             message: "Brak danych".into(),
-        }));
+        }).into_response());
     }
 
     // Validate display name
@@ -310,7 +449,7 @@ pub async fn patch_project(
                     success: false,
                     created: false,
                     message,
-                }));
+                }).into_response());
             }
         };
     }
@@ -324,14 +463,53 @@ pub async fn patch_project(
                     success: false,
                     created: false,
                     message,
-                }));
+                }).into_response());
+            }
+        };
+    }
+
+    // Validate GitHub repository
+    if let Some(github_url) = patch.github_url.take() {
+        patch.github_url = match validate_github_url(github_url) {
+            Ok(github_url) => Some(github_url),
+            Err(message) => {
+                return Ok(json(&PostProjectResponse {
+                    success: false,
+                    created: false,
+                    message,
+                }).into_response());
+            }
+        };
+    }
+
+    // Resolve tags
+    if let Some(tags) = patch.tags.take() {
+        patch.tags = match resolve_project_tags(tags, &db_pool).await? {
+            Ok(tags) => Some(tags),
+            Err(message) => {
+                return Ok(json(&PostProjectResponse {
+                    success: false,
+                    created: false,
+                    message,
+                }).into_response());
             }
         };
     }
 
     // Perform update
-    match db::patch_project(&user_uid, patch, &project_name, &db_pool).await {
-        Ok(_) => {}
+    let entered_tournament = patch.tournament.unwrap_or(false);
+    let version = match db::patch_project(&project, patch, &author_id, expected_version, &db_pool).await {
+        Ok(PatchResult::Patched { revision, version }) => {
+            if let Some(revision) = revision {
+                info!("Revision {revision} of {} recorded by {author_id}", project.id);
+            }
+            if entered_tournament {
+                spawn_evaluate(project.owner_id, BadgeEvent::TournamentEntered, &db_pool);
+            }
+            version
+        }
+        Ok(PatchResult::Conflict) => return Err(reject::custom(error::Error::VersionConflict)),
+        Ok(PatchResult::NotFound) => return Err(reject::custom(error::Error::NotFound)),
         Err(err) => {
             warn!("Database failed to update project: {err}");
             return Err(reject::custom(error::Error::ServerProblem));
@@ -339,11 +517,12 @@ pub async fn patch_project(
     };
 
     // Return success
-    Ok(json(&PostProjectResponse {
+    let response = json(&PostProjectResponse {
         success: true,
         created: true,
         message: "Zaktualizowano dane".into(),
-    }))
+    });
+    Ok(with_etag(response, version))
 }
 
 // POST v1/project/create
@@ -358,6 +537,7 @@ pub async fn create_project(
     }
 
     // Validate
+    let visibility = body.visibility();
     let project_name = match validate_name(body.name) {
         Ok(name) => name,
         Err(message) => {
@@ -419,13 +599,16 @@ pub async fn create_project(
         name: project_name.clone(),
         display_name: project_display_name.clone(),
         owner_id,
-        private: body.private,
+        visibility,
         description: project_description,
         content: String::new(),
         github_url: None,
         website_url: None,
+        thumbnail: None,
         likes: 0,
         tournament: false,
+        version: 1,
+        views: 0,
         created_at: Utc::now(),
         updated_at: Utc::now(),
     };
@@ -441,6 +624,7 @@ pub async fn create_project(
                 &owner_id,
                 current_millis() - create_start
             );
+            spawn_evaluate(owner_id, BadgeEvent::ProjectCreated, &db_pool);
             Ok(json(&PostProjectResponse {
                 success: true,
                 created: true,
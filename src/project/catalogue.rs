@@ -0,0 +1,134 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::PgPool;
+use tracing::warn;
+use uuid::Uuid;
+use warp::{reject, Reply};
+
+use crate::error::Error;
+use crate::pagination::{
+  decode_count_cursor, decode_time_cursor, encode_count_cursor, encode_time_cursor, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE,
+};
+use crate::prelude::web_json;
+use crate::project::db;
+use crate::project::models::ProjectCardWithOwner;
+use crate::tags::models::normalize_tag;
+use crate::WebResult;
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectSort {
+  #[default]
+  Newest,
+  Updated,
+  Likes,
+}
+
+// Position after the last row of the previous page
+#[derive(PartialEq, Debug)]
+pub enum CatalogueCursor {
+  Time(DateTime<Utc>, Uuid),
+  Likes(i32, Uuid),
+}
+
+impl ProjectSort {
+  // Column the rows are ordered by, the id is a tiebreaker
+  pub fn column(&self) -> &'static str {
+    match self {
+      ProjectSort::Newest => "projects.created_at",
+      ProjectSort::Updated => "projects.updated_at",
+      ProjectSort::Likes => "projects.likes",
+    }
+  }
+
+  pub fn encode_cursor(&self, project: &ProjectCardWithOwner) -> String {
+    match self {
+      ProjectSort::Newest => encode_time_cursor(&project.created_at, &project.id),
+      ProjectSort::Updated => encode_time_cursor(&project.updated_at, &project.id),
+      ProjectSort::Likes => encode_count_cursor(project.likes as i64, &project.id),
+    }
+  }
+
+  pub fn decode_cursor(&self, cursor: &str) -> Option<CatalogueCursor> {
+    match self {
+      ProjectSort::Newest | ProjectSort::Updated => {
+        decode_time_cursor(cursor).map(|(time, id)| CatalogueCursor::Time(time, id))
+      }
+      ProjectSort::Likes => {
+        let (likes, id) = decode_count_cursor(cursor)?;
+        Some(CatalogueCursor::Likes(likes.try_into().ok()?, id))
+      }
+    }
+  }
+}
+
+#[derive(Deserialize)]
+pub struct CatalogueQuery {
+  pub cursor: Option<String>,
+  pub limit: Option<i64>,
+  #[serde(default)]
+  pub sort: ProjectSort,
+  // Username of the owner
+  pub owner: Option<String>,
+  pub tournament: Option<bool>,
+  // Tag or its alias
+  pub tag: Option<String>,
+  // Has a website or GitHub link
+  pub website: Option<bool>,
+  pub github: Option<bool>,
+  // Milliseconds since the epoch, the range includes `created_after` only
+  pub created_after: Option<i64>,
+  pub created_before: Option<i64>,
+}
+
+pub struct CatalogueFilter {
+  pub owner: Option<String>,
+  pub tournament: Option<bool>,
+  pub tag: Option<String>,
+  pub website: Option<bool>,
+  pub github: Option<bool>,
+  pub created_after: Option<DateTime<Utc>>,
+  pub created_before: Option<DateTime<Utc>>,
+}
+
+fn parse_millis(millis: Option<i64>) -> Result<Option<DateTime<Utc>>, Error> {
+  match millis {
+    Some(millis) => DateTime::from_timestamp_millis(millis).map(Some).ok_or(Error::ParameterProblem),
+    None => Ok(None),
+  }
+}
+
+impl CatalogueQuery {
+  pub fn limit(&self) -> i64 {
+    self.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+  }
+
+  pub fn filter(&self) -> Result<CatalogueFilter, Error> {
+    Ok(CatalogueFilter {
+      owner: self.owner.as_ref().map(|owner| owner.trim().to_lowercase()),
+      tournament: self.tournament,
+      tag: self.tag.as_deref().map(normalize_tag),
+      website: self.website,
+      github: self.github,
+      created_after: parse_millis(self.created_after)?,
+      created_before: parse_millis(self.created_before)?,
+    })
+  }
+}
+
+// GET v1/projects/catalogue?sort=SORT&cursor=CURSOR
+pub async fn get_catalogue(query: CatalogueQuery, user_uid: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  let filter = query.filter()?;
+  let cursor = match &query.cursor {
+    Some(cursor) => Some(query.sort.decode_cursor(cursor).ok_or(Error::ParameterProblem)?),
+    None => None,
+  };
+
+  match db::get_catalogue(user_uid, &filter, query.sort, cursor, query.limit(), &db_pool).await {
+    Ok(page) => web_json(&page),
+    Err(err) => {
+      warn!("Failed to get projects catalogue: {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
@@ -1,26 +1,109 @@
-use sqlx::PgPool;
+use chrono::NaiveDate;
+use sqlx::types::Json;
+use sqlx::{PgConnection, PgPool, Postgres, QueryBuilder};
 use uuid::Uuid;
 
+use crate::markdown::{Rendered, TocEntry};
+use crate::pagination::Page;
 use crate::project::api::PatchProject;
 use crate::project::models::Project;
+use crate::storage::quota::check_quota_in;
 
 use super::api::FullProjectResponse;
-use super::models::{ContestProject, ProjectCard};
+use super::catalogue::{CatalogueCursor, CatalogueFilter, ProjectSort};
+use super::revisions::{apply_patch, changed_fields};
+use super::search::{highlight_snippet, SearchTerm};
+use super::trending::{HALF_LIFE_DAYS, LIKE_WEIGHT, NEW_PROJECT_HALF_LIFE_DAYS, VIEW_WINDOW_DAYS};
+use super::models::{
+  ContestProject, ProjectCardWithOwner, ProjectImage, ProjectInvitation, ProjectMember, ProjectRevision, ProjectRole, ProjectTransfer,
+  ProjectSearchResult, ProjectSnapshot, Release, ReleaseAsset,
+};
 
-pub async fn delete_project(owner_id: &Uuid, project_name: &String, pool: &PgPool) -> Result<(), Box<dyn std::error::Error>> {
-  let query = r"DELETE FROM projects USING users WHERE projects.owner_id = $1 AND projects.name = $2";
+// Returns storage keys of the project media and release assets, which have to be removed as well,
+// None if the project was changed in the meantime
+pub async fn delete_project(
+  project: &Project,
+  expected_version: Option<i32>,
+  pool: &PgPool,
+) -> Result<Option<Vec<String>>, Box<dyn std::error::Error>> {
+  let query = r"DELETE FROM projects WHERE id = $1 AND owner_id = $2 AND ($3::integer IS NULL OR version = $3)
+  RETURNING thumbnail";
 
   let mut transaction = pool.begin().await?;
 
-  sqlx::query(query)
-    .bind(owner_id)
-    .bind(project_name)
+  let deleted: Option<Option<String>> = sqlx::query_scalar(query)
+    .bind(project.id)
+    .bind(project.owner_id)
+    .bind(expected_version)
+    .fetch_optional(&mut *transaction)
+    .await?;
+  let Some(thumbnail) = deleted else {
+    return Ok(None);
+  };
+
+  let project_id = project.id;
+  let mut media: Vec<String> = thumbnail.into_iter().collect();
+  let images: Vec<String> = sqlx::query_scalar(r"DELETE FROM project_images WHERE project_id = $1 RETURNING object_key")
+    .bind(project_id)
+    .fetch_all(&mut *transaction)
+    .await?;
+  media.extend(images);
+  let assets: Vec<String> = sqlx::query_scalar(
+    r"DELETE FROM release_assets WHERE release_id IN (SELECT id FROM project_releases WHERE project_id = $1)
+    RETURNING object_key",
+  )
+  .bind(project_id)
+  .fetch_all(&mut *transaction)
+  .await?;
+  media.extend(assets);
+  sqlx::query(r"DELETE FROM project_releases WHERE project_id = $1")
+    .bind(project_id)
+    .execute(&mut *transaction)
+    .await?;
+  sqlx::query(r"DELETE FROM projects_likes WHERE project_id = $1")
+    .bind(project_id)
+    .execute(&mut *transaction)
+    .await?;
+  sqlx::query(r"DELETE FROM project_tags WHERE project_id = $1")
+    .bind(project_id)
+    .execute(&mut *transaction)
+    .await?;
+  sqlx::query(r"DELETE FROM project_members WHERE project_id = $1")
+    .bind(project_id)
+    .execute(&mut *transaction)
+    .await?;
+  sqlx::query(r"DELETE FROM project_revisions WHERE project_id = $1")
+    .bind(project_id)
+    .execute(&mut *transaction)
+    .await?;
+  sqlx::query(r"DELETE FROM project_renders WHERE project_id = $1")
+    .bind(project_id)
+    .execute(&mut *transaction)
+    .await?;
+  sqlx::query(r"DELETE FROM project_redirects WHERE project_id = $1")
+    .bind(project_id)
+    .execute(&mut *transaction)
+    .await?;
+  sqlx::query(r"DELETE FROM project_transfers WHERE project_id = $1")
+    .bind(project_id)
+    .execute(&mut *transaction)
+    .await?;
+  sqlx::query(r"DELETE FROM project_repositories WHERE project_id = $1")
+    .bind(project_id)
+    .execute(&mut *transaction)
+    .await?;
+  sqlx::query(r"DELETE FROM project_views WHERE project_id = $1")
+    .bind(project_id)
+    .execute(&mut *transaction)
+    .await?;
+  sqlx::query(r"DELETE FROM project_trending WHERE project_id = $1")
+    .bind(project_id)
     .execute(&mut *transaction)
     .await?;
 
   transaction.commit().await?;
 
-  Ok(())
+  Ok(Some(media))
 }
 
 #[allow(dead_code)]
@@ -40,15 +123,15 @@ pub async fn get_projects_by_ownername(
   username: &String,
   pool: &PgPool,
 ) -> Result<Vec<Project>, Box<dyn std::error::Error>> {
-  let query = r"SELECT content, website_url, github_url, tournament, projects.id, projects.name, projects.display_name, projects.owner_id, projects.private, projects.description, projects.likes, projects.created_at, projects.updated_at, users.id AS userid, users.name AS username FROM projects INNER JOIN users ON projects.owner_id = users.id WHERE users.name = $1 ORDER BY projects.updated_at DESC";
+  let query = r"SELECT content, website_url, github_url, tournament, projects.id, projects.name, projects.display_name, projects.owner_id, projects.visibility, projects.description, projects.likes, projects.created_at, projects.updated_at, users.id AS userid, users.name AS username FROM projects INNER JOIN users ON projects.owner_id = users.id WHERE users.name = $1 ORDER BY projects.updated_at DESC";
 
   let result: Vec<Project> = sqlx::query_as(query).bind(username).fetch_all(pool).await?;
 
   Ok(result)
 }
 
-pub async fn get_newest_projects(pool: &PgPool) -> Result<Vec<FullProjectResponse>, Box<dyn std::error::Error>> {
-  let query = r"SELECT content,
+pub async fn get_newest_projects(viewer: Option<Uuid>, pool: &PgPool) -> Result<Vec<FullProjectResponse>, Box<dyn std::error::Error>> {
+  let query = r#"SELECT content,
   projects.website_url,
   projects.github_url,
   projects.tournament,
@@ -56,26 +139,30 @@ pub async fn get_newest_projects(pool: &PgPool) -> Result<Vec<FullProjectRespons
   projects.name,
   projects.display_name,
   projects.owner_id,
-  projects.private,
+  projects.visibility,
   projects.description,
+  '/v1/media/' || projects.thumbnail                                  AS thumbnail,
   projects.likes,
+  projects.views,
   projects.created_at,
   projects.updated_at,
   users.id                                                            AS userid,
   users.name                                                          AS owner_name,
-  concat('https://ckziucodefest.pl/p/', users.name, '/', projects.name) as url
+  concat('https://ckziucodefest.pl/p/', users.name, '/', projects.name) as url,
+  EXISTS(SELECT 1 FROM projects_likes WHERE project_id = projects.id AND $1 IS NOT NULL AND user_id = $1) as "liked",
+  ARRAY(SELECT tag FROM project_tags WHERE project_id = projects.id ORDER BY tag) AS tags
 FROM projects
     INNER JOIN users ON projects.owner_id = users.id
-WHERE projects.private = false
+WHERE is_project_listed(projects.visibility, $1) AND can_view_profile(users.id, $1)
 ORDER BY updated_at DESC
-LIMIT 6";
-  let result: Vec<FullProjectResponse> = sqlx::query_as(query).fetch_all(pool).await?;
+LIMIT 6"#;
+  let result: Vec<FullProjectResponse> = sqlx::query_as(query).bind(viewer).fetch_all(pool).await?;
 
   Ok(result)
 }
 
 
-pub async fn get_contest_projects(pool: &PgPool) -> Result<Vec<ContestProject>, Box<dyn std::error::Error>> {
+pub async fn get_contest_projects(viewer: Option<Uuid>, pool: &PgPool) -> Result<Vec<ContestProject>, Box<dyn std::error::Error>> {
   let query = r#"
   SELECT
     projects.id,
@@ -88,6 +175,7 @@ pub async fn get_contest_projects(pool: &PgPool) -> Result<Vec<ContestProject>,
     users.display_name as "owner_display_name",
     
     projects.description,
+    '/v1/media/' || projects.thumbnail as "thumbnail",
 
     projects.votes,
     projects.created_at,
@@ -95,27 +183,26 @@ pub async fn get_contest_projects(pool: &PgPool) -> Result<Vec<ContestProject>,
 
   FROM projects
   INNER JOIN users ON projects.owner_id = users.id
-  WHERE projects.tournament = true AND private = false
+  WHERE projects.tournament = true AND is_project_listed(projects.visibility, $1) AND can_view_profile(users.id, $1)
   ORDER BY projects.created_at DESC
   "#;
 
-  let result: Vec<ContestProject> = sqlx::query_as(query).fetch_all(pool).await?;
+  let result: Vec<ContestProject> = sqlx::query_as(query).bind(viewer).fetch_all(pool).await?;
 
   Ok(result)
 }
 
+// Visibility is left to the caller, which knows the role of the viewer
 pub async fn get_project_by_ownername_projectname(
   username: &String,
   project_name: &String,
-  can_be_private: bool,
   pool: &PgPool,
-) -> Result<Option<Project>, Box<dyn std::error::Error>> {
-  let query = r"SELECT tournament, projects.id, owner_id, private, projects.name, projects.display_name, github_url, website_url, content, description, likes, projects.created_at, projects.updated_at, users.id AS userid, users.name AS username FROM projects INNER JOIN users ON projects.owner_id = users.id WHERE users.name = $1 AND projects.name = $2 AND (projects.private = false OR projects.private = $3) LIMIT 1";
+) -> Result<Option<Project>, Box<dyn std::error::Error + Send + Sync>> {
+  let query = r"SELECT tournament, version, views, projects.id, owner_id, visibility, projects.name, projects.display_name, github_url, website_url, content, description, thumbnail, likes, projects.created_at, projects.updated_at, users.id AS userid, users.name AS username FROM projects INNER JOIN users ON projects.owner_id = users.id WHERE users.name = $1 AND projects.name = $2 LIMIT 1";
 
   let result: Option<Project> = sqlx::query_as(query)
     .bind(username)
     .bind(project_name)
-    .bind(can_be_private)
     .fetch_optional(pool)
     .await?;
 
@@ -142,7 +229,7 @@ pub async fn create_project(
   project: &Project,
   pool: &PgPool,
 ) -> Result<(), Box<dyn std::error::Error>> {
-  let query = r"INSERT INTO projects (id, name, display_name, owner_id, private, description, likes, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)";
+  let query = r"INSERT INTO projects (id, name, display_name, owner_id, visibility, description, likes, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)";
 
   let mut transaction = pool.begin().await?;
 
@@ -151,7 +238,7 @@ pub async fn create_project(
     .bind(&project.name)
     .bind(&project.display_name)
     .bind(project.owner_id)
-    .bind(project.private)
+    .bind(project.visibility)
     .bind(&project.description)
     .bind(project.likes)
     .bind(project.created_at)
@@ -159,91 +246,1285 @@ pub async fn create_project(
     .execute(&mut *transaction)
     .await?;
 
+  sqlx::query(r"INSERT INTO project_members (project_id, user_id, role, accepted) VALUES ($1, $2, 'owner', true)")
+    .bind(project.id)
+    .bind(project.owner_id)
+    .execute(&mut *transaction)
+    .await?;
+
+  // The new project takes over the address of a renamed one
+  sqlx::query(r"DELETE FROM project_redirects WHERE owner_id = $1 AND name = $2")
+    .bind(project.owner_id)
+    .bind(&project.name)
+    .execute(&mut *transaction)
+    .await?;
+
   transaction.commit().await?;
 
   Ok(())
 }
 
-pub async fn patch_project(owner_id: &Uuid, patch: PatchProject, projectname: &String, pool: &PgPool) -> Result<(), Box<dyn std::error::Error>> {
+const SNAPSHOT_QUERY: &str = r"SELECT display_name,
+  description,
+  content,
+  github_url,
+  website_url,
+  ARRAY(SELECT tag FROM project_tags WHERE project_id = projects.id ORDER BY tag) AS tags
+FROM projects
+WHERE id = $1
+FOR UPDATE";
+
+// Records the revision if anything changed and drops the outdated rendering, returns its number
+async fn record_revision(
+  project_id: &Uuid,
+  author_id: &Uuid,
+  previous: &ProjectSnapshot,
+  snapshot: &ProjectSnapshot,
+  restored_from: Option<i32>,
+  connection: &mut PgConnection,
+) -> Result<Option<i32>, sqlx::Error> {
+  let changes = changed_fields(previous, snapshot);
+  if changes.is_empty() {
+    return Ok(None);
+  }
+  if changes.contains_key("content") {
+    sqlx::query(r"DELETE FROM project_renders WHERE project_id = $1")
+      .bind(project_id)
+      .execute(&mut *connection)
+      .await?;
+  }
+  let fields: Vec<&str> = changes.keys().map(String::as_str).collect();
+
+  let number: i32 = sqlx::query_scalar(
+    r"INSERT INTO project_revisions (project_id, number, author_id, fields, previous, snapshot, restored_from)
+    VALUES ($1, (SELECT coalesce(max(number), 0) + 1 FROM project_revisions WHERE project_id = $1), $2, $3, $4, $5, $6)
+    RETURNING number",
+  )
+  .bind(project_id)
+  .bind(author_id)
+  .bind(&fields)
+  .bind(Json(&changes))
+  .bind(Json(snapshot))
+  .bind(restored_from)
+  .fetch_one(&mut *connection)
+  .await?;
+
+  Ok(Some(number))
+}
+
+pub enum PatchResult {
+  // Number of the recorded revision, None if the content did not change
+  Patched { revision: Option<i32>, version: i32 },
+  // The project was updated or transferred since it was read
+  Conflict,
+  // The project was deleted since it was read
+  NotFound,
+}
+
+// Updates the present fields with a single query, checking the version if given
+pub async fn patch_project(
+  project: &Project,
+  patch: PatchProject,
+  author_id: &Uuid,
+  expected_version: Option<i32>,
+  pool: &PgPool,
+) -> Result<PatchResult, Box<dyn std::error::Error>> {
+  let project_id = &project.id;
   let mut transaction = pool.begin().await?;
 
-  sqlx::query(r"UPDATE projects SET updated_at = now() WHERE name = $1")
-    .bind(projectname)
-    .execute(&mut *transaction)
-    .await?;
+  // Locks the project until the revision is recorded
+  let previous: Option<ProjectSnapshot> = sqlx::query_as(SNAPSHOT_QUERY).bind(project_id).fetch_optional(&mut *transaction).await?;
+  let Some(previous) = previous else {
+    return Ok(PatchResult::NotFound);
+  };
+  let snapshot = apply_patch(&previous, &patch);
+  // Only public projects stay in the contest
+  let tournament = match patch.visibility {
+    Some(visibility) if !visibility.allows_tournament() => Some(false),
+    _ => patch.tournament,
+  };
 
+  let mut query = QueryBuilder::<Postgres>::new("UPDATE projects SET updated_at = now(), version = version + 1");
   if let Some(display_name) = patch.display_name {
-    let query = r"UPDATE projects SET display_name = $1 WHERE name = $2";
-    sqlx::query(query)
-      .bind(display_name)
-      .bind(projectname)
-      .execute(&mut *transaction)
-      .await?;
+    query.push(", display_name = ").push_bind(display_name);
   }
   if let Some(description) = patch.description {
-    let query = r"UPDATE projects SET description = $1 WHERE name = $2";
-    sqlx::query(query)
-      .bind(description)
-      .bind(projectname)
-      .execute(&mut *transaction)
-      .await?;
+    query.push(", description = ").push_bind(description);
   }
   if let Some(website_url) = patch.website_url {
-    let query = r"UPDATE projects SET website_url = $1 WHERE name = $2";
-    sqlx::query(query)
-      .bind(website_url)
-      .bind(projectname)
-      .execute(&mut *transaction)
-      .await?;
+    query.push(", website_url = ").push_bind(website_url);
   }
   if let Some(content) = patch.content {
-    let query = r"UPDATE projects SET content = $1 WHERE name = $2";
+    query.push(", content = ").push_bind(content);
+  }
+  if let Some(github_url) = patch.github_url {
+    query.push(", github_url = ").push_bind(github_url);
+  }
+  if let Some(visibility) = patch.visibility {
+    query.push(", visibility = ").push_bind(visibility);
+  }
+  if let Some(tournament) = tournament {
+    query.push(", tournament = ").push_bind(tournament);
+  }
+  query.push(" WHERE id = ").push_bind(project_id);
+  query.push(" AND owner_id = ").push_bind(project.owner_id);
+  if let Some(version) = expected_version {
+    query.push(" AND version = ").push_bind(version);
+  }
+  query.push(" RETURNING version");
+
+  let version: Option<i32> = query.build_query_scalar().fetch_optional(&mut *transaction).await?;
+  let Some(version) = version else {
+    return Ok(PatchResult::Conflict);
+  };
+
+  if let Some(tags) = patch.tags {
+    let query = r"DELETE FROM project_tags WHERE project_id = $1";
     sqlx::query(query)
-      .bind(content)
-      .bind(projectname)
+      .bind(project_id)
       .execute(&mut *transaction)
       .await?;
-  }
-  if let Some(github_url) = patch.github_url {
-    let query = r"UPDATE projects SET github_url = $1 WHERE name = $2";
+    let query = r"INSERT INTO project_tags (project_id, tag) SELECT $1, unnest($2::varchar[])";
     sqlx::query(query)
-      .bind(github_url)
-      .bind(projectname)
+      .bind(project_id)
+      .bind(tags)
       .execute(&mut *transaction)
       .await?;
   }
-  if let Some(private) = patch.private {
-    let query = r"UPDATE projects SET private = $1 WHERE name = $2";
+  // Each user enters the contest with a single project
+  if tournament == Some(true) {
+    let query = r"UPDATE projects SET tournament = false WHERE owner_id = $1 AND id <> $2";
     sqlx::query(query)
-      .bind(private)
-      .bind(projectname)
+      .bind(project.owner_id)
+      .bind(project_id)
       .execute(&mut *transaction)
       .await?;
-    if private {
-      let query = r"UPDATE projects SET tournament = false WHERE name = $1";
-      sqlx::query(query)
-        .bind(projectname)
-        .execute(&mut *transaction)
-        .await?;
-    }
   }
-  if let Some(tournament) = patch.tournament {
-    let query = r"UPDATE projects SET tournament = false WHERE owner_id = $1";
-    sqlx::query(query)
-      .bind(owner_id)
+
+  let revision = record_revision(project_id, author_id, &previous, &snapshot, None, &mut transaction).await?;
+
+  transaction.commit().await?;
+
+  Ok(PatchResult::Patched { revision, version })
+}
+
+// Overwrites the tracked fields with the snapshot as a new revision, checking the version if given
+pub async fn restore_snapshot(
+  project: &Project,
+  snapshot: &ProjectSnapshot,
+  author_id: &Uuid,
+  restored_from: i32,
+  expected_version: Option<i32>,
+  pool: &PgPool,
+) -> Result<PatchResult, Box<dyn std::error::Error + Send + Sync>> {
+  let mut transaction = pool.begin().await?;
+
+  let previous: Option<ProjectSnapshot> = sqlx::query_as(SNAPSHOT_QUERY).bind(project.id).fetch_optional(&mut *transaction).await?;
+  let Some(previous) = previous else {
+    return Ok(PatchResult::NotFound);
+  };
+
+  let version: Option<i32> = sqlx::query_scalar(
+    r"UPDATE projects SET display_name = $2, description = $3, content = $4, github_url = $5, website_url = $6,
+    updated_at = now(), version = version + 1
+    WHERE id = $1 AND owner_id = $7 AND ($8::integer IS NULL OR version = $8)
+    RETURNING version",
+  )
+  .bind(project.id)
+  .bind(&snapshot.display_name)
+  .bind(&snapshot.description)
+  .bind(&snapshot.content)
+  .bind(&snapshot.github_url)
+  .bind(&snapshot.website_url)
+  .bind(project.owner_id)
+  .bind(expected_version)
+  .fetch_optional(&mut *transaction)
+  .await?;
+  let Some(version) = version else {
+    return Ok(PatchResult::Conflict);
+  };
+  sqlx::query(r"DELETE FROM project_tags WHERE project_id = $1")
+    .bind(project.id)
+    .execute(&mut *transaction)
+    .await?;
+  sqlx::query(r"INSERT INTO project_tags (project_id, tag) SELECT $1, unnest($2::varchar[])")
+    .bind(project.id)
+    .bind(&snapshot.tags)
+    .execute(&mut *transaction)
+    .await?;
+
+  let revision = record_revision(&project.id, author_id, &previous, snapshot, Some(restored_from), &mut transaction).await?;
+
+  transaction.commit().await?;
+
+  Ok(PatchResult::Patched { revision, version })
+}
+
+// Whether the project is public and whether the viewer can open it, None if there is no such project
+pub async fn get_media_access(
+  project_id: &Uuid,
+  viewer: Option<Uuid>,
+  pool: &PgPool,
+) -> Result<Option<(bool, bool)>, Box<dyn std::error::Error + Send + Sync>> {
+  let query = r"SELECT visibility = 'public', can_view_project(id, owner_id, visibility, $2) FROM projects WHERE id = $1";
+
+  let result: Option<(bool, bool)> = sqlx::query_as(query)
+    .bind(project_id)
+    .bind(viewer)
+    .fetch_optional(pool)
+    .await?;
+
+  Ok(result)
+}
+
+// Returns the previous thumbnail
+pub async fn set_thumbnail(
+  project_id: &Uuid,
+  object_key: Option<&str>,
+  pool: &PgPool,
+) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+  let mut transaction = pool.begin().await?;
+
+  let previous: Option<Option<String>> = sqlx::query_scalar(r"SELECT thumbnail FROM projects WHERE id = $1 FOR UPDATE")
+    .bind(project_id)
+    .fetch_optional(&mut *transaction)
+    .await?;
+
+  sqlx::query(r"UPDATE projects SET thumbnail = $1, updated_at = now(), version = version + 1 WHERE id = $2")
+    .bind(object_key)
+    .bind(project_id)
+    .execute(&mut *transaction)
+    .await?;
+
+  transaction.commit().await?;
+
+  Ok(previous.flatten())
+}
+
+const GET_GALLERY_QUERY: &str = r"SELECT id, position, caption, '/v1/media/' || object_key AS url
+FROM project_images
+WHERE project_id = $1
+ORDER BY position";
+
+pub async fn get_gallery(project_id: &Uuid, pool: &PgPool) -> Result<Vec<ProjectImage>, Box<dyn std::error::Error + Send + Sync>> {
+  let result: Vec<ProjectImage> = sqlx::query_as(GET_GALLERY_QUERY).bind(project_id).fetch_all(pool).await?;
+
+  Ok(result)
+}
+
+// Appends the image at the end of the gallery, returns its position or None if the gallery is full
+pub async fn add_gallery_image(
+  project_id: &Uuid,
+  image_id: &Uuid,
+  caption: Option<&str>,
+  object_key: &str,
+  limit: i64,
+  pool: &PgPool,
+) -> Result<Option<i32>, Box<dyn std::error::Error + Send + Sync>> {
+  let mut transaction = pool.begin().await?;
+
+  // Serializes uploads to the same project
+  sqlx::query(r"SELECT id FROM projects WHERE id = $1 FOR UPDATE")
+    .bind(project_id)
+    .execute(&mut *transaction)
+    .await?;
+
+  let count: i64 = sqlx::query_scalar(r"SELECT count(*) FROM project_images WHERE project_id = $1")
+    .bind(project_id)
+    .fetch_one(&mut *transaction)
+    .await?;
+  if count >= limit {
+    return Ok(None);
+  }
+
+  let position: i32 = sqlx::query_scalar(
+    r"INSERT INTO project_images (id, project_id, position, caption, object_key)
+    VALUES ($1, $2, (SELECT coalesce(max(position) + 1, 0) FROM project_images WHERE project_id = $2), $3, $4)
+    RETURNING position",
+  )
+  .bind(image_id)
+  .bind(project_id)
+  .bind(caption)
+  .bind(object_key)
+  .fetch_one(&mut *transaction)
+  .await?;
+
+  transaction.commit().await?;
+
+  Ok(Some(position))
+}
+
+// Returns false if the image does not exist
+pub async fn set_gallery_caption(
+  project_id: &Uuid,
+  image_id: &Uuid,
+  caption: Option<&str>,
+  pool: &PgPool,
+) -> Result<bool, Box<dyn std::error::Error>> {
+  let result = sqlx::query(r"UPDATE project_images SET caption = $1 WHERE project_id = $2 AND id = $3")
+    .bind(caption)
+    .bind(project_id)
+    .bind(image_id)
+    .execute(pool)
+    .await?;
+
+  Ok(result.rows_affected() == 1)
+}
+
+// The order must contain every image of the gallery exactly once, returns false otherwise
+pub async fn reorder_gallery(project_id: &Uuid, order: &[Uuid], pool: &PgPool) -> Result<bool, Box<dyn std::error::Error>> {
+  let mut transaction = pool.begin().await?;
+
+  let images: Vec<Uuid> = sqlx::query_scalar(r"SELECT id FROM project_images WHERE project_id = $1 FOR UPDATE")
+    .bind(project_id)
+    .fetch_all(&mut *transaction)
+    .await?;
+  let mut sorted = order.to_vec();
+  sorted.sort();
+  sorted.dedup();
+  if sorted.len() != order.len() || images.len() != order.len() || !images.iter().all(|id| order.contains(id)) {
+    return Ok(false);
+  }
+
+  sqlx::query(r"UPDATE project_images SET position = array_position($2, id) - 1 WHERE project_id = $1")
+    .bind(project_id)
+    .bind(order)
+    .execute(&mut *transaction)
+    .await?;
+
+  transaction.commit().await?;
+
+  Ok(true)
+}
+
+// Returns the storage key of the removed image
+pub async fn delete_gallery_image(
+  project_id: &Uuid,
+  image_id: &Uuid,
+  pool: &PgPool,
+) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+  let mut transaction = pool.begin().await?;
+
+  let deleted: Option<(String, i32)> =
+    sqlx::query_as(r"DELETE FROM project_images WHERE project_id = $1 AND id = $2 RETURNING object_key, position")
+      .bind(project_id)
+      .bind(image_id)
+      .fetch_optional(&mut *transaction)
+      .await?;
+
+  // Close the gap
+  if let Some((_, position)) = &deleted {
+    sqlx::query(r"UPDATE project_images SET position = position - 1 WHERE project_id = $1 AND position > $2")
+      .bind(project_id)
+      .bind(position)
       .execute(&mut *transaction)
       .await?;
-    if tournament {
-      let query = r"UPDATE projects SET tournament = true WHERE name = $1";
-      sqlx::query(query)
-        .bind(projectname)
-        .execute(&mut *transaction)
-        .await?;
+  }
+
+  transaction.commit().await?;
+
+  Ok(deleted.map(|(object_key, _)| object_key))
+}
+
+const RELEASE_COLUMNS: &str = r"id, tag, notes, created_at, updated_at";
+const RELEASE_ASSET_COLUMNS: &str =
+  r"id, release_id, name, object_key, content_type, size, sha256, downloads, created_at";
+
+async fn attach_assets(releases: &mut [Release], pool: &PgPool) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+  let ids: Vec<Uuid> = releases.iter().map(|release| release.id).collect();
+  let query = format!("SELECT {RELEASE_ASSET_COLUMNS} FROM release_assets WHERE release_id = ANY($1) ORDER BY name");
+  let assets: Vec<ReleaseAsset> = sqlx::query_as(&query).bind(&ids).fetch_all(pool).await?;
+
+  for asset in assets {
+    if let Some(release) = releases.iter_mut().find(|release| release.id == asset.release_id) {
+      release.assets.push(asset);
     }
   }
 
+  Ok(())
+}
+
+// Releases with their assets, unordered
+pub async fn get_releases(project_id: &Uuid, pool: &PgPool) -> Result<Vec<Release>, Box<dyn std::error::Error + Send + Sync>> {
+  let query = format!("SELECT {RELEASE_COLUMNS} FROM project_releases WHERE project_id = $1");
+  let mut releases: Vec<Release> = sqlx::query_as(&query).bind(project_id).fetch_all(pool).await?;
+  attach_assets(&mut releases, pool).await?;
+
+  Ok(releases)
+}
+
+pub async fn get_release(
+  project_id: &Uuid,
+  tag: &str,
+  pool: &PgPool,
+) -> Result<Option<Release>, Box<dyn std::error::Error + Send + Sync>> {
+  let query = format!("SELECT {RELEASE_COLUMNS} FROM project_releases WHERE project_id = $1 AND tag = $2");
+  let release: Option<Release> = sqlx::query_as(&query).bind(project_id).bind(tag).fetch_optional(pool).await?;
+
+  match release {
+    Some(release) => {
+      let mut releases = [release];
+      attach_assets(&mut releases, pool).await?;
+      let [release] = releases;
+      Ok(Some(release))
+    }
+    None => Ok(None),
+  }
+}
+
+// Returns false if the tag is already released
+pub async fn create_release(
+  release_id: &Uuid,
+  project_id: &Uuid,
+  tag: &str,
+  notes: &str,
+  pool: &PgPool,
+) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+  let result = sqlx::query(
+    r"INSERT INTO project_releases (id, project_id, tag, notes) VALUES ($1, $2, $3, $4)
+    ON CONFLICT (project_id, tag) DO NOTHING",
+  )
+  .bind(release_id)
+  .bind(project_id)
+  .bind(tag)
+  .bind(notes)
+  .execute(pool)
+  .await?;
+
+  Ok(result.rows_affected() == 1)
+}
+
+// Returns false if the release does not exist
+pub async fn set_release_notes(
+  project_id: &Uuid,
+  tag: &str,
+  notes: &str,
+  pool: &PgPool,
+) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+  let result = sqlx::query(r"UPDATE project_releases SET notes = $1, updated_at = now() WHERE project_id = $2 AND tag = $3")
+    .bind(notes)
+    .bind(project_id)
+    .bind(tag)
+    .execute(pool)
+    .await?;
+
+  Ok(result.rows_affected() == 1)
+}
+
+// Returns storage keys of the release assets or None if the release does not exist
+pub async fn delete_release(
+  project_id: &Uuid,
+  tag: &str,
+  pool: &PgPool,
+) -> Result<Option<Vec<String>>, Box<dyn std::error::Error + Send + Sync>> {
+  let mut transaction = pool.begin().await?;
+
+  let release_id: Option<Uuid> = sqlx::query_scalar(r"DELETE FROM project_releases WHERE project_id = $1 AND tag = $2 RETURNING id")
+    .bind(project_id)
+    .bind(tag)
+    .fetch_optional(&mut *transaction)
+    .await?;
+  let Some(release_id) = release_id else {
+    return Ok(None);
+  };
+
+  let assets: Vec<String> = sqlx::query_scalar(r"DELETE FROM release_assets WHERE release_id = $1 RETURNING object_key")
+    .bind(release_id)
+    .fetch_all(&mut *transaction)
+    .await?;
+
+  transaction.commit().await?;
+
+  Ok(Some(assets))
+}
+
+pub enum AddAssetResult {
+  Added,
+  NameTaken,
+  LimitReached,
+}
+
+// Adds the already stored asset to the release
+pub async fn add_release_asset(asset: &ReleaseAsset, limit: i64, pool: &PgPool) -> Result<AddAssetResult, Box<dyn std::error::Error + Send + Sync>> {
+  let mut transaction = pool.begin().await?;
+
+  // Serializes uploads to the same release
+  sqlx::query(r"SELECT id FROM project_releases WHERE id = $1 FOR UPDATE")
+    .bind(asset.release_id)
+    .execute(&mut *transaction)
+    .await?;
+
+  let count: i64 = sqlx::query_scalar(r"SELECT count(*) FROM release_assets WHERE release_id = $1")
+    .bind(asset.release_id)
+    .fetch_one(&mut *transaction)
+    .await?;
+  if count >= limit {
+    return Ok(AddAssetResult::LimitReached);
+  }
+
+  let result = sqlx::query(
+    r"INSERT INTO release_assets (id, release_id, name, object_key, content_type, size, sha256)
+    VALUES ($1, $2, $3, $4, $5, $6, $7)
+    ON CONFLICT (release_id, name) DO NOTHING",
+  )
+  .bind(asset.id)
+  .bind(asset.release_id)
+  .bind(&asset.name)
+  .bind(&asset.object_key)
+  .bind(&asset.content_type)
+  .bind(asset.size)
+  .bind(&asset.sha256)
+  .execute(&mut *transaction)
+  .await?;
+  if result.rows_affected() == 0 {
+    return Ok(AddAssetResult::NameTaken);
+  }
+
+  transaction.commit().await?;
+
+  Ok(AddAssetResult::Added)
+}
+
+// Returns the storage key of the removed asset
+pub async fn delete_release_asset(
+  release_id: &Uuid,
+  name: &str,
+  pool: &PgPool,
+) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+  let object_key: Option<String> =
+    sqlx::query_scalar(r"DELETE FROM release_assets WHERE release_id = $1 AND name = $2 RETURNING object_key")
+      .bind(release_id)
+      .bind(name)
+      .fetch_optional(pool)
+      .await?;
+
+  Ok(object_key)
+}
+
+// Finds the asset and counts the download
+pub async fn download_release_asset(
+  project_id: &Uuid,
+  tag: &str,
+  name: &str,
+  count: bool,
+  pool: &PgPool,
+) -> Result<Option<ReleaseAsset>, Box<dyn std::error::Error + Send + Sync>> {
+  let query = format!(
+    r"UPDATE release_assets SET downloads = downloads + $4::int
+WHERE name = $3 AND release_id = (SELECT id FROM project_releases WHERE project_id = $1 AND tag = $2)
+RETURNING {RELEASE_ASSET_COLUMNS}"
+  );
+  let asset: Option<ReleaseAsset> = sqlx::query_as(&query)
+    .bind(project_id)
+    .bind(tag)
+    .bind(name)
+    .bind(count as i32)
+    .fetch_optional(pool)
+    .await?;
+
+  Ok(asset)
+}
+
+pub async fn get_project_tags(project_id: &Uuid, pool: &PgPool) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+  let result: Vec<String> = sqlx::query_scalar(r"SELECT tag FROM project_tags WHERE project_id = $1 ORDER BY tag")
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+
+  Ok(result)
+}
+
+pub async fn is_project_liked(project_id: &Uuid, user_id: &Option<Uuid>, pool: &PgPool) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+  let result: bool = sqlx::query_scalar(r"SELECT EXISTS(SELECT 1 FROM projects_likes WHERE project_id = $1 AND user_id = $2)")
+    .bind(project_id)
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?;
+
+  Ok(result)
+}
+
+// Returns whether anything changed and the current number of likes
+pub async fn set_project_like(
+  project_id: &Uuid,
+  user_id: &Uuid,
+  liked: bool,
+  pool: &PgPool,
+) -> Result<(bool, i32), Box<dyn std::error::Error + Send + Sync>> {
+  let query = match liked {
+    true => r"INSERT INTO projects_likes (project_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+    false => r"DELETE FROM projects_likes WHERE project_id = $1 AND user_id = $2",
+  };
+
+  let mut transaction = pool.begin().await?;
+
+  let changed = sqlx::query(query)
+    .bind(project_id)
+    .bind(user_id)
+    .execute(&mut *transaction)
+    .await?
+    .rows_affected()
+    > 0;
+
+  let delta = match (changed, liked) {
+    (false, _) => 0,
+    (true, true) => 1,
+    (true, false) => -1,
+  };
+  let likes: i32 = sqlx::query_scalar(r"UPDATE projects SET likes = likes + $2 WHERE id = $1 RETURNING likes")
+    .bind(project_id)
+    .bind(delta)
+    .fetch_one(&mut *transaction)
+    .await?;
+
+  transaction.commit().await?;
+
+  Ok((changed, likes))
+}
+
+pub async fn get_catalogue(
+  viewer: Option<Uuid>,
+  filter: &CatalogueFilter,
+  sort: ProjectSort,
+  cursor: Option<CatalogueCursor>,
+  limit: i64,
+  pool: &PgPool,
+) -> Result<Page<ProjectCardWithOwner>, Box<dyn std::error::Error + Send + Sync>> {
+  let column = sort.column();
+  let after_cursor = match sort {
+    ProjectSort::Likes => "($9::integer IS NULL OR (projects.likes, projects.id) < ($9, $10))".to_string(),
+    _ => format!("($8::timestamptz IS NULL OR ({column}, projects.id) < ($8, $10))"),
+  };
+  let query = format!(
+    r#"SELECT projects.tournament,
+  projects.id,
+  projects.name,
+  projects.display_name,
+  projects.owner_id,
+  users.name                                                          AS owner_name,
+  projects.visibility,
+  projects.description,
+  '/v1/media/' || projects.thumbnail                                  AS thumbnail,
+  projects.likes,
+  projects.created_at,
+  projects.updated_at,
+  EXISTS(SELECT 1 FROM projects_likes WHERE project_id = projects.id AND $1 IS NOT NULL AND user_id = $1) as "liked",
+  ARRAY(SELECT tag FROM project_tags WHERE project_id = projects.id ORDER BY tag) AS tags
+FROM projects
+    INNER JOIN users ON projects.owner_id = users.id
+WHERE (is_project_listed(projects.visibility, $1) OR is_project_member(projects.id, $1)) AND can_view_profile(users.id, $1)
+  AND ($2::varchar IS NULL OR users.name = $2)
+  AND ($3::boolean IS NULL OR projects.tournament = $3)
+  AND ($4::boolean IS NULL OR (coalesce(projects.website_url, '') <> '') = $4)
+  AND ($5::boolean IS NULL OR (coalesce(projects.github_url, '') <> '') = $5)
+  AND ($6::timestamptz IS NULL OR projects.created_at >= $6)
+  AND ($7::timestamptz IS NULL OR projects.created_at < $7)
+  AND ($12::varchar IS NULL OR EXISTS(SELECT 1 FROM project_tags WHERE project_id = projects.id
+      AND tag = coalesce((SELECT tag FROM tag_aliases WHERE alias = $12), $12)))
+  AND {after_cursor}
+ORDER BY {column} DESC, projects.id DESC
+LIMIT $11"#
+  );
+
+  let (time, likes, id) = match cursor {
+    Some(CatalogueCursor::Time(time, id)) => (Some(time), None, Some(id)),
+    Some(CatalogueCursor::Likes(likes, id)) => (None, Some(likes), Some(id)),
+    None => (None, None, None),
+  };
+  let rows: Vec<ProjectCardWithOwner> = sqlx::query_as(&query)
+    .bind(viewer)
+    .bind(&filter.owner)
+    .bind(filter.tournament)
+    .bind(filter.website)
+    .bind(filter.github)
+    .bind(filter.created_after)
+    .bind(filter.created_before)
+    .bind(time)
+    .bind(likes)
+    .bind(id)
+    .bind(limit + 1)
+    .bind(&filter.tag)
+    .fetch_all(pool)
+    .await?;
+
+  Ok(Page::from_rows(rows, limit, |project| sort.encode_cursor(project)))
+}
+
+// Matches in the snippet are wrapped in the STX and ETX control characters, which are removed from the text
+const SEARCH_QUERY: &str = r#"SELECT projects.id,
+  projects.name,
+  projects.display_name,
+  projects.owner_id,
+  users.name                                                          AS owner_name,
+  projects.visibility,
+  projects.description,
+  '/v1/media/' || projects.thumbnail                                  AS thumbnail,
+  projects.likes,
+  ts_rank_cd(projects.search, query)                                  AS rank,
+  ts_headline('codefest_polish',
+              translate(coalesce(projects.description, '') || E'\n' || projects.content, E'\x02\x03', ''),
+              query,
+              'StartSel=' || chr(2) || ', StopSel=' || chr(3) || ', MaxWords=30, MinWords=12, MaxFragments=2, FragmentDelimiter=" … "') AS snippet
+FROM projects
+    INNER JOIN users ON projects.owner_id = users.id,
+    (SELECT tsquery_and_agg(search_term(term.prefix, term.word)) AS query
+     FROM unnest($2::text[], $3::text[]) AS term(prefix, word)) terms
+WHERE projects.search @@ query AND (is_project_listed(projects.visibility, $1) OR is_project_member(projects.id, $1))
+  AND can_view_profile(users.id, $1)
+ORDER BY rank DESC, projects.likes DESC, projects.id DESC
+LIMIT $4"#;
+
+pub async fn search_projects(
+  viewer: Option<Uuid>,
+  terms: &[SearchTerm],
+  limit: i64,
+  pool: &PgPool,
+) -> Result<Vec<ProjectSearchResult>, Box<dyn std::error::Error + Send + Sync>> {
+  let prefixes: Vec<&str> = terms.iter().map(|term| term.prefix.as_str()).collect();
+  let words: Vec<&str> = terms.iter().map(|term| term.word.as_str()).collect();
+  let mut result: Vec<ProjectSearchResult> = sqlx::query_as(SEARCH_QUERY)
+    .bind(viewer)
+    .bind(prefixes)
+    .bind(words)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+  for project in result.iter_mut() {
+    project.snippet = highlight_snippet(&project.snippet);
+  }
+
+  Ok(result)
+}
+
+const MEMBER_COLUMNS: &str = r"users.id AS user_id, users.name, users.display_name, project_members.role, project_members.accepted, project_members.created_at";
+
+// Members ordered by role, pending invitations are included on request
+pub async fn get_members(
+  project_id: &Uuid,
+  with_pending: bool,
+  pool: &PgPool,
+) -> Result<Vec<ProjectMember>, Box<dyn std::error::Error + Send + Sync>> {
+  let query = format!(
+    r"SELECT {MEMBER_COLUMNS}
+FROM project_members
+    INNER JOIN users ON project_members.user_id = users.id
+WHERE project_members.project_id = $1 AND (project_members.accepted = true OR $2)
+ORDER BY project_members.role, project_members.created_at"
+  );
+  let result: Vec<ProjectMember> = sqlx::query_as(&query).bind(project_id).bind(with_pending).fetch_all(pool).await?;
+
+  Ok(result)
+}
+
+// Role of the user with whether they accepted the invitation
+pub async fn get_member_role(
+  project_id: &Uuid,
+  user_id: &Uuid,
+  pool: &PgPool,
+) -> Result<Option<(ProjectRole, bool)>, Box<dyn std::error::Error + Send + Sync>> {
+  let result: Option<(ProjectRole, bool)> =
+    sqlx::query_as(r"SELECT role, accepted FROM project_members WHERE project_id = $1 AND user_id = $2")
+      .bind(project_id)
+      .bind(user_id)
+      .fetch_optional(pool)
+      .await?;
+
+  Ok(result)
+}
+
+// Returns false if the user is already a member or invited
+pub async fn invite_member(
+  project_id: &Uuid,
+  user_id: &Uuid,
+  role: ProjectRole,
+  invited_by: &Uuid,
+  pool: &PgPool,
+) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+  let result = sqlx::query(
+    r"INSERT INTO project_members (project_id, user_id, role, invited_by) VALUES ($1, $2, $3, $4)
+    ON CONFLICT DO NOTHING",
+  )
+  .bind(project_id)
+  .bind(user_id)
+  .bind(role)
+  .bind(invited_by)
+  .execute(pool)
+  .await?;
+
+  Ok(result.rows_affected() == 1)
+}
+
+// Returns false if there is no pending invitation
+pub async fn accept_invitation(project_id: &Uuid, user_id: &Uuid, pool: &PgPool) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+  let result = sqlx::query(
+    r"UPDATE project_members SET accepted = true WHERE project_id = $1 AND user_id = $2 AND accepted = false",
+  )
+  .bind(project_id)
+  .bind(user_id)
+  .execute(pool)
+  .await?;
+
+  Ok(result.rows_affected() == 1)
+}
+
+// The owner of the project namespace keeps their role, returns false if nothing changed
+pub async fn set_member_role(
+  project_id: &Uuid,
+  user_id: &Uuid,
+  role: ProjectRole,
+  pool: &PgPool,
+) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+  let result = sqlx::query(
+    r"UPDATE project_members SET role = $3
+    WHERE project_id = $1 AND user_id = $2 AND user_id <> (SELECT owner_id FROM projects WHERE id = $1)",
+  )
+  .bind(project_id)
+  .bind(user_id)
+  .bind(role)
+  .execute(pool)
+  .await?;
+
+  Ok(result.rows_affected() == 1)
+}
+
+// Removes a member or a pending invitation, never the owner of the project namespace
+pub async fn remove_member(project_id: &Uuid, user_id: &Uuid, pool: &PgPool) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+  let result = sqlx::query(
+    r"DELETE FROM project_members
+    WHERE project_id = $1 AND user_id = $2 AND user_id <> (SELECT owner_id FROM projects WHERE id = $1)",
+  )
+  .bind(project_id)
+  .bind(user_id)
+  .execute(pool)
+  .await?;
+
+  Ok(result.rows_affected() == 1)
+}
+
+const GET_INVITATIONS_QUERY: &str = r"SELECT projects.id AS project_id,
+  projects.name AS project_name,
+  projects.display_name AS project_display_name,
+  owners.name AS owner_name,
+  project_members.role,
+  inviters.name AS invited_by,
+  project_members.created_at
+FROM project_members
+    INNER JOIN projects ON project_members.project_id = projects.id
+    INNER JOIN users owners ON projects.owner_id = owners.id
+    LEFT JOIN users inviters ON project_members.invited_by = inviters.id
+WHERE project_members.user_id = $1 AND project_members.accepted = false
+ORDER BY project_members.created_at DESC";
+
+pub async fn get_invitations(user_id: &Uuid, pool: &PgPool) -> Result<Vec<ProjectInvitation>, Box<dyn std::error::Error + Send + Sync>> {
+  let result: Vec<ProjectInvitation> = sqlx::query_as(GET_INVITATIONS_QUERY).bind(user_id).fetch_all(pool).await?;
+
+  Ok(result)
+}
+
+const REVISION_COLUMNS: &str = r"project_revisions.number,
+  project_revisions.author_id,
+  users.name AS author_name,
+  project_revisions.fields,
+  project_revisions.restored_from,
+  project_revisions.created_at";
+
+// Newest revisions first, without their values
+pub async fn get_revisions(
+  project_id: &Uuid,
+  before: Option<i32>,
+  limit: i64,
+  pool: &PgPool,
+) -> Result<Page<ProjectRevision>, Box<dyn std::error::Error + Send + Sync>> {
+  let query = format!(
+    r"SELECT {REVISION_COLUMNS}
+FROM project_revisions
+    LEFT JOIN users ON project_revisions.author_id = users.id
+WHERE project_revisions.project_id = $1 AND ($2::integer IS NULL OR project_revisions.number < $2)
+ORDER BY project_revisions.number DESC
+LIMIT $3"
+  );
+  let rows: Vec<ProjectRevision> = sqlx::query_as(&query).bind(project_id).bind(before).bind(limit + 1).fetch_all(pool).await?;
+
+  Ok(Page::from_rows(rows, limit, |revision| revision.number.to_string()))
+}
+
+pub async fn get_revision(
+  project_id: &Uuid,
+  number: i32,
+  pool: &PgPool,
+) -> Result<Option<ProjectRevision>, Box<dyn std::error::Error + Send + Sync>> {
+  let query = format!(
+    r"SELECT {REVISION_COLUMNS}, project_revisions.previous, project_revisions.snapshot
+FROM project_revisions
+    LEFT JOIN users ON project_revisions.author_id = users.id
+WHERE project_revisions.project_id = $1 AND project_revisions.number = $2"
+  );
+  let result: Option<ProjectRevision> = sqlx::query_as(&query).bind(project_id).bind(number).fetch_optional(pool).await?;
+
+  Ok(result)
+}
+
+// Rendering of the content with the given hash
+pub async fn get_render(project_id: &Uuid, content_sha256: &str, pool: &PgPool) -> Result<Option<Rendered>, Box<dyn std::error::Error + Send + Sync>> {
+  let result: Option<(String, Json<Vec<TocEntry>>, String)> =
+    sqlx::query_as(r"SELECT html, toc, excerpt FROM project_renders WHERE project_id = $1 AND content_sha256 = $2")
+      .bind(project_id)
+      .bind(content_sha256)
+      .fetch_optional(pool)
+      .await?;
+
+  Ok(result.map(|(html, toc, excerpt)| Rendered { html, toc: toc.0, excerpt }))
+}
+
+pub async fn store_render(
+  project_id: &Uuid,
+  content_sha256: &str,
+  rendered: &Rendered,
+  pool: &PgPool,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+  sqlx::query(
+    r"INSERT INTO project_renders (project_id, content_sha256, html, toc, excerpt) VALUES ($1, $2, $3, $4, $5)
+    ON CONFLICT (project_id) DO UPDATE
+    SET content_sha256 = excluded.content_sha256, html = excluded.html, toc = excluded.toc, excerpt = excluded.excerpt, rendered_at = now()",
+  )
+  .bind(project_id)
+  .bind(content_sha256)
+  .bind(&rendered.html)
+  .bind(Json(&rendered.toc))
+  .bind(&rendered.excerpt)
+  .execute(pool)
+  .await?;
+
+  Ok(())
+}
+
+// Keeps the previous address of the project, replacing the redirect taken by its new address
+async fn move_address(
+  project_id: &Uuid,
+  previous: (&Uuid, &str),
+  current: (&Uuid, &str),
+  connection: &mut PgConnection,
+) -> Result<(), sqlx::Error> {
+  sqlx::query(
+    r"INSERT INTO project_redirects (owner_id, name, project_id) VALUES ($1, $2, $3)
+    ON CONFLICT (owner_id, name) DO UPDATE SET project_id = excluded.project_id, created_at = now()",
+  )
+  .bind(previous.0)
+  .bind(previous.1)
+  .bind(project_id)
+  .execute(&mut *connection)
+  .await?;
+  sqlx::query(r"DELETE FROM project_redirects WHERE owner_id = $1 AND name = $2")
+    .bind(current.0)
+    .bind(current.1)
+    .execute(&mut *connection)
+    .await?;
+
+  Ok(())
+}
+
+// Returns false if the owner already has a project with the name
+pub async fn rename_project(project: &Project, name: &str, pool: &PgPool) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+  let mut transaction = pool.begin().await?;
+
+  let renamed = sqlx::query(
+    r"UPDATE projects SET name = $2, updated_at = now(), version = version + 1
+    WHERE id = $1 AND owner_id = $3 AND NOT EXISTS(SELECT 1 FROM projects WHERE owner_id = $3 AND name = $2)",
+  )
+  .bind(project.id)
+  .bind(name)
+  .bind(project.owner_id)
+  .execute(&mut *transaction)
+  .await?
+  .rows_affected()
+    == 1;
+  if !renamed {
+    return Ok(false);
+  }
+
+  move_address(&project.id, (&project.owner_id, &project.name), (&project.owner_id, name), &mut transaction).await?;
 
   transaction.commit().await?;
 
+  Ok(true)
+}
+
+// Current owner and name of a renamed or transferred project the viewer can see
+pub async fn get_redirect(
+  username: &str,
+  project_name: &str,
+  viewer: Option<Uuid>,
+  pool: &PgPool,
+) -> Result<Option<(String, String)>, Box<dyn std::error::Error + Send + Sync>> {
+  let query = r"SELECT users.name, projects.name
+FROM project_redirects
+    INNER JOIN users previous_owners ON project_redirects.owner_id = previous_owners.id
+    INNER JOIN projects ON project_redirects.project_id = projects.id
+    INNER JOIN users ON projects.owner_id = users.id
+WHERE previous_owners.name = $1 AND project_redirects.name = $2
+  AND can_view_project(projects.id, projects.owner_id, projects.visibility, $3)";
+
+  let result: Option<(String, String)> = sqlx::query_as(query)
+    .bind(username)
+    .bind(project_name)
+    .bind(viewer)
+    .fetch_optional(pool)
+    .await?;
+
+  Ok(result)
+}
+
+// Replaces the pending transfer of the project
+pub async fn set_transfer(
+  project_id: &Uuid,
+  sender_id: &Uuid,
+  recipient_id: &Uuid,
+  pool: &PgPool,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+  sqlx::query(
+    r"INSERT INTO project_transfers (project_id, sender_id, recipient_id) VALUES ($1, $2, $3)
+    ON CONFLICT (project_id) DO UPDATE SET sender_id = excluded.sender_id, recipient_id = excluded.recipient_id, created_at = now()",
+  )
+  .bind(project_id)
+  .bind(sender_id)
+  .bind(recipient_id)
+  .execute(pool)
+  .await?;
+
   Ok(())
-}
\ No newline at end of file
+}
+
+// Sender and recipient of the pending transfer
+pub async fn get_transfer(project_id: &Uuid, pool: &PgPool) -> Result<Option<(Uuid, Uuid)>, Box<dyn std::error::Error + Send + Sync>> {
+  let result: Option<(Uuid, Uuid)> = sqlx::query_as(r"SELECT sender_id, recipient_id FROM project_transfers WHERE project_id = $1")
+    .bind(project_id)
+    .fetch_optional(pool)
+    .await?;
+
+  Ok(result)
+}
+
+pub async fn delete_transfer(project_id: &Uuid, pool: &PgPool) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+  let result = sqlx::query(r"DELETE FROM project_transfers WHERE project_id = $1")
+    .bind(project_id)
+    .execute(pool)
+    .await?;
+
+  Ok(result.rows_affected() == 1)
+}
+
+pub enum TransferResult {
+  // Whether the project is still entered in the contest
+  Transferred { tournament: bool },
+  NameTaken,
+  QuotaExceeded,
+  NotPending,
+}
+
+// Moves the project with its files to the recipient, the previous owner stays a maintainer
+pub async fn transfer_project(
+  project_id: &Uuid,
+  recipient_id: &Uuid,
+  pool: &PgPool,
+) -> Result<TransferResult, Box<dyn std::error::Error + Send + Sync>> {
+  let mut transaction = pool.begin().await?;
+
+  let project: Option<(Uuid, String, bool)> = sqlx::query_as(r"SELECT owner_id, name, tournament FROM projects WHERE id = $1 FOR UPDATE")
+    .bind(project_id)
+    .fetch_optional(&mut *transaction)
+    .await?;
+  let Some((owner_id, name, tournament)) = project else {
+    return Ok(TransferResult::NotPending);
+  };
+
+  // The transfer is void once the sender is not the owner anymore
+  let pending: bool = sqlx::query_scalar(
+    r"SELECT EXISTS(SELECT 1 FROM project_transfers WHERE project_id = $1 AND recipient_id = $2 AND sender_id = $3)",
+  )
+  .bind(project_id)
+  .bind(recipient_id)
+  .bind(owner_id)
+  .fetch_one(&mut *transaction)
+  .await?;
+  if !pending {
+    return Ok(TransferResult::NotPending);
+  }
+  let name_taken: bool = sqlx::query_scalar(r"SELECT EXISTS(SELECT 1 FROM projects WHERE owner_id = $1 AND name = $2)")
+    .bind(recipient_id)
+    .bind(&name)
+    .fetch_one(&mut *transaction)
+    .await?;
+  if name_taken {
+    return Ok(TransferResult::NameTaken);
+  }
+  // Files of the project move to the quota of the recipient
+  let size: i64 = sqlx::query_scalar(r"SELECT coalesce(sum(size), 0)::bigint FROM stored_files WHERE project_id = $1 AND owner_id <> $2")
+    .bind(project_id)
+    .bind(recipient_id)
+    .fetch_one(&mut *transaction)
+    .await?;
+  if !check_quota_in(recipient_id, None, size, &mut transaction).await? {
+    return Ok(TransferResult::QuotaExceeded);
+  }
+
+  // Each user enters the contest with a single project
+  let tournament = tournament
+    && !sqlx::query_scalar::<_, bool>(r"SELECT EXISTS(SELECT 1 FROM projects WHERE owner_id = $1 AND tournament = true)")
+      .bind(recipient_id)
+      .fetch_one(&mut *transaction)
+      .await?;
+
+  sqlx::query(r"UPDATE projects SET owner_id = $2, tournament = $3, updated_at = now(), version = version + 1 WHERE id = $1")
+    .bind(project_id)
+    .bind(recipient_id)
+    .bind(tournament)
+    .execute(&mut *transaction)
+    .await?;
+  move_address(project_id, (&owner_id, &name), (recipient_id, &name), &mut transaction).await?;
+  sqlx::query(r"UPDATE project_members SET role = 'maintainer' WHERE project_id = $1 AND user_id = $2")
+    .bind(project_id)
+    .bind(owner_id)
+    .execute(&mut *transaction)
+    .await?;
+  sqlx::query(
+    r"INSERT INTO project_members (project_id, user_id, role, invited_by, accepted) VALUES ($1, $2, 'owner', $3, true)
+    ON CONFLICT (project_id, user_id) DO UPDATE SET role = 'owner', accepted = true",
+  )
+  .bind(project_id)
+  .bind(recipient_id)
+  .bind(owner_id)
+  .execute(&mut *transaction)
+  .await?;
+  // Files of the project count towards the quota of its owner
+  sqlx::query(r"UPDATE stored_files SET owner_id = $2 WHERE project_id = $1")
+    .bind(project_id)
+    .bind(recipient_id)
+    .execute(&mut *transaction)
+    .await?;
+  sqlx::query(r"DELETE FROM project_transfers WHERE project_id = $1")
+    .bind(project_id)
+    .execute(&mut *transaction)
+    .await?;
+
+  transaction.commit().await?;
+
+  Ok(TransferResult::Transferred { tournament })
+}
+
+const GET_TRANSFERS_QUERY: &str = r"SELECT projects.id AS project_id,
+  projects.name AS project_name,
+  projects.display_name AS project_display_name,
+  users.name AS owner_name,
+  project_transfers.created_at
+FROM project_transfers
+    INNER JOIN projects ON project_transfers.project_id = projects.id AND project_transfers.sender_id = projects.owner_id
+    INNER JOIN users ON projects.owner_id = users.id
+WHERE project_transfers.recipient_id = $1
+ORDER BY project_transfers.created_at DESC";
+
+pub async fn get_transfers(user_id: &Uuid, pool: &PgPool) -> Result<Vec<ProjectTransfer>, Box<dyn std::error::Error + Send + Sync>> {
+  let result: Vec<ProjectTransfer> = sqlx::query_as(GET_TRANSFERS_QUERY).bind(user_id).fetch_all(pool).await?;
+
+  Ok(result)
+}
+
+// Counts the view unless the viewer has already seen the project that day
+pub async fn record_view(
+  project_id: &Uuid,
+  day: NaiveDate,
+  viewer_hash: &str,
+  pool: &PgPool,
+) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+  let query = r"WITH viewed AS (
+    INSERT INTO project_views (project_id, day, viewer_hash) VALUES ($1, $2, $3)
+    ON CONFLICT DO NOTHING
+    RETURNING project_id
+)
+UPDATE projects SET views = views + 1 WHERE id IN (SELECT project_id FROM viewed)";
+
+  let result = sqlx::query(query)
+    .bind(project_id)
+    .bind(day)
+    .bind(viewer_hash)
+    .execute(pool)
+    .await?;
+
+  Ok(result.rows_affected() == 1)
+}
+
+// Views and likes lose half of their weight every few days, new projects count up to twice as much.
+// Views older than the window are forgotten. School projects are ranked too, guests don't get them
+// from `get_trending`. Returns the number of ranked projects.
+pub async fn refresh_trending(today: NaiveDate, pool: &PgPool) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+  let query = r"INSERT INTO project_trending (project_id, score)
+SELECT projects.id,
+  (coalesce(views.score, 0) + $3 * coalesce(likes.score, 0))
+    * (1 + power(0.5, extract(epoch FROM now() - projects.created_at) / 86400 / $4))
+FROM projects
+    LEFT JOIN (SELECT project_id, sum(power(0.5, ($1 - day) / $2)) AS score
+               FROM project_views
+               WHERE day > $1 - $5::integer
+               GROUP BY project_id) views ON views.project_id = projects.id
+    LEFT JOIN (SELECT project_id, sum(power(0.5, extract(epoch FROM now() - liked_at) / 86400 / $2)) AS score
+               FROM projects_likes
+               WHERE liked_at > now() - make_interval(days => $5)
+               GROUP BY project_id) likes ON likes.project_id = projects.id
+WHERE projects.visibility IN ('public', 'school') AND (views.score IS NOT NULL OR likes.score IS NOT NULL)";
+
+  let mut transaction = pool.begin().await?;
+
+  sqlx::query(r"DELETE FROM project_trending").execute(&mut *transaction).await?;
+  let ranked = sqlx::query(query)
+    .bind(today)
+    .bind(HALF_LIFE_DAYS)
+    .bind(LIKE_WEIGHT)
+    .bind(NEW_PROJECT_HALF_LIFE_DAYS)
+    .bind(VIEW_WINDOW_DAYS)
+    .execute(&mut *transaction)
+    .await?
+    .rows_affected();
+  sqlx::query(r"DELETE FROM project_views WHERE day <= $1 - $2::integer")
+    .bind(today)
+    .bind(VIEW_WINDOW_DAYS)
+    .execute(&mut *transaction)
+    .await?;
+
+  transaction.commit().await?;
+
+  Ok(ranked)
+}
+
+const GET_TRENDING_QUERY: &str = r#"SELECT projects.tournament,
+  projects.id,
+  projects.name,
+  projects.display_name,
+  projects.owner_id,
+  users.name                                                          AS owner_name,
+  projects.visibility,
+  projects.description,
+  '/v1/media/' || projects.thumbnail                                  AS thumbnail,
+  projects.likes,
+  projects.created_at,
+  projects.updated_at,
+  EXISTS(SELECT 1 FROM projects_likes WHERE project_id = projects.id AND $1 IS NOT NULL AND user_id = $1) as "liked",
+  ARRAY(SELECT tag FROM project_tags WHERE project_id = projects.id ORDER BY tag) AS tags
+FROM project_trending
+    INNER JOIN projects ON project_trending.project_id = projects.id
+    INNER JOIN users ON projects.owner_id = users.id
+WHERE is_project_listed(projects.visibility, $1) AND can_view_profile(users.id, $1)
+ORDER BY project_trending.score DESC, projects.id DESC
+LIMIT $2"#;
+
+pub async fn get_trending(
+  viewer: Option<Uuid>,
+  limit: i64,
+  pool: &PgPool,
+) -> Result<Vec<ProjectCardWithOwner>, Box<dyn std::error::Error + Send + Sync>> {
+  let result: Vec<ProjectCardWithOwner> = sqlx::query_as(GET_TRENDING_QUERY)
+    .bind(viewer)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+  Ok(result)
+}
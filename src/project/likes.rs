@@ -0,0 +1,102 @@
+use serde_json::json;
+use sqlx::PgPool;
+use tracing::warn;
+use uuid::Uuid;
+use warp::http::StatusCode;
+use warp::reply::{self, json};
+use warp::{reject, Reply};
+
+use crate::badges::rules::{spawn_evaluate, BadgeEvent};
+use crate::error::Error;
+use crate::project::db;
+use crate::project::releases::visible_project;
+use crate::user;
+use crate::WebResult;
+
+// GET v1/projects/USER_NAME/PROJECT_NAME/like
+pub async fn get_like(
+  username: String,
+  project_name: String,
+  user_uid: Option<Uuid>,
+  db_pool: PgPool,
+) -> WebResult<impl Reply> {
+  let project = visible_project(&username, &project_name, &user_uid, &db_pool).await?;
+
+  match db::is_project_liked(&project.id, &user_uid, &db_pool).await {
+    Ok(liked) => Ok(json(&json!({ "liked": liked, "likes": project.likes }))),
+    Err(err) => {
+      warn!("Failed to check like of {}: {err}", project.id);
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// PUT v1/projects/USER_NAME/PROJECT_NAME/like
+pub async fn like_project(
+  username: String,
+  project_name: String,
+  user_uid: Option<Uuid>,
+  db_pool: PgPool,
+) -> WebResult<impl Reply> {
+  set_like(username, project_name, user_uid, true, db_pool).await
+}
+
+// DELETE v1/projects/USER_NAME/PROJECT_NAME/like
+pub async fn unlike_project(
+  username: String,
+  project_name: String,
+  user_uid: Option<Uuid>,
+  db_pool: PgPool,
+) -> WebResult<impl Reply> {
+  set_like(username, project_name, user_uid, false, db_pool).await
+}
+
+// Liking twice or removing a missing like changes nothing
+async fn set_like(
+  username: String,
+  project_name: String,
+  user_uid: Option<Uuid>,
+  liked: bool,
+  db_pool: PgPool,
+) -> WebResult<reply::WithStatus<reply::Json>> {
+  let user_id = user_uid.ok_or(Error::Unauthorized)?;
+  let project = visible_project(&username, &project_name, &user_uid, &db_pool).await?;
+
+  // A blocked user can still take the like back
+  if liked {
+    match user::db::has_blocked(&project.owner_id, &user_id, &db_pool).await {
+      Ok(false) => {}
+      Ok(true) => {
+        return Ok(reply::with_status(
+          json(&json!({ "success": false, "message": "Nie możesz polubić tego projektu" })),
+          StatusCode::FORBIDDEN,
+        ));
+      }
+      Err(err) => {
+        warn!("Failed to check is user blocked: {err}");
+        return Err(reject::custom(Error::ServerProblem));
+      }
+    }
+  }
+
+  let (changed, likes) = match db::set_project_like(&project.id, &user_id, liked, &db_pool).await {
+    Ok(result) => result,
+    Err(err) => {
+      warn!("Failed to set like of {}: {err}", project.id);
+      return Err(reject::custom(Error::ServerProblem));
+    }
+  };
+
+  if changed && liked {
+    spawn_evaluate(project.owner_id, BadgeEvent::LikeReceived, &db_pool);
+  }
+
+  let message = match liked {
+    true => "Polubiono projekt",
+    false => "Usunięto polubienie",
+  };
+  Ok(reply::with_status(
+    json(&json!({ "success": true, "message": message, "liked": liked, "likes": likes })),
+    StatusCode::OK,
+  ))
+}
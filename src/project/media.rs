@@ -0,0 +1,324 @@
+use std::path::Path;
+
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::PgPool;
+use tracing::{info, warn};
+use uuid::Uuid;
+use warp::http::StatusCode;
+use warp::multipart::FormData;
+use warp::reply::{self, json};
+use warp::{reject, Reply};
+
+use crate::error::Error;
+use crate::project::db;
+use crate::project::members::project_access;
+use crate::project::models::{media_url, Project, ProjectImage};
+use crate::project::responses::PostProjectResponse;
+use crate::storage::quota::{check_quota, delete_file, store_file};
+use crate::storage::SharedStorage;
+use crate::upload::image::{
+  crop_to_ratio, decode_image, encode_image, extension_for_mime, fit_within, output_mime, resize_exact, ImageKind,
+};
+use crate::upload::receive::{process_blocking, receive_image, temp_upload_path, UploadError};
+use crate::WebResult;
+
+pub const PROJECT_IMAGE_MAX_SIZE: u64 = 10 * 1024 * 1024;
+pub const GALLERY_LIMIT: i64 = 12;
+const CAPTION_MAX_LENGTH: usize = 200;
+// Thumbnails are cropped to 16:9
+const THUMBNAIL_SIZE: (u32, u32) = (960, 540);
+const GALLERY_MAX_SIZE: (u32, u32) = (1920, 1080);
+
+struct ProcessedImage {
+  mime: &'static str,
+  bytes: Vec<u8>,
+}
+
+fn process_thumbnail(path: &Path, kind: ImageKind) -> image::ImageResult<ProcessedImage> {
+  let (width, height) = THUMBNAIL_SIZE;
+  let image = resize_exact(&crop_to_ratio(&decode_image(path, kind)?, width, height), width, height);
+  let mime = output_mime(&image);
+  Ok(ProcessedImage { mime, bytes: encode_image(&image, mime)? })
+}
+
+fn process_gallery_image(path: &Path, kind: ImageKind) -> image::ImageResult<ProcessedImage> {
+  let (width, height) = GALLERY_MAX_SIZE;
+  let image = fit_within(decode_image(path, kind)?, width, height);
+  let mime = output_mime(&image);
+  Ok(ProcessedImage { mime, bytes: encode_image(&image, mime)? })
+}
+
+// Empty captions are removed
+pub fn validate_caption(caption: Option<String>) -> Result<Option<String>, String> {
+  match caption.map(|caption| caption.trim().to_string()) {
+    Some(caption) if caption.chars().count() > CAPTION_MAX_LENGTH => {
+      Err(format!("Podpis nie może przekraczać {CAPTION_MAX_LENGTH} znaków."))
+    }
+    Some(caption) if caption.is_empty() => Ok(None),
+    caption => Ok(caption),
+  }
+}
+
+// Finds the project, rejects anyone but its owners and maintainers
+pub(super) async fn owned_project(username: &String, project_name: &String, user_uid: &Option<Uuid>, db_pool: &PgPool) -> WebResult<Project> {
+  user_uid.ok_or(Error::Unauthorized)?;
+  match project_access(username, project_name, user_uid, db_pool).await? {
+    (project, Some(role)) if role.can_edit() => Ok(project),
+    _ => Err(reject::custom(Error::Unauthorized)),
+  }
+}
+
+// Receives, processes and stores the image within the quotas. Returns its storage key.
+async fn store_image(
+  form: FormData,
+  project: &Project,
+  process: fn(&Path, ImageKind) -> image::ImageResult<ProcessedImage>,
+  db_pool: &PgPool,
+  storage: &SharedStorage,
+) -> Result<(String, Option<String>), UploadError> {
+  let project_id = &project.id;
+  let temp_path = temp_upload_path();
+  let result = async {
+    let mut received = receive_image(form, &temp_path, project_id.as_bytes(), PROJECT_IMAGE_MAX_SIZE).await?;
+    let caption = validate_caption(received.fields.remove("caption")).map_err(UploadError::InvalidField)?;
+
+    let path = temp_path.clone();
+    let processed = process_blocking(move || process(&path, received.kind)).await?;
+
+    // Every upload gets a new key, so it can be cached forever
+    let object_key = format!("projects/{}/{}.{}", project_id, Uuid::new_v4(), extension_for_mime(processed.mime));
+    match check_quota(&project.owner_id, Some(project_id), processed.bytes.len() as i64, db_pool).await {
+      Ok(true) => {}
+      Ok(false) => return Err(UploadError::QuotaExceeded),
+      Err(err) => {
+        warn!("Failed to check quota of {project_id}: {err}");
+        return Err(UploadError::Server);
+      }
+    }
+    store_file(&object_key, processed.bytes, processed.mime, &project.owner_id, Some(project_id), storage, db_pool)
+      .await
+      .map_err(|err| {
+        warn!("Failed to store project image {object_key}: {err}");
+        UploadError::Server
+      })?;
+    Ok((object_key, caption))
+  }
+  .await;
+  // The original is never kept
+  let _ = tokio::fs::remove_file(&temp_path).await;
+  result
+}
+
+// POST v1/projects/USER_NAME/PROJECT_NAME/thumbnail
+pub async fn upload_thumbnail(
+  username: String,
+  project_name: String,
+  user_uid: Option<Uuid>,
+  form: FormData,
+  db_pool: PgPool,
+  storage: SharedStorage,
+) -> WebResult<impl Reply> {
+  let project = owned_project(&username, &project_name, &user_uid, &db_pool).await?;
+
+  let object_key = match store_image(form, &project, process_thumbnail, &db_pool, &storage).await {
+    Ok((object_key, _)) => object_key,
+    Err(err) => return Ok(err.reply()),
+  };
+
+  let previous = match db::set_thumbnail(&project.id, Some(&object_key), &db_pool).await {
+    Ok(previous) => previous,
+    Err(err) => {
+      warn!("Failed to set thumbnail of {}: {err}", project.id);
+      delete_file(&object_key, &storage, &db_pool).await;
+      return Ok(UploadError::Server.reply());
+    }
+  };
+  if let Some(previous) = previous {
+    delete_file(&previous, &storage, &db_pool).await;
+  }
+
+  info!("Uploaded thumbnail of {username}/{project_name}");
+  Ok(reply::with_status(
+    json(&json!({
+      "success": true,
+      "message": "Zaktualizowano miniaturę.",
+      "thumbnail": media_url(&object_key),
+    })),
+    StatusCode::OK,
+  ))
+}
+
+// DELETE v1/projects/USER_NAME/PROJECT_NAME/thumbnail
+pub async fn delete_thumbnail(
+  username: String,
+  project_name: String,
+  user_uid: Option<Uuid>,
+  db_pool: PgPool,
+  storage: SharedStorage,
+) -> WebResult<impl Reply> {
+  let project = owned_project(&username, &project_name, &user_uid, &db_pool).await?;
+
+  match db::set_thumbnail(&project.id, None, &db_pool).await {
+    Ok(Some(previous)) => delete_file(&previous, &storage, &db_pool).await,
+    Ok(None) => {}
+    Err(err) => {
+      warn!("Failed to remove thumbnail of {}: {err}", project.id);
+      return Err(reject::custom(Error::ServerProblem));
+    }
+  }
+
+  Ok(json(&PostProjectResponse {
+    success: true,
+    created: false,
+    message: "Usunięto miniaturę.".into(),
+  }))
+}
+
+// POST v1/projects/USER_NAME/PROJECT_NAME/gallery
+pub async fn upload_gallery_image(
+  username: String,
+  project_name: String,
+  user_uid: Option<Uuid>,
+  form: FormData,
+  db_pool: PgPool,
+  storage: SharedStorage,
+) -> WebResult<impl Reply> {
+  let project = owned_project(&username, &project_name, &user_uid, &db_pool).await?;
+
+  let (object_key, caption) = match store_image(form, &project, process_gallery_image, &db_pool, &storage).await {
+    Ok(stored) => stored,
+    Err(err) => return Ok(err.reply()),
+  };
+
+  let image_id = Uuid::new_v4();
+  let position = match db::add_gallery_image(&project.id, &image_id, caption.as_deref(), &object_key, GALLERY_LIMIT, &db_pool).await {
+    Ok(Some(position)) => position,
+    Ok(None) => {
+      delete_file(&object_key, &storage, &db_pool).await;
+      return Ok(UploadError::LimitReached("Galeria może zawierać najwyżej 12 zdjęć.").reply());
+    }
+    Err(err) => {
+      warn!("Failed to add gallery image of {}: {err}", project.id);
+      delete_file(&object_key, &storage, &db_pool).await;
+      return Ok(UploadError::Server.reply());
+    }
+  };
+
+  info!("Uploaded gallery image of {username}/{project_name}");
+  Ok(reply::with_status(
+    json(&json!({
+      "success": true,
+      "message": "Dodano zdjęcie do galerii.",
+      "image": ProjectImage {
+        id: image_id,
+        position,
+        caption,
+        url: media_url(&object_key),
+      },
+    })),
+    StatusCode::CREATED,
+  ))
+}
+
+#[derive(Deserialize)]
+pub struct PatchGalleryImage {
+  pub caption: Option<String>,
+}
+
+// PATCH v1/projects/USER_NAME/PROJECT_NAME/gallery/ID
+pub async fn patch_gallery_image(
+  username: String,
+  project_name: String,
+  image_id: Uuid,
+  user_uid: Option<Uuid>,
+  body: PatchGalleryImage,
+  db_pool: PgPool,
+) -> WebResult<impl Reply> {
+  let project = owned_project(&username, &project_name, &user_uid, &db_pool).await?;
+
+  let caption = match validate_caption(body.caption) {
+    Ok(caption) => caption,
+    Err(message) => {
+      return Ok(json(&PostProjectResponse {
+        success: false,
+        created: false,
+        message,
+      }));
+    }
+  };
+
+  match db::set_gallery_caption(&project.id, &image_id, caption.as_deref(), &db_pool).await {
+    Ok(true) => Ok(json(&PostProjectResponse {
+      success: true,
+      created: false,
+      message: "Zaktualizowano podpis.".into(),
+    })),
+    Ok(false) => Err(reject::custom(Error::NotFound)),
+    Err(err) => {
+      warn!("Failed to update gallery caption: {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+#[derive(Deserialize)]
+pub struct GalleryOrder {
+  pub order: Vec<Uuid>,
+}
+
+// PUT v1/projects/USER_NAME/PROJECT_NAME/gallery/order
+pub async fn reorder_gallery(
+  username: String,
+  project_name: String,
+  user_uid: Option<Uuid>,
+  body: GalleryOrder,
+  db_pool: PgPool,
+) -> WebResult<impl Reply> {
+  let project = owned_project(&username, &project_name, &user_uid, &db_pool).await?;
+
+  match db::reorder_gallery(&project.id, &body.order, &db_pool).await {
+    Ok(true) => Ok(json(&PostProjectResponse {
+      success: true,
+      created: false,
+      message: "Zmieniono kolejność zdjęć.".into(),
+    })),
+    Ok(false) => Ok(json(&PostProjectResponse {
+      success: false,
+      created: false,
+      message: "Kolejność musi zawierać każde zdjęcie galerii.".into(),
+    })),
+    Err(err) => {
+      warn!("Failed to reorder gallery: {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// DELETE v1/projects/USER_NAME/PROJECT_NAME/gallery/ID
+pub async fn delete_gallery_image(
+  username: String,
+  project_name: String,
+  image_id: Uuid,
+  user_uid: Option<Uuid>,
+  db_pool: PgPool,
+  storage: SharedStorage,
+) -> WebResult<impl Reply> {
+  let project = owned_project(&username, &project_name, &user_uid, &db_pool).await?;
+
+  match db::delete_gallery_image(&project.id, &image_id, &db_pool).await {
+    Ok(Some(object_key)) => {
+      delete_file(&object_key, &storage, &db_pool).await;
+      Ok(json(&PostProjectResponse {
+        success: true,
+        created: false,
+        message: "Usunięto zdjęcie z galerii.".into(),
+      }))
+    }
+    Ok(None) => Err(reject::custom(Error::NotFound)),
+    Err(err) => {
+      warn!("Failed to delete gallery image: {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
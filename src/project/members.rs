@@ -0,0 +1,278 @@
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::PgPool;
+use tracing::{info, warn};
+use uuid::Uuid;
+use warp::http::StatusCode;
+use warp::{reject, reply, Reply};
+
+use crate::classes;
+use crate::error::Error;
+use crate::prelude::web_json;
+use crate::project::db;
+use crate::project::models::{Project, ProjectRole, ProjectViewer, ProjectVisibility};
+use crate::user;
+use crate::WebResult;
+
+#[derive(Deserialize)]
+pub struct InviteBody {
+  pub username: String,
+  pub role: ProjectRole,
+}
+
+#[derive(Deserialize)]
+pub struct RoleBody {
+  pub role: ProjectRole,
+}
+
+fn response(success: bool, message: &str, status: StatusCode) -> reply::WithStatus<reply::Json> {
+  reply::with_status(reply::json(&json!({ "success": success, "message": message })), status)
+}
+
+pub(super) async fn find_project(username: &String, project_name: &String, db_pool: &PgPool) -> WebResult<Project> {
+  match db::get_project_by_ownername_projectname(username, project_name, db_pool).await {
+    Ok(Some(project)) => Ok(project),
+    Ok(None) => Err(reject::custom(Error::ProjectNotFound)),
+    Err(err) => {
+      warn!("Failed to get project {username}/{project_name}: {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// Role of the user with whether they accepted the invitation
+async fn member_role(project: &Project, user_uid: &Option<Uuid>, db_pool: &PgPool) -> WebResult<Option<(ProjectRole, bool)>> {
+  let Some(user_id) = user_uid else {
+    return Ok(None);
+  };
+  match db::get_member_role(&project.id, user_id, db_pool).await {
+    Ok(role) => Ok(role),
+    Err(err) => {
+      warn!("Failed to get role of {user_id} in {}: {err}", project.id);
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// Teachers are looked up only when the project is hidden from other users
+async fn project_viewer(project: &Project, user_uid: &Option<Uuid>, member: bool, db_pool: &PgPool) -> WebResult<ProjectViewer> {
+  let Some(user_id) = user_uid else {
+    return Ok(ProjectViewer::Guest);
+  };
+  if member {
+    return Ok(ProjectViewer::Member);
+  }
+  if project.visibility != ProjectVisibility::Private {
+    return Ok(ProjectViewer::User);
+  }
+  match classes::db::is_student_teacher(&project.owner_id, user_id, db_pool).await {
+    Ok(true) => Ok(ProjectViewer::Teacher),
+    Ok(false) => Ok(ProjectViewer::User),
+    Err(err) => {
+      warn!("Failed to check teacher of {}: {err}", project.owner_id);
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// Resolves the project with the role of the user, school projects are visible only to signed in users
+// and private ones only to members and teachers of the owner, who get no role
+pub async fn project_access(
+  username: &String,
+  project_name: &String,
+  user_uid: &Option<Uuid>,
+  db_pool: &PgPool,
+) -> WebResult<(Project, Option<ProjectRole>)> {
+  let project = find_project(username, project_name, db_pool).await?;
+  let role = match member_role(&project, user_uid, db_pool).await? {
+    Some((role, true)) => Some(role),
+    _ => None,
+  };
+  let viewer = project_viewer(&project, user_uid, role.is_some(), db_pool).await?;
+  if !project.visibility.can_view(viewer) {
+    return match project.visibility {
+      ProjectVisibility::School => Err(reject::custom(Error::Unauthorized)),
+      _ => Err(reject::custom(Error::ProjectNotFound)),
+    };
+  }
+  Ok((project, role))
+}
+
+pub(super) async fn managed_project(username: &String, project_name: &String, user_uid: &Option<Uuid>, db_pool: &PgPool) -> WebResult<Project> {
+  user_uid.ok_or(Error::Unauthorized)?;
+  match project_access(username, project_name, user_uid, db_pool).await? {
+    (project, Some(role)) if role.can_manage() => Ok(project),
+    _ => Err(reject::custom(Error::NoPermission)),
+  }
+}
+
+pub(super) async fn find_user(username: &str, db_pool: &PgPool) -> WebResult<Uuid> {
+  let username = username.trim().to_lowercase();
+  match user::db::get_user(&username, db_pool).await {
+    Ok(Some(user)) => Ok(user.id),
+    Ok(None) => Err(reject::custom(Error::UserNotFound)),
+    Err(err) => {
+      warn!("Failed to get user '{username}': {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// GET v1/projects/USER_NAME/PROJECT_NAME/members
+pub async fn get_members(
+  username: String,
+  project_name: String,
+  user_uid: Option<Uuid>,
+  db_pool: PgPool,
+) -> WebResult<impl Reply> {
+  let (project, role) = project_access(&username, &project_name, &user_uid, &db_pool).await?;
+  let with_pending = role.is_some_and(|role| role.can_manage());
+
+  match db::get_members(&project.id, with_pending, &db_pool).await {
+    Ok(members) => web_json(&members),
+    Err(err) => {
+      warn!("Failed to get members of {}: {err}", project.id);
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// POST v1/projects/USER_NAME/PROJECT_NAME/members
+pub async fn invite_member(
+  username: String,
+  project_name: String,
+  user_uid: Option<Uuid>,
+  body: InviteBody,
+  db_pool: PgPool,
+) -> WebResult<impl Reply> {
+  let project = managed_project(&username, &project_name, &user_uid, &db_pool).await?;
+  let inviter_id = user_uid.ok_or(Error::Unauthorized)?;
+  if !body.role.can_be_granted() {
+    return Ok(response(false, "Nie można nadać roli autora projektu", StatusCode::BAD_REQUEST));
+  }
+  let member_id = find_user(&body.username, &db_pool).await?;
+
+  match user::db::has_blocked(&member_id, &inviter_id, &db_pool).await {
+    Ok(false) => {}
+    Ok(true) => return Ok(response(false, "Nie możesz zaprosić tego użytkownika", StatusCode::FORBIDDEN)),
+    Err(err) => {
+      warn!("Failed to check is user blocked: {err}");
+      return Err(reject::custom(Error::ServerProblem));
+    }
+  }
+
+  match db::invite_member(&project.id, &member_id, body.role, &inviter_id, &db_pool).await {
+    Ok(true) => {
+      info!("User {member_id} invited to {} as {:?} by {inviter_id}", project.id, body.role);
+      Ok(response(true, "Wysłano zaproszenie", StatusCode::CREATED))
+    }
+    Ok(false) => Ok(response(false, "Użytkownik jest już członkiem projektu lub został zaproszony", StatusCode::CONFLICT)),
+    Err(err) => {
+      warn!("Failed to invite {member_id} to {}: {err}", project.id);
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// PATCH v1/projects/USER_NAME/PROJECT_NAME/members/MEMBER_NAME
+pub async fn patch_member(
+  username: String,
+  project_name: String,
+  member_name: String,
+  user_uid: Option<Uuid>,
+  body: RoleBody,
+  db_pool: PgPool,
+) -> WebResult<impl Reply> {
+  let project = managed_project(&username, &project_name, &user_uid, &db_pool).await?;
+  let member_id = find_user(&member_name, &db_pool).await?;
+  if member_id == project.owner_id {
+    return Ok(response(false, "Nie można zmienić roli autora projektu", StatusCode::BAD_REQUEST));
+  }
+  if !body.role.can_be_granted() {
+    return Ok(response(false, "Nie można nadać roli autora projektu", StatusCode::BAD_REQUEST));
+  }
+
+  match db::set_member_role(&project.id, &member_id, body.role, &db_pool).await {
+    Ok(true) => Ok(response(true, "Zmieniono rolę", StatusCode::OK)),
+    Ok(false) => Err(reject::custom(Error::NotFound)),
+    Err(err) => {
+      warn!("Failed to set role of {member_id} in {}: {err}", project.id);
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// Owners remove anyone, members leave the project or decline the invitation themselves
+// DELETE v1/projects/USER_NAME/PROJECT_NAME/members/MEMBER_NAME
+pub async fn remove_member(
+  username: String,
+  project_name: String,
+  member_name: String,
+  user_uid: Option<Uuid>,
+  db_pool: PgPool,
+) -> WebResult<impl Reply> {
+  let user_id = user_uid.ok_or(Error::Unauthorized)?;
+  let member_id = find_user(&member_name, &db_pool).await?;
+  let project = match member_id == user_id {
+    true => find_project(&username, &project_name, &db_pool).await?,
+    false => managed_project(&username, &project_name, &user_uid, &db_pool).await?,
+  };
+  if member_id == project.owner_id {
+    return Ok(response(false, "Nie można usunąć autora projektu", StatusCode::BAD_REQUEST));
+  }
+
+  match db::remove_member(&project.id, &member_id, &db_pool).await {
+    Ok(true) => {
+      info!("User {member_id} removed from {} by {user_id}", project.id);
+      Ok(response(true, "Usunięto członka projektu", StatusCode::OK))
+    }
+    // Private projects are not revealed to users who are not members
+    Ok(false) if member_id == user_id && project.visibility == ProjectVisibility::Private => Err(reject::custom(Error::ProjectNotFound)),
+    Ok(false) => Err(reject::custom(Error::NotFound)),
+    Err(err) => {
+      warn!("Failed to remove {member_id} from {}: {err}", project.id);
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// POST v1/projects/USER_NAME/PROJECT_NAME/invitation
+pub async fn accept_invitation(
+  username: String,
+  project_name: String,
+  user_uid: Option<Uuid>,
+  db_pool: PgPool,
+) -> WebResult<impl Reply> {
+  let user_id = user_uid.ok_or(Error::Unauthorized)?;
+  let project = find_project(&username, &project_name, &db_pool).await?;
+
+  match member_role(&project, &user_uid, &db_pool).await? {
+    Some((_, true)) => return Ok(response(true, "Już jesteś członkiem projektu", StatusCode::OK)),
+    Some((_, false)) => {}
+    None => return Err(reject::custom(Error::ProjectNotFound)),
+  }
+
+  match db::accept_invitation(&project.id, &user_id, &db_pool).await {
+    Ok(_) => {
+      info!("User {user_id} joined {}", project.id);
+      Ok(response(true, "Dołączono do projektu", StatusCode::OK))
+    }
+    Err(err) => {
+      warn!("Failed to accept invitation of {user_id} to {}: {err}", project.id);
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// GET v1/invitations
+pub async fn get_invitations(user_uid: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  let user_id = user_uid.ok_or(Error::Unauthorized)?;
+
+  match db::get_invitations(&user_id, &db_pool).await {
+    Ok(invitations) => web_json(&invitations),
+    Err(err) => {
+      warn!("Failed to get invitations of {user_id}: {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
@@ -1,5 +1,14 @@
 pub mod models;
 pub mod responses;
 pub mod api;
+pub mod catalogue;
 pub mod db;
+pub mod likes;
+pub mod media;
+pub mod members;
+pub mod releases;
+pub mod revisions;
+pub mod search;
+pub mod transfer;
+pub mod trending;
 pub mod routes;
@@ -1,7 +1,9 @@
 use chrono::{DateTime, Utc};
 use chrono::serde::ts_milliseconds;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use sqlx::prelude::FromRow;
+use sqlx::types::Json;
 use sqlx::Decode;
 use uuid::Uuid;
 
@@ -13,8 +15,11 @@ pub struct Project {
 
   pub owner_id: Uuid,
 
-  pub private: bool,
+  pub visibility: ProjectVisibility,
   pub description: Option<String>,
+  // Storage key of the thumbnail
+  #[sqlx(default)]
+  pub thumbnail: Option<String>,
 
   pub content: String,
   pub github_url: Option<String>,
@@ -22,6 +27,12 @@ pub struct Project {
 
   pub tournament: bool,
   pub likes: i32,
+  // Increased with every update, guards concurrent edits
+  #[sqlx(default)]
+  pub version: i32,
+  // Unique views, counted once a day per viewer
+  #[sqlx(default)]
+  pub views: i32,
 
   #[serde(with = "ts_milliseconds")]
   pub created_at: DateTime<Utc>,
@@ -29,6 +40,11 @@ pub struct Project {
   pub updated_at: DateTime<Utc>,
 }
 
+// Strong validator of the project, sent as its ETag
+pub fn project_etag(version: i32) -> String {
+  format!("\"{version}\"")
+}
+
 #[derive(FromRow, Serialize, Deserialize)]
 pub struct ProjectCard {
   pub id: Uuid,
@@ -36,12 +52,17 @@ pub struct ProjectCard {
   pub display_name: String,
 
   pub owner_id: Uuid,
+  pub owner_name: String,
 
-  pub private: bool,
+  pub visibility: ProjectVisibility,
   pub description: Option<String>,
+  // URL of the thumbnail
+  pub thumbnail: Option<String>,
 
   pub tournament: bool,
   pub likes: i32,
+  // Liked by the viewer
+  pub liked: bool,
 
   #[serde(with = "ts_milliseconds")]
   pub created_at: DateTime<Utc>,
@@ -69,6 +90,7 @@ pub struct ContestProject {
   pub owner_display_name: String,
   
   pub description: Option<String>,
+  pub thumbnail: Option<String>,
   
   pub votes: i32,
 
@@ -78,7 +100,6 @@ pub struct ContestProject {
   pub updated_at: DateTime<Utc>,
 }
 
-
 #[derive(FromRow, Serialize, Deserialize)]
 pub struct ProjectCardWithOwner {
   pub id: Uuid,
@@ -86,15 +107,235 @@ pub struct ProjectCardWithOwner {
   pub display_name: String,
 
   pub owner_id: Uuid,
+  pub owner_name: String,
 
-  pub private: bool,
+  pub visibility: ProjectVisibility,
   pub description: Option<String>,
+  // URL of the thumbnail
+  pub thumbnail: Option<String>,
 
   pub tournament: bool,
   pub likes: i32,
+  // Liked by the viewer
+  pub liked: bool,
+  pub tags: Vec<String>,
+
+  #[serde(with = "ts_milliseconds")]
+  pub created_at: DateTime<Utc>,
+  #[serde(with = "ts_milliseconds")]
+  pub updated_at: DateTime<Utc>,
+}
+
+#[derive(FromRow, Serialize)]
+pub struct ProjectSearchResult {
+  pub id: Uuid,
+  pub name: String,
+  pub display_name: String,
+
+  pub owner_id: Uuid,
+  pub owner_name: String,
+
+  pub visibility: ProjectVisibility,
+  pub description: Option<String>,
+  // URL of the thumbnail
+  pub thumbnail: Option<String>,
+  pub likes: i32,
+
+  pub rank: f32,
+  // Matched fragments of the description or content as HTML, matches are in <mark>
+  pub snippet: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "project_role", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectRole {
+  Owner,
+  Maintainer,
+  Viewer,
+}
+
+impl ProjectRole {
+  // Edits the project, its media and releases
+  pub fn can_edit(&self) -> bool {
+    matches!(self, ProjectRole::Owner | ProjectRole::Maintainer)
+  }
+
+  // Manages members, visibility and the contest entry, deletes the project
+  pub fn can_manage(&self) -> bool {
+    matches!(self, ProjectRole::Owner)
+  }
+
+  // Only the author in the project URL is an owner
+  pub fn can_be_granted(&self) -> bool {
+    !matches!(self, ProjectRole::Owner)
+  }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "project_visibility", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectVisibility {
+  #[default]
+  Public,
+  // Reachable by the address, left out of listings
+  Unlisted,
+  // Signed in users only
+  School,
+  // Members only
+  Private,
+}
+
+impl ProjectVisibility {
+  // The former `private` flag, still accepted from older clients
+  pub fn from_private(private: bool) -> Self {
+    if private {
+      ProjectVisibility::Private
+    } else {
+      ProjectVisibility::Public
+    }
+  }
+
+  // Mirrors `can_view_project` in the database
+  pub fn can_view(&self, viewer: ProjectViewer) -> bool {
+    match self {
+      ProjectVisibility::Public | ProjectVisibility::Unlisted => true,
+      ProjectVisibility::School => viewer != ProjectViewer::Guest,
+      ProjectVisibility::Private => matches!(viewer, ProjectViewer::Teacher | ProjectViewer::Member),
+    }
+  }
+
+  // Only public projects can enter the contest
+  pub fn allows_tournament(&self) -> bool {
+    matches!(self, ProjectVisibility::Public)
+  }
+}
+
+// Teachers of a class the owner attends read the project without a role, so they never edit it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectViewer {
+  Guest,
+  User,
+  Teacher,
+  Member,
+}
+
+#[derive(FromRow, Serialize)]
+pub struct ProjectMember {
+  pub user_id: Uuid,
+  pub name: String,
+  pub display_name: String,
+  pub role: ProjectRole,
+  // Pending invitations are listed only to owners
+  pub accepted: bool,
+
+  #[serde(with = "ts_milliseconds")]
+  pub created_at: DateTime<Utc>,
+}
+
+#[derive(FromRow, Serialize)]
+pub struct ProjectInvitation {
+  pub project_id: Uuid,
+  pub project_name: String,
+  pub project_display_name: String,
+  pub owner_name: String,
+  pub role: ProjectRole,
+  pub invited_by: Option<String>,
+
+  #[serde(with = "ts_milliseconds")]
+  pub created_at: DateTime<Utc>,
+}
+
+#[derive(FromRow, Serialize)]
+pub struct ProjectTransfer {
+  pub project_id: Uuid,
+  pub project_name: String,
+  pub project_display_name: String,
+  pub owner_name: String,
+
+  #[serde(with = "ts_milliseconds")]
+  pub created_at: DateTime<Utc>,
+}
+
+// Fields of the project tracked by its revisions
+#[derive(FromRow, Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ProjectSnapshot {
+  pub display_name: String,
+  pub description: Option<String>,
+  pub content: String,
+  pub github_url: Option<String>,
+  pub website_url: Option<String>,
+  pub tags: Vec<String>,
+}
+
+#[derive(FromRow, Serialize)]
+pub struct ProjectRevision {
+  pub number: i32,
+  pub author_id: Uuid,
+  pub author_name: Option<String>,
+  pub fields: Vec<String>,
+  // Number of the revision this one restored
+  pub restored_from: Option<i32>,
+
+  #[serde(with = "ts_milliseconds")]
+  pub created_at: DateTime<Utc>,
+
+  // Values of the changed fields before the revision, loaded only for a single revision
+  #[sqlx(default)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub previous: Option<Json<Map<String, Value>>>,
+  #[sqlx(default)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub snapshot: Option<Json<ProjectSnapshot>>,
+}
+
+// Uploaded project images are served by the media endpoint
+pub fn media_url(object_key: &str) -> String {
+  format!("/v1/media/{object_key}")
+}
+
+#[derive(FromRow, Serialize, Deserialize)]
+pub struct ProjectImage {
+  pub id: Uuid,
+  pub position: i32,
+  pub caption: Option<String>,
+  // URL of the image
+  pub url: String,
+}
+
+#[derive(FromRow, Serialize)]
+pub struct Release {
+  pub id: Uuid,
+  pub tag: String,
+  // Markdown
+  pub notes: String,
 
   #[serde(with = "ts_milliseconds")]
   pub created_at: DateTime<Utc>,
   #[serde(with = "ts_milliseconds")]
   pub updated_at: DateTime<Utc>,
+
+  #[sqlx(skip)]
+  pub assets: Vec<ReleaseAsset>,
+}
+
+#[derive(FromRow, Serialize)]
+pub struct ReleaseAsset {
+  pub id: Uuid,
+  #[serde(skip)]
+  pub release_id: Uuid,
+  pub name: String,
+  #[serde(skip)]
+  pub object_key: String,
+  pub content_type: String,
+  pub size: i64,
+  pub sha256: String,
+  pub downloads: i64,
+
+  #[serde(with = "ts_milliseconds")]
+  pub created_at: DateTime<Utc>,
+
+  // URL of the download, set by the handler
+  #[sqlx(skip)]
+  pub url: String,
 }
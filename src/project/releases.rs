@@ -0,0 +1,490 @@
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::PgPool;
+use tracing::{info, warn};
+use uuid::Uuid;
+use warp::http::StatusCode;
+use warp::multipart::FormData;
+use warp::reply::{self, json};
+use warp::{reject, Reply};
+
+use crate::error::Error;
+use crate::project::db::{self, AddAssetResult};
+use crate::project::media::owned_project;
+use crate::project::members::project_access;
+use crate::project::models::{Project, Release, ReleaseAsset};
+use crate::project::responses::PostProjectResponse;
+use crate::storage::quota::{assign_file, check_quota, delete_file, store_file_from};
+use crate::storage::SharedStorage;
+use crate::upload::db::{get_upload, take_committed_upload};
+use crate::upload::receive::{receive_file, temp_upload_path, UploadError};
+use crate::WebResult;
+
+pub const ASSET_MAX_SIZE: u64 = 50 * 1024 * 1024;
+pub const ASSETS_LIMIT: i64 = 10;
+const TAG_MAX_LENGTH: usize = 64;
+const NOTES_MAX_LENGTH: usize = 20_000;
+const ASSET_NAME_MAX_LENGTH: usize = 100;
+const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+
+// Semantic version, a leading "v" is dropped
+pub fn validate_tag(tag: &str) -> Result<String, String> {
+  let tag = tag.trim();
+  let version = tag.strip_prefix(['v', 'V']).unwrap_or(tag);
+  if version.len() > TAG_MAX_LENGTH {
+    return Err(format!("Wersja nie może przekraczać {TAG_MAX_LENGTH} znaków."));
+  }
+  match semver::Version::parse(version) {
+    Ok(version) => Ok(version.to_string()),
+    Err(_) => Err("Wersja musi mieć format MAJOR.MINOR.PATCH, np. 1.0.0.".into()),
+  }
+}
+
+pub fn validate_notes(notes: Option<String>) -> Result<String, String> {
+  let notes = notes.unwrap_or_default().trim().to_string();
+  if notes.chars().count() > NOTES_MAX_LENGTH {
+    return Err(format!("Opis wydania nie może przekraczać {NOTES_MAX_LENGTH} znaków."));
+  }
+  Ok(notes)
+}
+
+// Keeps only the file name, which ends up in the download URL
+pub fn validate_asset_name(file_name: &str) -> Result<String, String> {
+  let name = file_name.rsplit(['/', '\\']).next().unwrap_or_default().trim().replace(' ', "-");
+  if name.is_empty() || name.starts_with('.') || name.len() > ASSET_NAME_MAX_LENGTH {
+    return Err(format!("Nazwa pliku musi mieć od 1 do {ASSET_NAME_MAX_LENGTH} znaków."));
+  }
+  if !name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '+')) {
+    return Err("Nazwa pliku może zawierać tylko litery, cyfry oraz znaki . - _ +".into());
+  }
+  Ok(name)
+}
+
+// Types with parameters or unexpected characters are not passed on to downloads
+pub fn asset_content_type(content_type: Option<&str>) -> String {
+  let valid = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '+'));
+  match content_type.and_then(|content_type| content_type.split_once('/')) {
+    Some((kind, subtype)) if valid(kind) && valid(subtype) => format!("{kind}/{subtype}").to_ascii_lowercase(),
+    _ => DEFAULT_CONTENT_TYPE.into(),
+  }
+}
+
+// Newest version first
+pub fn sort_releases(releases: &mut [Release]) {
+  releases.sort_by_cached_key(|release| std::cmp::Reverse(semver::Version::parse(&release.tag).ok()));
+}
+
+pub fn asset_url(username: &str, project_name: &str, tag: &str, name: &str) -> String {
+  format!("/v1/projects/{username}/{project_name}/releases/{tag}/assets/{name}")
+}
+
+fn set_asset_urls(release: &mut Release, username: &str, project_name: &str) {
+  for asset in &mut release.assets {
+    asset.url = asset_url(username, project_name, &release.tag, &asset.name);
+  }
+}
+
+// Finds the project if the user can see it
+pub async fn visible_project(username: &String, project_name: &String, user_uid: &Option<Uuid>, db_pool: &PgPool) -> WebResult<Project> {
+  let (project, _) = project_access(username, project_name, user_uid, db_pool).await?;
+  Ok(project)
+}
+
+async fn find_release(project: &Project, tag: &str, db_pool: &PgPool) -> WebResult<Release> {
+  // Tags are stored normalized
+  let tag = validate_tag(tag).map_err(|_| Error::NotFound)?;
+  match db::get_release(&project.id, &tag, db_pool).await {
+    Ok(Some(release)) => Ok(release),
+    Ok(None) => Err(reject::custom(Error::NotFound)),
+    Err(err) => {
+      warn!("Failed to get release {tag} of {}: {err}", project.id);
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// GET v1/projects/USER_NAME/PROJECT_NAME/releases
+pub async fn get_releases(
+  username: String,
+  project_name: String,
+  user_uid: Option<Uuid>,
+  db_pool: PgPool,
+) -> WebResult<impl Reply> {
+  let project = visible_project(&username, &project_name, &user_uid, &db_pool).await?;
+
+  let mut releases = match db::get_releases(&project.id, &db_pool).await {
+    Ok(releases) => releases,
+    Err(err) => {
+      warn!("Failed to get releases of {}: {err}", project.id);
+      return Err(reject::custom(Error::ServerProblem));
+    }
+  };
+  sort_releases(&mut releases);
+  for release in &mut releases {
+    set_asset_urls(release, &username, &project_name);
+  }
+
+  Ok(json(&releases))
+}
+
+// GET v1/projects/USER_NAME/PROJECT_NAME/releases/TAG
+pub async fn get_release(
+  username: String,
+  project_name: String,
+  tag: String,
+  user_uid: Option<Uuid>,
+  db_pool: PgPool,
+) -> WebResult<impl Reply> {
+  let project = visible_project(&username, &project_name, &user_uid, &db_pool).await?;
+  let mut release = find_release(&project, &tag, &db_pool).await?;
+  set_asset_urls(&mut release, &username, &project_name);
+
+  Ok(json(&release))
+}
+
+#[derive(Deserialize)]
+pub struct PostRelease {
+  pub tag: String,
+  pub notes: Option<String>,
+}
+
+// POST v1/projects/USER_NAME/PROJECT_NAME/releases
+pub async fn create_release(
+  username: String,
+  project_name: String,
+  user_uid: Option<Uuid>,
+  body: PostRelease,
+  db_pool: PgPool,
+) -> WebResult<impl Reply> {
+  let project = owned_project(&username, &project_name, &user_uid, &db_pool).await?;
+
+  let (tag, notes) = match (validate_tag(&body.tag), validate_notes(body.notes)) {
+    (Ok(tag), Ok(notes)) => (tag, notes),
+    (Err(message), _) | (_, Err(message)) => {
+      return Ok(reply::with_status(
+        json(&PostProjectResponse {
+          success: false,
+          created: false,
+          message,
+        }),
+        StatusCode::BAD_REQUEST,
+      ));
+    }
+  };
+
+  match db::create_release(&Uuid::new_v4(), &project.id, &tag, &notes, &db_pool).await {
+    Ok(true) => {
+      info!("Released {tag} of {username}/{project_name}");
+      Ok(reply::with_status(
+        json(&PostProjectResponse {
+          success: true,
+          created: true,
+          message: format!("Utworzono wydanie {tag}."),
+        }),
+        StatusCode::CREATED,
+      ))
+    }
+    Ok(false) => Ok(reply::with_status(
+      json(&PostProjectResponse {
+        success: false,
+        created: false,
+        message: format!("Wydanie {tag} już istnieje."),
+      }),
+      StatusCode::CONFLICT,
+    )),
+    Err(err) => {
+      warn!("Failed to create release {tag} of {}: {err}", project.id);
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+#[derive(Deserialize)]
+pub struct PatchRelease {
+  pub notes: Option<String>,
+}
+
+// PATCH v1/projects/USER_NAME/PROJECT_NAME/releases/TAG
+pub async fn patch_release(
+  username: String,
+  project_name: String,
+  tag: String,
+  user_uid: Option<Uuid>,
+  body: PatchRelease,
+  db_pool: PgPool,
+) -> WebResult<impl Reply> {
+  let project = owned_project(&username, &project_name, &user_uid, &db_pool).await?;
+  let release = find_release(&project, &tag, &db_pool).await?;
+
+  let notes = match validate_notes(body.notes) {
+    Ok(notes) => notes,
+    Err(message) => {
+      return Ok(json(&PostProjectResponse {
+        success: false,
+        created: false,
+        message,
+      }));
+    }
+  };
+
+  match db::set_release_notes(&project.id, &release.tag, &notes, &db_pool).await {
+    Ok(true) => Ok(json(&PostProjectResponse {
+      success: true,
+      created: false,
+      message: "Zaktualizowano wydanie.".into(),
+    })),
+    Ok(false) => Err(reject::custom(Error::NotFound)),
+    Err(err) => {
+      warn!("Failed to update release {tag} of {}: {err}", project.id);
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// DELETE v1/projects/USER_NAME/PROJECT_NAME/releases/TAG
+pub async fn delete_release(
+  username: String,
+  project_name: String,
+  tag: String,
+  user_uid: Option<Uuid>,
+  db_pool: PgPool,
+  storage: SharedStorage,
+) -> WebResult<impl Reply> {
+  let project = owned_project(&username, &project_name, &user_uid, &db_pool).await?;
+  let release = find_release(&project, &tag, &db_pool).await?;
+
+  match db::delete_release(&project.id, &release.tag, &db_pool).await {
+    Ok(Some(assets)) => {
+      for object_key in assets {
+        delete_file(&object_key, &storage, &db_pool).await;
+      }
+      info!("Deleted release {} of {username}/{project_name}", release.tag);
+      Ok(json(&PostProjectResponse {
+        success: true,
+        created: false,
+        message: "Usunięto wydanie.".into(),
+      }))
+    }
+    Ok(None) => Err(reject::custom(Error::NotFound)),
+    Err(err) => {
+      warn!("Failed to delete release {tag} of {}: {err}", project.id);
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// Checks the limits of the release and the quotas before adding the file
+async fn check_asset(project: &Project, release: &Release, name: &str, size: i64, db_pool: &PgPool) -> Result<(), UploadError> {
+  if release.assets.iter().any(|asset| asset.name == name) {
+    return Err(UploadError::NameTaken("Wydanie zawiera już plik o tej nazwie."));
+  }
+  if release.assets.len() as i64 >= ASSETS_LIMIT {
+    return Err(UploadError::LimitReached("Wydanie może zawierać najwyżej 10 plików."));
+  }
+
+  match check_quota(&project.owner_id, Some(&project.id), size, db_pool).await {
+    Ok(true) => Ok(()),
+    Ok(false) => Err(UploadError::QuotaExceeded),
+    Err(err) => {
+      warn!("Failed to check quota of {}: {err}", project.id);
+      Err(UploadError::Server)
+    }
+  }
+}
+
+// Receives the file, checks the quotas and stores it
+async fn store_asset(form: FormData, project: &Project, release: &Release, db_pool: &PgPool, storage: &SharedStorage) -> Result<ReleaseAsset, UploadError> {
+  let temp_path = temp_upload_path();
+  let result = async {
+    let mut received = receive_file(form, &temp_path, ASSET_MAX_SIZE).await?;
+    // The name field overrides the name of the uploaded file
+    let name = received.fields.remove("name").unwrap_or(received.file_name);
+    let name = validate_asset_name(&name).map_err(UploadError::InvalidField)?;
+    check_asset(project, release, &name, received.size as i64, db_pool).await?;
+
+    let id = Uuid::new_v4();
+    let asset = ReleaseAsset {
+      id,
+      release_id: release.id,
+      // Every upload gets a new key, so a replaced file is never served from a cache
+      object_key: format!("releases/{}/{}/{}", project.id, id, name),
+      name,
+      content_type: asset_content_type(received.content_type.as_deref()),
+      size: received.size as i64,
+      sha256: received.sha256,
+      downloads: 0,
+      created_at: chrono::Utc::now(),
+      url: String::new(),
+    };
+
+    let (owner_id, project_id) = (&project.owner_id, Some(&project.id));
+    store_file_from(&asset.object_key, &temp_path, asset.size, &asset.content_type, owner_id, project_id, storage, db_pool)
+      .await
+      .map_err(|err| {
+        warn!("Failed to store release asset {}: {err}", asset.object_key);
+        UploadError::Server
+      })?;
+    Ok(asset)
+  }
+  .await;
+  let _ = tokio::fs::remove_file(&temp_path).await;
+  result
+}
+
+// POST v1/projects/USER_NAME/PROJECT_NAME/releases/TAG/assets
+pub async fn upload_release_asset(
+  username: String,
+  project_name: String,
+  tag: String,
+  user_uid: Option<Uuid>,
+  form: FormData,
+  db_pool: PgPool,
+  storage: SharedStorage,
+) -> WebResult<impl Reply> {
+  let project = owned_project(&username, &project_name, &user_uid, &db_pool).await?;
+  let release = find_release(&project, &tag, &db_pool).await?;
+
+  let asset = match store_asset(form, &project, &release, &db_pool, &storage).await {
+    Ok(asset) => asset,
+    Err(err) => return Ok(err.reply()),
+  };
+
+  add_asset(asset, &username, &project_name, &release, &db_pool, &storage).await
+}
+
+// Adds the stored file to the release, removes it if that fails
+async fn add_asset(
+  mut asset: ReleaseAsset,
+  username: &str,
+  project_name: &str,
+  release: &Release,
+  db_pool: &PgPool,
+  storage: &SharedStorage,
+) -> WebResult<reply::WithStatus<reply::Json>> {
+  // Checked again, other uploads could finish in the meantime
+  let error = match db::add_release_asset(&asset, ASSETS_LIMIT, db_pool).await {
+    Ok(AddAssetResult::Added) => None,
+    Ok(AddAssetResult::NameTaken) => Some(UploadError::NameTaken("Wydanie zawiera już plik o tej nazwie.")),
+    Ok(AddAssetResult::LimitReached) => Some(UploadError::LimitReached("Wydanie może zawierać najwyżej 10 plików.")),
+    Err(err) => {
+      warn!("Failed to add release asset to {}: {err}", release.id);
+      Some(UploadError::Server)
+    }
+  };
+  if let Some(err) = error {
+    delete_file(&asset.object_key, storage, db_pool).await;
+    return Ok(err.reply());
+  }
+
+  info!("Added {} to release {} of {username}/{project_name}", asset.name, release.tag);
+  asset.url = asset_url(username, project_name, &release.tag, &asset.name);
+  Ok(reply::with_status(
+    json(&json!({
+      "success": true,
+      "message": "Dodano plik do wydania.",
+      "asset": asset,
+    })),
+    StatusCode::CREATED,
+  ))
+}
+
+#[derive(Deserialize)]
+pub struct AttachUpload {
+  // Id of a committed resumable upload
+  pub upload: Uuid,
+}
+
+// PUT v1/projects/USER_NAME/PROJECT_NAME/releases/TAG/assets/NAME
+#[allow(clippy::too_many_arguments)]
+pub async fn attach_upload(
+  username: String,
+  project_name: String,
+  tag: String,
+  name: String,
+  user_uid: Option<Uuid>,
+  body: AttachUpload,
+  db_pool: PgPool,
+  storage: SharedStorage,
+) -> WebResult<impl Reply> {
+  let project = owned_project(&username, &project_name, &user_uid, &db_pool).await?;
+  let uploader_id = user_uid.ok_or(Error::Unauthorized)?;
+  let release = find_release(&project, &tag, &db_pool).await?;
+
+  let name = match validate_asset_name(&name) {
+    Ok(name) => name,
+    Err(message) => return Ok(UploadError::InvalidField(message).reply()),
+  };
+  // Maintainers attach their own uploads
+  let upload = match get_upload(&body.upload, &uploader_id, &db_pool).await {
+    Ok(Some(upload)) if upload.object_key.is_some() => upload,
+    Ok(Some(_)) => return Ok(UploadError::Incomplete.reply()),
+    Ok(None) => return Ok(UploadError::MissingFile.reply()),
+    Err(err) => {
+      warn!("Failed to get upload {}: {err}", body.upload);
+      return Ok(UploadError::Server.reply());
+    }
+  };
+  if let Err(err) = check_asset(&project, &release, &name, upload.length, &db_pool).await {
+    return Ok(err.reply());
+  }
+
+  // The upload can be attached only once
+  let upload = match take_committed_upload(&body.upload, &uploader_id, &db_pool).await {
+    Ok(Some(upload)) => upload,
+    Ok(None) => return Ok(UploadError::MissingFile.reply()),
+    Err(err) => {
+      warn!("Failed to take upload {}: {err}", body.upload);
+      return Ok(UploadError::Server.reply());
+    }
+  };
+  let Some(object_key) = upload.object_key else {
+    return Ok(UploadError::Incomplete.reply());
+  };
+  if let Err(err) = assign_file(&object_key, &project.id, &project.owner_id, &db_pool).await {
+    warn!("Failed to assign {object_key} to {}: {err}", project.id);
+  }
+
+  let asset = ReleaseAsset {
+    id: Uuid::new_v4(),
+    release_id: release.id,
+    name,
+    object_key,
+    content_type: upload.content_type,
+    size: upload.length,
+    sha256: upload.sha256.unwrap_or_default(),
+    downloads: 0,
+    created_at: chrono::Utc::now(),
+    url: String::new(),
+  };
+  add_asset(asset, &username, &project_name, &release, &db_pool, &storage).await
+}
+
+// DELETE v1/projects/USER_NAME/PROJECT_NAME/releases/TAG/assets/NAME
+pub async fn delete_release_asset(
+  username: String,
+  project_name: String,
+  tag: String,
+  name: String,
+  user_uid: Option<Uuid>,
+  db_pool: PgPool,
+  storage: SharedStorage,
+) -> WebResult<impl Reply> {
+  let project = owned_project(&username, &project_name, &user_uid, &db_pool).await?;
+  let release = find_release(&project, &tag, &db_pool).await?;
+
+  match db::delete_release_asset(&release.id, &name, &db_pool).await {
+    Ok(Some(object_key)) => {
+      delete_file(&object_key, &storage, &db_pool).await;
+      Ok(json(&PostProjectResponse {
+        success: true,
+        created: false,
+        message: "Usunięto plik z wydania.".into(),
+      }))
+    }
+    Ok(None) => Err(reject::custom(Error::NotFound)),
+    Err(err) => {
+      warn!("Failed to delete release asset: {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
@@ -2,6 +2,7 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::project::models::ProjectVisibility;
 use crate::user::models::User;
 
 #[derive(Deserialize)]
@@ -9,9 +10,18 @@ pub struct PostProjectBody {
   pub name: String,
   pub display_name: String,
   pub description: Option<String>,
+  pub visibility: Option<ProjectVisibility>,
+  // Replaced by `visibility`, still accepted from older clients
+  #[serde(default)]
   pub private: bool,
 }
 
+impl PostProjectBody {
+  pub fn visibility(&self) -> ProjectVisibility {
+    self.visibility.unwrap_or(ProjectVisibility::from_private(self.private))
+  }
+}
+
 #[derive(Serialize)]
 pub struct PostProjectResponse {
   pub success: bool,
@@ -27,7 +37,7 @@ pub struct GetProjectResponse {
 
   pub owner: User,
 
-  pub private: bool,
+  pub visibility: ProjectVisibility,
   pub description: Option<String>,
 
   pub likes: u32,
@@ -0,0 +1,230 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use similar::{ChangeTag, TextDiff};
+use sqlx::PgPool;
+use tracing::{info, warn};
+use uuid::Uuid;
+use warp::reply::{json, Response};
+use warp::{reject, Reply};
+
+use crate::error::Error;
+use crate::pagination::CursorQuery;
+use crate::prelude::web_json;
+use crate::project::api::{expected_version, with_etag, PatchProject};
+use crate::project::db::{self, PatchResult};
+use crate::project::members::project_access;
+use crate::project::models::{Project, ProjectRevision, ProjectRole, ProjectSnapshot};
+use crate::project::responses::PostProjectResponse;
+use crate::tags::api::resolve_project_tags;
+use crate::WebResult;
+
+#[derive(Deserialize)]
+pub struct DiffQuery {
+  pub from: i32,
+  pub to: i32,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+pub struct ContentDiff {
+  pub additions: usize,
+  pub deletions: usize,
+  // Unified diff of the content lines
+  pub diff: String,
+}
+
+// Snapshot of the project after the patch is applied
+pub fn apply_patch(snapshot: &ProjectSnapshot, patch: &PatchProject) -> ProjectSnapshot {
+  let mut tags = patch.tags.clone().unwrap_or_else(|| snapshot.tags.clone());
+  tags.sort();
+  ProjectSnapshot {
+    display_name: patch.display_name.clone().unwrap_or_else(|| snapshot.display_name.clone()),
+    description: patch.description.clone().or_else(|| snapshot.description.clone()),
+    content: patch.content.clone().unwrap_or_else(|| snapshot.content.clone()),
+    github_url: patch.github_url.clone().or_else(|| snapshot.github_url.clone()),
+    website_url: patch.website_url.clone().or_else(|| snapshot.website_url.clone()),
+    tags,
+  }
+}
+
+// Previous values of the fields which differ between the snapshots
+pub fn changed_fields(previous: &ProjectSnapshot, current: &ProjectSnapshot) -> Map<String, Value> {
+  let mut changes = Map::new();
+  if previous.display_name != current.display_name {
+    changes.insert("display_name".into(), json!(previous.display_name));
+  }
+  if previous.description != current.description {
+    changes.insert("description".into(), json!(previous.description));
+  }
+  if previous.content != current.content {
+    changes.insert("content".into(), json!(previous.content));
+  }
+  if previous.github_url != current.github_url {
+    changes.insert("github_url".into(), json!(previous.github_url));
+  }
+  if previous.website_url != current.website_url {
+    changes.insert("website_url".into(), json!(previous.website_url));
+  }
+  if previous.tags != current.tags {
+    changes.insert("tags".into(), json!(previous.tags));
+  }
+  changes
+}
+
+pub fn content_diff(from: (i32, &str), to: (i32, &str)) -> ContentDiff {
+  let diff = TextDiff::from_lines(from.1, to.1);
+  let (mut additions, mut deletions) = (0, 0);
+  for change in diff.iter_all_changes() {
+    match change.tag() {
+      ChangeTag::Insert => additions += 1,
+      ChangeTag::Delete => deletions += 1,
+      ChangeTag::Equal => {}
+    }
+  }
+  let diff = diff
+    .unified_diff()
+    .header(&format!("#{}", from.0), &format!("#{}", to.0))
+    .to_string();
+  ContentDiff { additions, deletions, diff }
+}
+
+// The history keeps removed content, so it is shown only to members
+async fn member_project(
+  username: &String,
+  project_name: &String,
+  user_uid: &Option<Uuid>,
+  db_pool: &PgPool,
+) -> WebResult<(Project, ProjectRole)> {
+  user_uid.ok_or(Error::Unauthorized)?;
+  match project_access(username, project_name, user_uid, db_pool).await? {
+    (project, Some(role)) => Ok((project, role)),
+    _ => Err(reject::custom(Error::NoPermission)),
+  }
+}
+
+async fn find_revision(project: &Project, number: i32, db_pool: &PgPool) -> WebResult<ProjectRevision> {
+  match db::get_revision(&project.id, number, db_pool).await {
+    Ok(Some(revision)) => Ok(revision),
+    Ok(None) => Err(reject::custom(Error::NotFound)),
+    Err(err) => {
+      warn!("Failed to get revision {number} of {}: {err}", project.id);
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// GET v1/projects/USER_NAME/PROJECT_NAME/revisions?cursor=CURSOR
+pub async fn get_revisions(
+  username: String,
+  project_name: String,
+  query: CursorQuery,
+  user_uid: Option<Uuid>,
+  db_pool: PgPool,
+) -> WebResult<impl Reply> {
+  let (project, _) = member_project(&username, &project_name, &user_uid, &db_pool).await?;
+  let before: Option<i32> = match &query.cursor {
+    Some(cursor) => Some(cursor.parse().map_err(|_| Error::ParameterProblem)?),
+    None => None,
+  };
+
+  match db::get_revisions(&project.id, before, query.limit(), &db_pool).await {
+    Ok(page) => web_json(&page),
+    Err(err) => {
+      warn!("Failed to get revisions of {}: {err}", project.id);
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// GET v1/projects/USER_NAME/PROJECT_NAME/revisions/NUMBER
+pub async fn get_revision(
+  username: String,
+  project_name: String,
+  number: i32,
+  user_uid: Option<Uuid>,
+  db_pool: PgPool,
+) -> WebResult<impl Reply> {
+  let (project, _) = member_project(&username, &project_name, &user_uid, &db_pool).await?;
+  let revision = find_revision(&project, number, &db_pool).await?;
+
+  web_json(&revision)
+}
+
+// GET v1/projects/USER_NAME/PROJECT_NAME/revisions/diff?from=NUMBER&to=NUMBER
+pub async fn diff_revisions(
+  username: String,
+  project_name: String,
+  query: DiffQuery,
+  user_uid: Option<Uuid>,
+  db_pool: PgPool,
+) -> WebResult<impl Reply> {
+  let (project, _) = member_project(&username, &project_name, &user_uid, &db_pool).await?;
+  let from = find_revision(&project, query.from, &db_pool).await?;
+  let to = find_revision(&project, query.to, &db_pool).await?;
+  let (Some(from_snapshot), Some(to_snapshot)) = (&from.snapshot, &to.snapshot) else {
+    return Err(reject::custom(Error::ServerProblem));
+  };
+
+  web_json(&content_diff((from.number, &from_snapshot.content), (to.number, &to_snapshot.content)))
+}
+
+// Applies the snapshot of the revision as a new revision
+// POST v1/projects/USER_NAME/PROJECT_NAME/revisions/NUMBER/restore
+pub async fn restore_revision(
+  username: String,
+  project_name: String,
+  number: i32,
+  user_uid: Option<Uuid>,
+  if_match: Option<String>,
+  db_pool: PgPool,
+) -> WebResult<Response> {
+  let (project, role) = member_project(&username, &project_name, &user_uid, &db_pool).await?;
+  if !role.can_edit() {
+    return Err(reject::custom(Error::NoPermission));
+  }
+  let author_id = user_uid.ok_or(Error::Unauthorized)?;
+  let expected_version = expected_version(project.version, if_match.as_deref())?;
+  let revision = find_revision(&project, number, &db_pool).await?;
+  let Some(snapshot) = revision.snapshot else {
+    return Err(reject::custom(Error::ServerProblem));
+  };
+  let snapshot = snapshot.0;
+
+  // Tags merged since then are stored as aliases
+  let tags = match resolve_project_tags(snapshot.tags, &db_pool).await? {
+    Ok(tags) => tags,
+    Err(message) => {
+      return Ok(json(&PostProjectResponse {
+        success: false,
+        created: false,
+        message,
+      }).into_response());
+    }
+  };
+  let snapshot = ProjectSnapshot { tags, ..snapshot };
+
+  match db::restore_snapshot(&project, &snapshot, &author_id, number, expected_version, &db_pool).await {
+    Ok(PatchResult::Patched { revision: Some(restored), version }) => {
+      info!("Revision {number} of {} restored as {restored} by {author_id}", project.id);
+      let response = json(&PostProjectResponse {
+        success: true,
+        created: true,
+        message: format!("Przywrócono wersję {number}"),
+      });
+      Ok(with_etag(response, version))
+    }
+    Ok(PatchResult::Patched { revision: None, version }) => {
+      let response = json(&PostProjectResponse {
+        success: true,
+        created: false,
+        message: "Projekt nie różni się od tej wersji".into(),
+      });
+      Ok(with_etag(response, version))
+    }
+    Ok(PatchResult::Conflict) => Err(reject::custom(Error::VersionConflict)),
+    Ok(PatchResult::NotFound) => Err(reject::custom(Error::NotFound)),
+    Err(err) => {
+      warn!("Failed to restore revision {number} of {}: {err}", project.id);
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
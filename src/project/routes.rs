@@ -2,11 +2,17 @@ use sqlx::PgPool;
 use uuid::Uuid;
 use warp::Filter;
 
+use crate::proxy::with_client_ip;
+use crate::storage::{with_storage, SharedStorage};
 use crate::{auth::header::with_auth, db::with_db};
 
-use super::api;
+use crate::file;
 
-pub fn routes(db_pool: &PgPool) -> impl Filter<Extract=impl warp::Reply, Error=warp::Rejection> + Clone {
+use crate::pagination::CursorQuery;
+
+use super::{api, catalogue, likes, media, members, releases, revisions, search, transfer, trending};
+
+pub fn routes(db_pool: &PgPool, storage: &SharedStorage) -> impl Filter<Extract=impl warp::Reply, Error=warp::Rejection> + Clone {
   let post = warp::path!("projects")
     .and(warp::post())
     .and(warp::path::end())
@@ -19,12 +25,35 @@ pub fn routes(db_pool: &PgPool) -> impl Filter<Extract=impl warp::Reply, Error=w
   let list = warp::path("projects")
     .and(warp::get())
     .and(warp::path::end())
+    .and(with_auth())
     .and(with_db(db_pool.clone()))
     .and_then(api::new_projects);
 
+  let get_catalogue = warp::path!("projects" / "catalogue")
+    .and(warp::get())
+    .and(warp::query::<catalogue::CatalogueQuery>())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(catalogue::get_catalogue);
+
+  let search = warp::path!("projects" / "search")
+    .and(warp::get())
+    .and(warp::query::<search::SearchQuery>())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(search::search_projects);
+
+  let get_trending = warp::path!("projects" / "trending")
+    .and(warp::get())
+    .and(warp::query::<trending::TrendingQuery>())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(trending::get_trending);
+
   let get_contest = warp::path("contestprojects")
     .and(warp::get())
     .and(warp::path::end())
+    .and(with_auth())
     .and(with_db(db_pool.clone()))
     .and_then(api::contest_projects);
 
@@ -39,6 +68,7 @@ pub fn routes(db_pool: &PgPool) -> impl Filter<Extract=impl warp::Reply, Error=w
     .and(warp::get())
     .and(warp::path::end())
     .and(with_auth())
+    .and(with_client_ip())
     .and(with_db(db_pool.clone()))
     .and_then(api::get_project);
 
@@ -46,6 +76,7 @@ pub fn routes(db_pool: &PgPool) -> impl Filter<Extract=impl warp::Reply, Error=w
     .and(warp::patch())
     .and(warp::path::end())
     .and(with_auth())
+    .and(warp::header::optional::<String>("if-match"))
     .and(warp::body::content_length_limit(1024 * 16))
     .and(warp::body::json())
     .and(with_db(db_pool.clone()))
@@ -55,14 +86,317 @@ pub fn routes(db_pool: &PgPool) -> impl Filter<Extract=impl warp::Reply, Error=w
     .and(warp::delete())
     .and(warp::path::end())
     .and(with_auth())
+    .and(warp::header::optional::<String>("if-match"))
     .and(with_db(db_pool.clone()))
+    .and(with_storage(storage.clone()))
     .and_then(api::delete_project);
 
-  get
+  let upload_thumbnail = warp::path!("projects" / String / String / "thumbnail")
+    .and(warp::post())
+    .and(with_auth())
+    .and(warp::multipart::form().max_length(12 * 1024 * 1024))
+    .and(with_db(db_pool.clone()))
+    .and(with_storage(storage.clone()))
+    .and_then(media::upload_thumbnail);
+
+  let delete_thumbnail = warp::path!("projects" / String / String / "thumbnail")
+    .and(warp::delete())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and(with_storage(storage.clone()))
+    .and_then(media::delete_thumbnail);
+
+  let upload_gallery = warp::path!("projects" / String / String / "gallery")
+    .and(warp::post())
+    .and(with_auth())
+    .and(warp::multipart::form().max_length(12 * 1024 * 1024))
+    .and(with_db(db_pool.clone()))
+    .and(with_storage(storage.clone()))
+    .and_then(media::upload_gallery_image);
+
+  let reorder_gallery = warp::path!("projects" / String / String / "gallery" / "order")
+    .and(warp::put())
+    .and(with_auth())
+    .and(warp::body::content_length_limit(1024 * 16))
+    .and(warp::body::json())
+    .and(with_db(db_pool.clone()))
+    .and_then(media::reorder_gallery);
+
+  let patch_gallery = warp::path!("projects" / String / String / "gallery" / Uuid)
+    .and(warp::patch())
+    .and(with_auth())
+    .and(warp::body::content_length_limit(1024 * 16))
+    .and(warp::body::json())
+    .and(with_db(db_pool.clone()))
+    .and_then(media::patch_gallery_image);
+
+  let delete_gallery = warp::path!("projects" / String / String / "gallery" / Uuid)
+    .and(warp::delete())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and(with_storage(storage.clone()))
+    .and_then(media::delete_gallery_image);
+
+  let get_like = warp::path!("projects" / String / String / "like")
+    .and(warp::get())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(likes::get_like);
+
+  let put_like = warp::path!("projects" / String / String / "like")
+    .and(warp::put())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(likes::like_project);
+
+  let delete_like = warp::path!("projects" / String / String / "like")
+    .and(warp::delete())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(likes::unlike_project);
+
+  let get_members = warp::path!("projects" / String / String / "members")
+    .and(warp::get())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(members::get_members);
+
+  let invite_member = warp::path!("projects" / String / String / "members")
+    .and(warp::post())
+    .and(with_auth())
+    .and(warp::body::content_length_limit(1024 * 16))
+    .and(warp::body::json())
+    .and(with_db(db_pool.clone()))
+    .and_then(members::invite_member);
+
+  let patch_member = warp::path!("projects" / String / String / "members" / String)
+    .and(warp::patch())
+    .and(with_auth())
+    .and(warp::body::content_length_limit(1024 * 16))
+    .and(warp::body::json())
+    .and(with_db(db_pool.clone()))
+    .and_then(members::patch_member);
+
+  let remove_member = warp::path!("projects" / String / String / "members" / String)
+    .and(warp::delete())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(members::remove_member);
+
+  let accept_invitation = warp::path!("projects" / String / String / "invitation")
+    .and(warp::post())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(members::accept_invitation);
+
+  let get_invitations = warp::path!("invitations")
+    .and(warp::get())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(members::get_invitations);
+
+  let get_revisions = warp::path!("projects" / String / String / "revisions")
+    .and(warp::get())
+    .and(warp::query::<CursorQuery>())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(revisions::get_revisions);
+
+  let diff_revisions = warp::path!("projects" / String / String / "revisions" / "diff")
+    .and(warp::get())
+    .and(warp::query::<revisions::DiffQuery>())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(revisions::diff_revisions);
+
+  let get_revision = warp::path!("projects" / String / String / "revisions" / i32)
+    .and(warp::get())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(revisions::get_revision);
+
+  let restore_revision = warp::path!("projects" / String / String / "revisions" / i32 / "restore")
+    .and(warp::post())
+    .and(with_auth())
+    .and(warp::header::optional::<String>("if-match"))
+    .and(with_db(db_pool.clone()))
+    .and_then(revisions::restore_revision);
+
+  let get_releases = warp::path!("projects" / String / String / "releases")
+    .and(warp::get())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(releases::get_releases);
+
+  let post_release = warp::path!("projects" / String / String / "releases")
+    .and(warp::post())
+    .and(with_auth())
+    .and(warp::body::content_length_limit(1024 * 64))
+    .and(warp::body::json())
+    .and(with_db(db_pool.clone()))
+    .and_then(releases::create_release);
+
+  let get_release = warp::path!("projects" / String / String / "releases" / String)
+    .and(warp::get())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(releases::get_release);
+
+  let patch_release = warp::path!("projects" / String / String / "releases" / String)
+    .and(warp::patch())
+    .and(with_auth())
+    .and(warp::body::content_length_limit(1024 * 64))
+    .and(warp::body::json())
+    .and(with_db(db_pool.clone()))
+    .and_then(releases::patch_release);
+
+  let delete_release = warp::path!("projects" / String / String / "releases" / String)
+    .and(warp::delete())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and(with_storage(storage.clone()))
+    .and_then(releases::delete_release);
+
+  let upload_asset = warp::path!("projects" / String / String / "releases" / String / "assets")
+    .and(warp::post())
+    .and(with_auth())
+    .and(warp::multipart::form().max_length(releases::ASSET_MAX_SIZE + 1024 * 1024))
+    .and(with_db(db_pool.clone()))
+    .and(with_storage(storage.clone()))
+    .and_then(releases::upload_release_asset);
+
+  let download_asset = warp::path!("projects" / String / String / "releases" / String / "assets" / String)
+    .and(warp::get())
+    .and(with_auth())
+    .and(warp::header::headers_cloned())
+    .and(with_db(db_pool.clone()))
+    .and(with_storage(storage.clone()))
+    .and_then(file::get_release_asset);
+
+  let attach_upload = warp::path!("projects" / String / String / "releases" / String / "assets" / String)
+    .and(warp::put())
+    .and(with_auth())
+    .and(warp::body::content_length_limit(1024 * 16))
+    .and(warp::body::json())
+    .and(with_db(db_pool.clone()))
+    .and(with_storage(storage.clone()))
+    .and_then(releases::attach_upload);
+
+  let delete_asset = warp::path!("projects" / String / String / "releases" / String / "assets" / String)
+    .and(warp::delete())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and(with_storage(storage.clone()))
+    .and_then(releases::delete_release_asset);
+
+  let rename = warp::path!("projects" / String / String / "rename")
+    .and(warp::post())
+    .and(with_auth())
+    .and(warp::body::content_length_limit(1024 * 16))
+    .and(warp::body::json())
+    .and(with_db(db_pool.clone()))
+    .and_then(transfer::rename_project);
+
+  let request_transfer = warp::path!("projects" / String / String / "transfer")
+    .and(warp::post())
+    .and(with_auth())
+    .and(warp::body::content_length_limit(1024 * 16))
+    .and(warp::body::json())
+    .and(with_db(db_pool.clone()))
+    .and_then(transfer::request_transfer);
+
+  let cancel_transfer = warp::path!("projects" / String / String / "transfer")
+    .and(warp::delete())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(transfer::cancel_transfer);
+
+  let accept_transfer = warp::path!("projects" / String / String / "transfer" / "accept")
+    .and(warp::post())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(transfer::accept_transfer);
+
+  let get_transfers = warp::path!("transfers")
+    .and(warp::get())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(transfer::get_transfers);
+
+  // Tried after all other routes, so only unknown project addresses are looked up
+  let redirect = warp::path("projects")
+    .and(warp::path::param::<String>())
+    .and(warp::path::param::<String>())
+    .and(warp::path::tail())
+    .and(warp::query::raw().or(warp::any().map(String::new)).unify())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(transfer::redirect_project);
+
+  // Groups are boxed, so the first routes are not polled under a deep chain of futures
+  let projects = get
     .or(list)
+    .or(get_catalogue)
+    .or(get_trending)
+    .or(search)
     .or(post)
     .or(patch)
     .or(delete)
-    .or(get_contest)
-    .or(vote_contest)
+    .boxed();
+
+  let media = upload_thumbnail
+    .or(delete_thumbnail)
+    .or(upload_gallery)
+    .or(reorder_gallery)
+    .or(patch_gallery)
+    .or(delete_gallery)
+    .boxed();
+
+  let likes = get_like.or(put_like).or(delete_like).boxed();
+
+  let contest = get_contest.or(vote_contest).boxed();
+
+  let members = get_members
+    .or(invite_member)
+    .or(patch_member)
+    .or(remove_member)
+    .or(accept_invitation)
+    .or(get_invitations)
+    .boxed();
+
+  let revisions = get_revisions
+    .or(diff_revisions)
+    .or(get_revision)
+    .or(restore_revision)
+    .boxed();
+
+  let releases = get_releases
+    .or(post_release)
+    .or(get_release)
+    .or(patch_release)
+    .or(delete_release)
+    .or(upload_asset)
+    .or(download_asset)
+    .or(attach_upload)
+    .or(delete_asset)
+    .boxed();
+
+  let transfers = rename
+    .or(request_transfer)
+    .or(cancel_transfer)
+    .or(accept_transfer)
+    .or(get_transfers)
+    .boxed();
+
+  projects
+    .or(media)
+    .or(likes)
+    .or(contest)
+    .or(members)
+    .or(revisions)
+    .or(releases)
+    .or(transfers)
+    .or(redirect.boxed())
+    // Keeps the combined filter future off the stack
+    .boxed()
 }
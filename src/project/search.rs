@@ -0,0 +1,84 @@
+use serde::Deserialize;
+use sqlx::PgPool;
+use tracing::warn;
+use uuid::Uuid;
+use warp::{reject, Reply};
+
+use crate::error::Error;
+use crate::pagination::MAX_PAGE_SIZE;
+use crate::prelude::web_json;
+use crate::project::db;
+use crate::WebResult;
+
+const DEFAULT_RESULTS: i64 = 20;
+const MAX_TERMS: usize = 8;
+// Longer words lose their ending, so other inflected forms match by prefix without the dictionary
+const STEM_MIN_LENGTH: usize = 6;
+const STEM_ENDING_LENGTH: usize = 2;
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+  pub q: String,
+  pub limit: Option<i64>,
+}
+
+impl SearchQuery {
+  pub fn limit(&self) -> i64 {
+    self.limit.unwrap_or(DEFAULT_RESULTS).clamp(1, MAX_PAGE_SIZE)
+  }
+}
+
+// Every word is matched by its prefix or its dictionary stem
+#[derive(Debug, PartialEq, Eq)]
+pub struct SearchTerm {
+  pub word: String,
+  // tsquery of the prefix
+  pub prefix: String,
+}
+
+// Splits the query into searched words, None if nothing is searchable
+pub fn search_terms(query: &str) -> Option<Vec<SearchTerm>> {
+  let terms: Vec<SearchTerm> = query
+    .split(|c: char| !c.is_alphanumeric())
+    .filter(|word| !word.is_empty())
+    .take(MAX_TERMS)
+    .map(|word| {
+      let word = word.to_lowercase();
+      let length = word.chars().count();
+      let stem: String = match length >= STEM_MIN_LENGTH {
+        true => word.chars().take(length - STEM_ENDING_LENGTH).collect(),
+        false => word.clone(),
+      };
+      SearchTerm { prefix: format!("{stem}:*"), word }
+    })
+    .collect();
+
+  match terms.is_empty() {
+    true => None,
+    false => Some(terms),
+  }
+}
+
+// Escapes the headline text and turns its STX/ETX match markers into <mark> tags
+pub fn highlight_snippet(headline: &str) -> String {
+  headline
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+    .replace('\u{2}', "<mark>")
+    .replace('\u{3}', "</mark>")
+}
+
+// GET v1/projects/search?q=QUERY
+pub async fn search_projects(query: SearchQuery, user_uid: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  let terms = search_terms(&query.q).ok_or(Error::ParameterProblem)?;
+
+  match db::search_projects(user_uid, &terms, query.limit(), &db_pool).await {
+    Ok(results) => web_json(&results),
+    Err(err) => {
+      warn!("Failed to search projects for '{}': {err}", query.q);
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
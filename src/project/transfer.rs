@@ -0,0 +1,245 @@
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::PgPool;
+use tracing::{info, warn};
+use uuid::Uuid;
+use warp::http::{header, StatusCode};
+use warp::{reject, reply, Reply};
+
+use crate::badges::rules::{spawn_evaluate, BadgeEvent};
+use crate::error::Error;
+use crate::prelude::web_json;
+use crate::project::db::{self, TransferResult};
+use crate::project::members::{find_project, find_user, managed_project};
+use crate::project::models::{Project, ProjectVisibility};
+use crate::user;
+use crate::utils::validate_name;
+use crate::WebResult;
+
+#[derive(Deserialize)]
+pub struct RenameBody {
+  pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct TransferBody {
+  pub username: String,
+}
+
+fn response(success: bool, message: &str, status: StatusCode) -> reply::WithStatus<reply::Json> {
+  reply::with_status(reply::json(&json!({ "success": success, "message": message })), status)
+}
+
+// Address of the project with the rest of the requested path and its query
+pub fn redirect_location(owner_name: &str, project_name: &str, tail: &str, query: &str) -> String {
+  let mut location = format!("/v1/projects/{owner_name}/{project_name}");
+  if !tail.is_empty() {
+    location.push('/');
+    location.push_str(tail);
+  }
+  if !query.is_empty() {
+    location.push('?');
+    location.push_str(query);
+  }
+  location
+}
+
+async fn has_project(owner_id: &Uuid, project_name: &String, db_pool: &PgPool) -> WebResult<bool> {
+  match db::has_project_by_id(owner_id, project_name, db_pool).await {
+    Ok(exists) => Ok(exists),
+    Err(err) => {
+      warn!("Failed to check exist project: {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// Pending transfers are not revealed to other users
+fn transfer_not_found(project: &Project) -> warp::Rejection {
+  match project.visibility {
+    ProjectVisibility::Private => reject::custom(Error::ProjectNotFound),
+    _ => reject::custom(Error::NotFound),
+  }
+}
+
+// POST v1/projects/USER_NAME/PROJECT_NAME/rename
+pub async fn rename_project(
+  username: String,
+  project_name: String,
+  user_uid: Option<Uuid>,
+  body: RenameBody,
+  db_pool: PgPool,
+) -> WebResult<impl Reply> {
+  let project = managed_project(&username, &project_name, &user_uid, &db_pool).await?;
+  let name = match validate_name(body.name) {
+    Ok(name) => name,
+    Err(message) => return Ok(response(false, &message, StatusCode::BAD_REQUEST)),
+  };
+  if has_project(&project.owner_id, &name, &db_pool).await? {
+    return Ok(response(false, "Projekt już istnieje!", StatusCode::CONFLICT));
+  }
+
+  match db::rename_project(&project, &name, &db_pool).await {
+    Ok(true) => {
+      info!("Project {} renamed from '{}' to '{name}'", project.id, project.name);
+      Ok(response(true, "Zmieniono nazwę projektu", StatusCode::OK))
+    }
+    Ok(false) => Ok(response(false, "Projekt już istnieje!", StatusCode::CONFLICT)),
+    Err(err) => {
+      warn!("Failed to rename {}: {err}", project.id);
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// POST v1/projects/USER_NAME/PROJECT_NAME/transfer
+pub async fn request_transfer(
+  username: String,
+  project_name: String,
+  user_uid: Option<Uuid>,
+  body: TransferBody,
+  db_pool: PgPool,
+) -> WebResult<impl Reply> {
+  let project = managed_project(&username, &project_name, &user_uid, &db_pool).await?;
+  let sender_id = user_uid.ok_or(Error::Unauthorized)?;
+  // Only the author hands the project over
+  if sender_id != project.owner_id {
+    return Ok(response(false, "Tylko autor projektu może go przekazać", StatusCode::FORBIDDEN));
+  }
+  let recipient_id = find_user(&body.username, &db_pool).await?;
+  if recipient_id == project.owner_id {
+    return Ok(response(false, "Jesteś już autorem projektu", StatusCode::BAD_REQUEST));
+  }
+
+  match user::db::has_blocked(&recipient_id, &sender_id, &db_pool).await {
+    Ok(false) => {}
+    Ok(true) => return Ok(response(false, "Nie możesz przekazać projektu temu użytkownikowi", StatusCode::FORBIDDEN)),
+    Err(err) => {
+      warn!("Failed to check is user blocked: {err}");
+      return Err(reject::custom(Error::ServerProblem));
+    }
+  }
+  if has_project(&recipient_id, &project.name, &db_pool).await? {
+    return Ok(response(false, "Użytkownik ma już projekt o tej nazwie", StatusCode::CONFLICT));
+  }
+
+  match db::set_transfer(&project.id, &sender_id, &recipient_id, &db_pool).await {
+    Ok(()) => {
+      info!("Transfer of {} to {recipient_id} requested by {sender_id}", project.id);
+      Ok(response(true, "Wysłano prośbę o przekazanie projektu", StatusCode::CREATED))
+    }
+    Err(err) => {
+      warn!("Failed to request transfer of {} to {recipient_id}: {err}", project.id);
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// The sender cancels the transfer or the recipient declines it
+// DELETE v1/projects/USER_NAME/PROJECT_NAME/transfer
+pub async fn cancel_transfer(
+  username: String,
+  project_name: String,
+  user_uid: Option<Uuid>,
+  db_pool: PgPool,
+) -> WebResult<impl Reply> {
+  let user_id = user_uid.ok_or(Error::Unauthorized)?;
+  let project = find_project(&username, &project_name, &db_pool).await?;
+
+  match db::get_transfer(&project.id, &db_pool).await {
+    Ok(Some((sender_id, recipient_id))) if user_id == sender_id || user_id == recipient_id => {}
+    Ok(_) => return Err(transfer_not_found(&project)),
+    Err(err) => {
+      warn!("Failed to get transfer of {}: {err}", project.id);
+      return Err(reject::custom(Error::ServerProblem));
+    }
+  }
+
+  match db::delete_transfer(&project.id, &db_pool).await {
+    Ok(_) => {
+      info!("Transfer of {} cancelled by {user_id}", project.id);
+      Ok(response(true, "Anulowano przekazanie projektu", StatusCode::OK))
+    }
+    Err(err) => {
+      warn!("Failed to delete transfer of {}: {err}", project.id);
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// POST v1/projects/USER_NAME/PROJECT_NAME/transfer/accept
+pub async fn accept_transfer(
+  username: String,
+  project_name: String,
+  user_uid: Option<Uuid>,
+  db_pool: PgPool,
+) -> WebResult<impl Reply> {
+  let user_id = user_uid.ok_or(Error::Unauthorized)?;
+  let project = find_project(&username, &project_name, &db_pool).await?;
+
+  match db::transfer_project(&project.id, &user_id, &db_pool).await {
+    Ok(TransferResult::Transferred { tournament }) => {
+      info!("Project {} transferred from {} to {user_id}", project.id, project.owner_id);
+      spawn_evaluate(user_id, BadgeEvent::ProjectCreated, &db_pool);
+      if tournament {
+        spawn_evaluate(user_id, BadgeEvent::TournamentEntered, &db_pool);
+      }
+      let message = match project.tournament && !tournament {
+        // Each user enters the contest with a single project
+        true => "Przejęto projekt, został on wycofany z konkursu",
+        false => "Przejęto projekt",
+      };
+      Ok(response(true, message, StatusCode::OK))
+    }
+    Ok(TransferResult::NameTaken) => Ok(response(false, "Masz już projekt o tej nazwie", StatusCode::CONFLICT)),
+    Ok(TransferResult::QuotaExceeded) => Ok(response(
+      false,
+      "Pliki projektu przekraczają twój limit miejsca na pliki",
+      StatusCode::PAYLOAD_TOO_LARGE,
+    )),
+    Ok(TransferResult::NotPending) => Err(transfer_not_found(&project)),
+    Err(err) => {
+      warn!("Failed to transfer {} to {user_id}: {err}", project.id);
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// GET v1/transfers
+pub async fn get_transfers(user_uid: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  let user_id = user_uid.ok_or(Error::Unauthorized)?;
+
+  match db::get_transfers(&user_id, &db_pool).await {
+    Ok(transfers) => web_json(&transfers),
+    Err(err) => {
+      warn!("Failed to get transfers of {user_id}: {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// Sends requests for a previous address of a renamed or transferred project to the current one
+// ANY v1/projects/USER_NAME/PROJECT_NAME/...
+pub async fn redirect_project(
+  username: String,
+  project_name: String,
+  tail: warp::path::Tail,
+  query: String,
+  user_uid: Option<Uuid>,
+  db_pool: PgPool,
+) -> WebResult<impl Reply> {
+  match db::get_redirect(&username, &project_name, user_uid, &db_pool).await {
+    Ok(Some((owner_name, name))) => {
+      let location = redirect_location(&owner_name, &name, tail.as_str(), &query);
+      Ok(reply::with_status(
+        reply::with_header(reply(), header::LOCATION, location),
+        StatusCode::PERMANENT_REDIRECT,
+      ))
+    }
+    Ok(None) => Err(reject::not_found()),
+    Err(err) => {
+      warn!("Failed to get redirect of {username}/{project_name}: {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
@@ -0,0 +1,89 @@
+use std::net::IpAddr;
+use std::time::Duration;
+
+use chrono::{NaiveDate, Utc};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use sqlx::PgPool;
+use tracing::{info, warn};
+use uuid::Uuid;
+use warp::{reject, Reply};
+
+use crate::error::Error;
+use crate::pagination::MAX_PAGE_SIZE;
+use crate::prelude::web_json;
+use crate::project::db;
+use crate::WebResult;
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(15 * 60);
+const DEFAULT_RESULTS: i64 = 12;
+// Days after which views and likes count half as much
+pub const HALF_LIFE_DAYS: f64 = 3.0;
+// A like is worth this many views
+pub const LIKE_WEIGHT: f64 = 5.0;
+// Days after which the bonus of a new project is halved
+pub const NEW_PROJECT_HALF_LIFE_DAYS: f64 = 7.0;
+// Older views are not counted and removed
+pub const VIEW_WINDOW_DAYS: i32 = 30;
+
+#[derive(Deserialize)]
+pub struct TrendingQuery {
+  pub limit: Option<i64>,
+}
+
+impl TrendingQuery {
+  pub fn limit(&self) -> i64 {
+    self.limit.unwrap_or(DEFAULT_RESULTS).clamp(1, MAX_PAGE_SIZE)
+  }
+}
+
+// Identifies the viewer for the day only, the user or address cannot be read back without the secret
+pub fn viewer_hash(user_uid: Option<Uuid>, addr: Option<IpAddr>, day: NaiveDate) -> Option<String> {
+  let viewer = match (user_uid, addr) {
+    (Some(user_id), _) => format!("user:{user_id}"),
+    (None, Some(addr)) => format!("ip:{addr}"),
+    (None, None) => return None,
+  };
+  let mut mac = Hmac::<Sha256>::new_from_slice(dotenv!("TOKEN_SECRET").as_bytes()).expect("HMAC accepts keys of any size");
+  mac.update(format!("{day}:{viewer}").as_bytes());
+  Some(hex::encode(mac.finalize().into_bytes()))
+}
+
+// Counts the view later in a separate async task, so the request is not slowed down
+pub fn spawn_record_view(project_id: Uuid, user_uid: Option<Uuid>, addr: Option<IpAddr>, pool: &PgPool) {
+  let day = Utc::now().date_naive();
+  let Some(viewer_hash) = viewer_hash(user_uid, addr, day) else {
+    return;
+  };
+  let pool = pool.clone();
+  tokio::spawn(async move {
+    if let Err(err) = db::record_view(&project_id, day, &viewer_hash, &pool).await {
+      warn!("Failed to record view of {project_id}: {err}");
+    }
+  });
+}
+
+pub fn spawn_refresher(pool: PgPool) {
+  tokio::spawn(async move {
+    let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+    loop {
+      interval.tick().await;
+      match db::refresh_trending(Utc::now().date_naive(), &pool).await {
+        Ok(ranked) => info!("Ranked {ranked} trending projects"),
+        Err(err) => warn!("Failed to refresh trending projects: {err}"),
+      }
+    }
+  });
+}
+
+// GET v1/projects/trending?limit=LIMIT
+pub async fn get_trending(query: TrendingQuery, user_uid: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  match db::get_trending(user_uid, query.limit(), &db_pool).await {
+    Ok(projects) => web_json(&projects),
+    Err(err) => {
+      warn!("Failed to get trending projects: {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
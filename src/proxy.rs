@@ -0,0 +1,71 @@
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+
+use warp::header::headers_cloned;
+use warp::http::{HeaderMap, HeaderValue};
+use warp::Filter;
+
+// Address of the client, read from `PROXY_HEADER` when the request comes from one of `TRUSTED_PROXIES`
+pub fn with_client_ip() -> impl Filter<Extract = (Option<IpAddr>,), Error = Infallible> + Clone {
+  let trusted = trusted_proxies(dotenv!("TRUSTED_PROXIES"));
+  warp::addr::remote()
+    .and(headers_cloned())
+    .map(move |remote: Option<SocketAddr>, headers: HeaderMap<HeaderValue>| {
+      client_ip(remote, &headers, dotenv!("PROXY_HEADER"), &trusted)
+    })
+}
+
+pub fn trusted_proxies(list: &str) -> Vec<IpAddr> {
+  list.split(',').filter_map(|proxy| proxy.trim().parse().ok()).collect()
+}
+
+// Every proxy appends the address it was reached from, so the client is the last one not added by a trusted proxy.
+// Addresses sent by anyone else are ignored, as they can be forged.
+pub fn client_ip(remote: Option<SocketAddr>, headers: &HeaderMap<HeaderValue>, header: &str, trusted: &[IpAddr]) -> Option<IpAddr> {
+  let mut client = remote?.ip();
+  if !trusted.contains(&client) {
+    return Some(client);
+  }
+
+  let header = header.to_lowercase();
+  let entries: Vec<&str> = headers
+    .get_all(header.as_str())
+    .iter()
+    .filter_map(|value| value.to_str().ok())
+    .flat_map(|value| value.split(','))
+    .collect();
+  for entry in entries.iter().rev() {
+    let ip = match header.as_str() {
+      "forwarded" => forwarded_for(entry),
+      _ => parse_ip(entry),
+    };
+    // Unknown or obfuscated addresses end the chain
+    let Some(ip) = ip else {
+      break;
+    };
+    client = ip;
+    if !trusted.contains(&ip) {
+      break;
+    }
+  }
+  Some(client)
+}
+
+// The `for` parameter of a `Forwarded` element, like `for="[2001:db8::1]:4711";proto=https`
+fn forwarded_for(element: &str) -> Option<IpAddr> {
+  element
+    .split(';')
+    .filter_map(|pair| pair.trim().split_once('='))
+    .find(|(name, _)| name.eq_ignore_ascii_case("for"))
+    .and_then(|(_, value)| parse_ip(value.trim_matches('"')))
+}
+
+// An address with an optional port, IPv6 addresses in brackets
+fn parse_ip(value: &str) -> Option<IpAddr> {
+  let value = value.trim();
+  value
+    .parse::<SocketAddr>()
+    .map(|addr| addr.ip())
+    .or_else(|_| value.trim_start_matches('[').trim_end_matches(']').parse())
+    .ok()
+}
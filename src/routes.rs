@@ -2,6 +2,7 @@ use std::sync::Arc;
 
 use jsonwebtoken::EncodingKey;
 use sqlx::PgPool;
+use uuid::Uuid;
 use warp::reply::json;
 use warp::Filter;
 
@@ -10,13 +11,15 @@ use crate::auth::otp::OtpCodes;
 use crate::db::with_db;
 use crate::models::{CkziuNews, ServerServiceStatus, ServerStatus};
 use crate::prelude::web_json;
-use crate::{auth, error, file, gateway, panel, posts, project, upload, user};
+use crate::storage::{with_storage, SharedStorage};
+use crate::{auth, badges, classes, error, file, gateway, panel, posts, project, storage, tags, upload, user};
 
 pub fn routes(
     key: Arc<EncodingKey>,
     news: Arc<Vec<CkziuNews>>,
     otp_codes: OtpCodes,
     db_pool: PgPool,
+    storage: SharedStorage,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     let with_news = warp::any().map(move || Arc::clone(&news));
 
@@ -55,6 +58,26 @@ pub fn routes(
         .and(with_db(db_pool.clone()))
         .and_then(user::api::get_profile);
 
+    let profile_projects = warp::path!("profile" / String / "projects")
+        .and(warp::get())
+        .and(warp::query())
+        .and(with_auth())
+        .and(with_db(db_pool.clone()))
+        .and_then(user::api::get_profile_projects);
+
+    let profile_posts = warp::path!("profile" / String / "posts")
+        .and(warp::get())
+        .and(warp::query())
+        .and(with_auth())
+        .and(with_db(db_pool.clone()))
+        .and_then(user::api::get_profile_posts);
+
+    let profile_likes = warp::path!("profile" / String / "likes")
+        .and(warp::get())
+        .and(with_auth())
+        .and(with_db(db_pool.clone()))
+        .and_then(user::api::get_liked_posts);
+
     let update_user_bio = warp::path!("update" / "user" / "bio")
         .and(warp::post())
         .and(with_auth())
@@ -71,22 +94,56 @@ pub fn routes(
         .and(with_db(db_pool.clone()))
         .and_then(user::api::update_displayname);
 
+    let update_user_privacy = warp::path!("update" / "user" / "privacy")
+        .and(warp::post())
+        .and(with_auth())
+        .and(warp::body::content_length_limit(1024 * 16))
+        .and(warp::body::json())
+        .and(with_db(db_pool.clone()))
+        .and_then(user::api::update_privacy);
+
     let upload_avatar = warp::path!("upload" / "avatar")
         .and(warp::post())
         .and(with_auth())
         .and(warp::multipart::form().max_length(8192 * 1024))
+        .and(with_db(db_pool.clone()))
+        .and(with_storage(storage.clone()))
         .and_then(upload::api::upload_profile_picture);
 
     let get_avatar = warp::path!("avatars" / String)
         .and(warp::get())
+        .and(warp::query::<file::AvatarQuery>())
+        .and(warp::header::headers_cloned())
         .and(with_db(db_pool.clone()))
+        .and(with_storage(storage.clone()))
         .and_then(file::get_profile);
 
+    let get_avatar_version = warp::path!("avatars" / String / String)
+        .and(warp::get())
+        .and(warp::query::<file::AvatarQuery>())
+        .and(warp::header::headers_cloned())
+        .and(with_db(db_pool.clone()))
+        .and(with_storage(storage.clone()))
+        .and_then(file::get_profile_version);
+
+    let get_project_media = warp::path!("media" / "projects" / Uuid / String)
+        .and(warp::get())
+        .and(warp::header::headers_cloned())
+        .and(with_auth())
+        .and(with_db(db_pool.clone()))
+        .and(with_storage(storage.clone()))
+        .and_then(file::get_project_media);
+
     // Module routes
     let users = user::routes::routes(&db_pool);
-    let projects = project::routes::routes(&db_pool);
+    let projects = project::routes::routes(&db_pool, &storage);
     let auth = auth::routes::routes(&db_pool, otp_codes.clone(), key.clone());
     let posts = posts::routes::routes(&db_pool);
+    let badges = badges::routes::routes(&db_pool);
+    let tags = tags::routes::routes(&db_pool);
+    let classes = classes::routes::routes(&db_pool);
+    let storage_routes = storage::routes::routes(&db_pool, &storage);
+    let uploads = upload::routes::routes(&db_pool, &storage);
     let gateway = gateway::routes::routes();
 
     let cors = warp::cors()
@@ -100,8 +157,21 @@ pub fn routes(
             "Access-Control-Request-Headers",
             "Content-Type",
             "Authorization",
+            "Tus-Resumable",
+            "Upload-Length",
+            "Upload-Offset",
+            "Upload-Metadata",
+            "Upload-Checksum",
+        ])
+        .expose_headers(vec![
+            "Location",
+            "Tus-Resumable",
+            "Tus-Version",
+            "Upload-Length",
+            "Upload-Offset",
+            "Upload-Expires",
         ])
-        .allow_methods(vec!["POST", "GET", "PATCH", "DELETE"]);
+        .allow_methods(vec!["POST", "GET", "HEAD", "PUT", "PATCH", "DELETE", "OPTIONS"]);
 
     // Combine all routes
     version1
@@ -109,14 +179,25 @@ pub fn routes(
             auth.or(projects)
                 .or(users)
                 .or(posts)
+                .or(badges)
+                .or(tags)
+                .or(classes)
+                .or(storage_routes)
+                .or(uploads)
                 .or(panel)
                 .or(get_avatar)
+                .or(get_avatar_version)
+                .or(get_project_media)
                 .or(upload_avatar)
                 .or(update_user_bio)
                 .or(update_user_displayname)
+                .or(update_user_privacy)
                 .or(status)
                 .or(ckziu_news)
-                .or(profile_get),
+                .or(profile_get)
+                .or(profile_projects)
+                .or(profile_posts)
+                .or(profile_likes),
         )
         .or(gateway)
         .or(status)
@@ -0,0 +1,67 @@
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::PgPool;
+use tracing::{info, warn};
+use uuid::Uuid;
+use warp::reply::json;
+use warp::{reject, Reply};
+
+use crate::error::Error;
+use crate::user::api::require_staff;
+use crate::WebResult;
+
+use super::gc::collect_garbage;
+use super::quota::{get_projects_usage, get_user_usage};
+use super::SharedStorage;
+
+// GET v1/storage/usage
+pub async fn get_usage(user_uid: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  let user_uid = user_uid.ok_or(Error::Unauthorized)?;
+
+  let usage = match tokio::try_join!(get_user_usage(&user_uid, &db_pool), get_projects_usage(&user_uid, &db_pool)) {
+    Ok(usage) => usage,
+    Err(err) => {
+      warn!("Failed to get storage usage of {user_uid}: {err}");
+      return Err(reject::custom(Error::ServerProblem));
+    }
+  };
+  let (user, projects) = usage;
+
+  Ok(json(&json!({
+    "used": user.used,
+    "quota": user.quota,
+    "projects": projects,
+  })))
+}
+
+#[derive(Deserialize)]
+pub struct GcQuery {
+  pub dry_run: Option<bool>,
+}
+
+// POST v1/storage/gc?dry_run=false
+pub async fn run_gc(
+  user_uid: Option<Uuid>,
+  query: GcQuery,
+  db_pool: PgPool,
+  storage: SharedStorage,
+) -> WebResult<impl Reply> {
+  let staff = require_staff(user_uid, &db_pool).await?;
+  // Nothing is deleted unless asked explicitly
+  let dry_run = query.dry_run.unwrap_or(true);
+
+  match collect_garbage(dry_run, &storage, &db_pool).await {
+    Ok(report) => {
+      info!(
+        "{} collected {} orphaned files (dry run: {dry_run})",
+        staff.name,
+        report.deleted.len()
+      );
+      Ok(json(&report))
+    }
+    Err(err) => {
+      warn!("Failed to collect orphaned files: {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
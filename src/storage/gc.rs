@@ -0,0 +1,149 @@
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+use sqlx::PgPool;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::upload::db::{count_expired_uploads, get_upload_ids};
+use crate::upload::resumable::expire_uploads;
+
+use super::quota::unregister_files;
+use super::SharedStorage;
+
+pub const GC_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+// Files of uploads in progress are not referenced yet
+const GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+type GcResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+#[derive(Serialize)]
+pub struct GcReport {
+  pub dry_run: bool,
+  pub scanned: usize,
+  // Files without any row pointing at them
+  pub deleted: Vec<String>,
+  pub freed_bytes: u64,
+  // Registered files missing from the storage
+  pub forgotten: Vec<String>,
+  // Resumable uploads nobody finished or attached in time
+  pub expired_uploads: usize,
+}
+
+#[derive(Default)]
+pub struct References {
+  // Keys of avatars, every size is stored under "avatars/{key}/"
+  pub avatars: HashSet<String>,
+  // Ids of resumable uploads, their chunks are stored under "resumable/{id}/"
+  pub uploads: HashSet<String>,
+  pub files: HashSet<String>,
+}
+
+impl References {
+  pub fn contains(&self, object_key: &str) -> bool {
+    if self.files.contains(object_key) {
+      return true;
+    }
+    if let Some((avatar, _)) = object_key.strip_prefix("avatars/").and_then(|rest| rest.split_once('/')) {
+      return self.avatars.contains(avatar);
+    }
+    match object_key.strip_prefix("resumable/").and_then(|rest| rest.split_once('/')) {
+      Some((upload, _)) => self.uploads.contains(upload),
+      None => false,
+    }
+  }
+}
+
+async fn load_references(pool: &PgPool) -> GcResult<References> {
+  let avatars: Vec<String> = sqlx::query_scalar(r"SELECT avatar FROM users WHERE avatar IS NOT NULL")
+    .fetch_all(pool)
+    .await?;
+  let files: Vec<String> = sqlx::query_scalar(
+    r"SELECT thumbnail FROM projects WHERE thumbnail IS NOT NULL
+UNION ALL
+SELECT object_key FROM project_images
+UNION ALL
+SELECT object_key FROM release_assets",
+  )
+  .fetch_all(pool)
+  .await?;
+
+  let uploads = get_upload_ids(pool).await?;
+
+  Ok(References {
+    avatars: avatars.into_iter().collect(),
+    uploads: uploads.iter().map(Uuid::to_string).collect(),
+    files: files.into_iter().collect(),
+  })
+}
+
+// Deletes files no row references. With dry run only reports what would be deleted.
+pub async fn collect_garbage(dry_run: bool, storage: &SharedStorage, pool: &PgPool) -> GcResult<GcReport> {
+  let expired_uploads = match dry_run {
+    true => count_expired_uploads(pool).await? as usize,
+    false => expire_uploads(storage, pool).await?,
+  };
+  let references = load_references(pool).await?;
+  let objects = storage.list("").await?;
+  let now = SystemTime::now();
+
+  let mut report = GcReport {
+    dry_run,
+    scanned: objects.len(),
+    deleted: Vec::new(),
+    freed_bytes: 0,
+    forgotten: Vec::new(),
+    expired_uploads,
+  };
+
+  for object in &objects {
+    let age = now.duration_since(object.last_modified).unwrap_or_default();
+    if references.contains(&object.key) || age < GRACE_PERIOD {
+      continue;
+    }
+    if !dry_run {
+      if let Err(err) = storage.delete(&object.key).await {
+        warn!("Failed to collect {}: {err}", object.key);
+        continue;
+      }
+    }
+    report.freed_bytes += object.size;
+    report.deleted.push(object.key.clone());
+  }
+
+  let stored: HashSet<&str> = objects.iter().map(|object| object.key.as_str()).collect();
+  // Files registered after the listing are not missing
+  let registered: Vec<String> =
+    sqlx::query_scalar(r"SELECT object_key FROM stored_files WHERE created_at < now() - make_interval(secs => $1)")
+      .bind(GRACE_PERIOD.as_secs() as f64)
+      .fetch_all(pool)
+      .await?;
+  report.forgotten = registered.into_iter().filter(|key| !stored.contains(key.as_str())).collect();
+
+  if !dry_run {
+    unregister_files(&report.deleted, pool).await?;
+    unregister_files(&report.forgotten, pool).await?;
+  }
+
+  Ok(report)
+}
+
+pub fn spawn_collector(storage: SharedStorage, pool: PgPool) {
+  tokio::spawn(async move {
+    let mut interval = tokio::time::interval(GC_INTERVAL);
+    loop {
+      interval.tick().await;
+      match collect_garbage(false, &storage, &pool).await {
+        Ok(report) => info!(
+          "Collected {} orphaned files ({} bytes) out of {} and {} expired uploads",
+          report.deleted.len(),
+          report.freed_bytes,
+          report.scanned,
+          report.expired_uploads
+        ),
+        Err(err) => warn!("Failed to collect orphaned files: {err}"),
+      }
+    }
+  });
+}
@@ -0,0 +1,177 @@
+use std::io::{ErrorKind, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt};
+use uuid::Uuid;
+
+use super::{ByteStream, ObjectInfo, Storage, StorageResult, StoredObject};
+
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+// Stores objects as files under the root directory.
+pub struct LocalStorage {
+  root: PathBuf,
+}
+
+impl LocalStorage {
+  pub fn new(root: impl Into<PathBuf>) -> Self {
+    LocalStorage { root: root.into() }
+  }
+
+  fn path(&self, key: &str) -> StorageResult<PathBuf> {
+    let relative = Path::new(key);
+    // Keys must never leave the root
+    if !relative.components().all(|component| matches!(component, Component::Normal(_))) {
+      return Err(format!("Invalid storage key '{key}'").into());
+    }
+    Ok(self.root.join(relative))
+  }
+}
+
+fn read_stream(reader: impl AsyncRead + Unpin + Send + 'static) -> ByteStream {
+  Box::pin(futures::stream::unfold(Some(reader), |reader| async move {
+    let mut reader = reader?;
+    let mut buffer = vec![0; READ_BUFFER_SIZE];
+    match reader.read(&mut buffer).await {
+      Ok(0) => None,
+      Ok(read) => {
+        buffer.truncate(read);
+        Some((Ok(Bytes::from(buffer)), Some(reader)))
+      }
+      // Ends the stream after the error
+      Err(err) => Some((Err(err.into()), None)),
+    }
+  }))
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+  async fn put(&self, key: &str, bytes: Vec<u8>, _content_type: &str) -> StorageResult<()> {
+    let path = self.path(key)?;
+    if let Some(parent) = path.parent() {
+      tokio::fs::create_dir_all(parent).await?;
+    }
+    // Readers never see a partially written file
+    let temp = path.with_extension(format!("tmp-{}", Uuid::new_v4()));
+    tokio::fs::write(&temp, bytes).await?;
+    if let Err(err) = tokio::fs::rename(&temp, &path).await {
+      let _ = tokio::fs::remove_file(&temp).await;
+      return Err(err.into());
+    }
+    Ok(())
+  }
+
+  async fn put_file(&self, key: &str, source: &Path, _content_type: &str) -> StorageResult<()> {
+    let path = self.path(key)?;
+    if let Some(parent) = path.parent() {
+      tokio::fs::create_dir_all(parent).await?;
+    }
+    let temp = path.with_extension(format!("tmp-{}", Uuid::new_v4()));
+    let result = async {
+      tokio::fs::copy(source, &temp).await?;
+      tokio::fs::rename(&temp, &path).await?;
+      Ok(())
+    }
+    .await;
+    if result.is_err() {
+      let _ = tokio::fs::remove_file(&temp).await;
+    }
+    result
+  }
+
+  async fn head(&self, key: &str) -> StorageResult<Option<StoredObject>> {
+    let metadata = match tokio::fs::metadata(self.path(key)?).await {
+      Ok(metadata) => metadata,
+      Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+      Err(err) => return Err(err.into()),
+    };
+    Ok(Some(StoredObject {
+      size: metadata.len(),
+      content_type: None,
+      last_modified: metadata.modified()?,
+    }))
+  }
+
+  async fn get_range(&self, key: &str, start: u64, end: u64) -> StorageResult<ByteStream> {
+    let mut file = tokio::fs::File::open(self.path(key)?).await?;
+    file.seek(SeekFrom::Start(start)).await?;
+    Ok(read_stream(file.take(end + 1 - start)))
+  }
+
+  async fn delete(&self, key: &str) -> StorageResult<()> {
+    let path = self.path(key)?;
+    match tokio::fs::remove_file(&path).await {
+      Ok(()) => {}
+      Err(err) if err.kind() == ErrorKind::NotFound => {}
+      Err(err) => return Err(err.into()),
+    }
+    // Clean up the directory once it is empty, fails otherwise
+    if let Some(parent) = path.parent().filter(|parent| *parent != self.root) {
+      let _ = tokio::fs::remove_dir(parent).await;
+    }
+    Ok(())
+  }
+
+  async fn list(&self, prefix: &str) -> StorageResult<Vec<ObjectInfo>> {
+    let mut objects = Vec::new();
+    let mut directories = vec![self.root.clone()];
+    while let Some(directory) = directories.pop() {
+      let mut entries = match tokio::fs::read_dir(&directory).await {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => continue,
+        Err(err) => return Err(err.into()),
+      };
+      while let Some(entry) = entries.next_entry().await? {
+        let metadata = entry.metadata().await?;
+        if metadata.is_dir() {
+          directories.push(entry.path());
+          continue;
+        }
+        let Ok(relative) = entry.path().strip_prefix(&self.root).map(Path::to_path_buf) else {
+          continue;
+        };
+        // Keys always use '/'
+        let key = relative.components().map(|component| component.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+        if key.starts_with(prefix) {
+          objects.push(ObjectInfo {
+            key,
+            size: metadata.len(),
+            last_modified: metadata.modified()?,
+          });
+        }
+      }
+    }
+    Ok(objects)
+  }
+
+  async fn presigned_url(&self, _key: &str, _expires_in: Duration) -> StorageResult<Option<String>> {
+    Ok(None)
+  }
+
+  // Streams the parts from disk
+  async fn concat(&self, key: &str, parts: &[String], _content_type: &str) -> StorageResult<()> {
+    let path = self.path(key)?;
+    if let Some(parent) = path.parent() {
+      tokio::fs::create_dir_all(parent).await?;
+    }
+    let temp = path.with_extension(format!("tmp-{}", Uuid::new_v4()));
+    let result = async {
+      let mut file = tokio::fs::File::create(&temp).await?;
+      for part in parts {
+        let mut source = tokio::fs::File::open(self.path(part)?).await?;
+        tokio::io::copy(&mut source, &mut file).await?;
+      }
+      file.sync_all().await?;
+      tokio::fs::rename(&temp, &path).await?;
+      Ok(())
+    }
+    .await;
+    if result.is_err() {
+      let _ = tokio::fs::remove_file(&temp).await;
+    }
+    result
+  }
+}
@@ -0,0 +1,80 @@
+use std::convert::Infallible;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::Stream;
+use warp::Filter;
+
+pub mod api;
+pub mod gc;
+pub mod local;
+pub mod quota;
+pub mod routes;
+pub mod s3;
+pub mod signature;
+
+pub type StorageError = Box<dyn std::error::Error + Send + Sync>;
+pub type StorageResult<T> = Result<T, StorageError>;
+pub type SharedStorage = Arc<dyn Storage>;
+pub type ByteStream = Pin<Box<dyn Stream<Item = StorageResult<Bytes>> + Send>>;
+
+pub struct StoredObject {
+  pub size: u64,
+  pub content_type: Option<String>,
+  pub last_modified: SystemTime,
+}
+
+pub struct ObjectInfo {
+  pub key: String,
+  pub size: u64,
+  pub last_modified: SystemTime,
+}
+
+// Keys are relative paths separated by '/', e.g. "avatars/{key}/256.png".
+#[async_trait]
+pub trait Storage: Send + Sync {
+  // Replaces the object if it exists
+  async fn put(&self, key: &str, bytes: Vec<u8>, content_type: &str) -> StorageResult<()>;
+
+  // Same as put, but reads the file in parts instead of loading it into memory
+  async fn put_file(&self, key: &str, path: &Path, content_type: &str) -> StorageResult<()>;
+
+  // Returns None if the object does not exist
+  async fn head(&self, key: &str) -> StorageResult<Option<StoredObject>>;
+
+  // Streams the bytes from start to end, both inclusive, of an existing object
+  async fn get_range(&self, key: &str, start: u64, end: u64) -> StorageResult<ByteStream>;
+
+  // Deleting a missing object is not an error
+  async fn delete(&self, key: &str) -> StorageResult<()>;
+
+  // Every object with the key starting with the prefix
+  async fn list(&self, prefix: &str) -> StorageResult<Vec<ObjectInfo>>;
+
+  // Returns None if the backend cannot serve objects by itself
+  async fn presigned_url(&self, key: &str, expires_in: Duration) -> StorageResult<Option<String>>;
+
+  // Writes the parts one after another into a new object without loading it into memory, the parts are kept
+  async fn concat(&self, key: &str, parts: &[String], content_type: &str) -> StorageResult<()>;
+}
+
+pub fn create_storage() -> SharedStorage {
+  match dotenv!("STORAGE") {
+    "s3" => Arc::new(s3::S3Storage::new(
+      dotenv!("S3_ENDPOINT"),
+      dotenv!("S3_REGION"),
+      dotenv!("S3_BUCKET"),
+      dotenv!("S3_ACCESS_KEY"),
+      dotenv!("S3_SECRET_KEY"),
+    )),
+    _ => Arc::new(local::LocalStorage::new(dotenv!("STORAGE_PATH"))),
+  }
+}
+
+pub fn with_storage(storage: SharedStorage) -> impl Filter<Extract = (SharedStorage,), Error = Infallible> + Clone {
+  warp::any().map(move || storage.clone())
+}
@@ -0,0 +1,171 @@
+use std::path::Path;
+
+use serde::Serialize;
+use sqlx::prelude::FromRow;
+use sqlx::{PgConnection, PgExecutor, PgPool};
+use tracing::warn;
+use uuid::Uuid;
+
+use super::{SharedStorage, StorageResult};
+
+type QuotaResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+#[derive(Serialize, FromRow, Debug, Clone, Copy)]
+pub struct Usage {
+  pub used: i64,
+  pub quota: i64,
+}
+
+impl Usage {
+  pub fn fits(&self, bytes: i64) -> bool {
+    self.used + bytes <= self.quota
+  }
+}
+
+#[derive(Serialize, FromRow)]
+pub struct ProjectUsage {
+  pub id: Uuid,
+  pub name: String,
+  pub used: i64,
+  pub quota: i64,
+}
+
+pub async fn get_user_usage(owner_id: &Uuid, executor: impl PgExecutor<'_>) -> QuotaResult<Usage> {
+  let query = r"SELECT
+  (SELECT coalesce(sum(size), 0) FROM stored_files WHERE owner_id = users.id)::bigint AS used,
+  storage_quota AS quota
+FROM users
+WHERE id = $1";
+
+  let result: Usage = sqlx::query_as(query).bind(owner_id).fetch_one(executor).await?;
+
+  Ok(result)
+}
+
+pub async fn get_project_usage(project_id: &Uuid, executor: impl PgExecutor<'_>) -> QuotaResult<Usage> {
+  let query = r"SELECT
+  (SELECT coalesce(sum(size), 0) FROM stored_files WHERE project_id = projects.id)::bigint AS used,
+  storage_quota AS quota
+FROM projects
+WHERE id = $1";
+
+  let result: Usage = sqlx::query_as(query).bind(project_id).fetch_one(executor).await?;
+
+  Ok(result)
+}
+
+pub async fn get_projects_usage(owner_id: &Uuid, pool: &PgPool) -> QuotaResult<Vec<ProjectUsage>> {
+  let query = r"SELECT
+  id,
+  name,
+  (SELECT coalesce(sum(size), 0) FROM stored_files WHERE project_id = projects.id)::bigint AS used,
+  storage_quota AS quota
+FROM projects
+WHERE owner_id = $1
+ORDER BY name";
+
+  let result: Vec<ProjectUsage> = sqlx::query_as(query).bind(owner_id).fetch_all(pool).await?;
+
+  Ok(result)
+}
+
+// Checks the owner quota and, for project files, the project quota as well
+pub async fn check_quota(owner_id: &Uuid, project_id: Option<&Uuid>, bytes: i64, pool: &PgPool) -> QuotaResult<bool> {
+  check_quota_in(owner_id, project_id, bytes, &mut *pool.acquire().await?).await
+}
+
+// Same as check_quota, as a part of a transaction
+pub async fn check_quota_in(
+  owner_id: &Uuid,
+  project_id: Option<&Uuid>,
+  bytes: i64,
+  connection: &mut PgConnection,
+) -> QuotaResult<bool> {
+  if !get_user_usage(owner_id, &mut *connection).await?.fits(bytes) {
+    return Ok(false);
+  }
+  match project_id {
+    Some(project_id) => Ok(get_project_usage(project_id, &mut *connection).await?.fits(bytes)),
+    None => Ok(true),
+  }
+}
+
+// Counts an object written without store_file
+pub async fn register_file(object_key: &str, owner_id: &Uuid, project_id: Option<&Uuid>, size: i64, pool: &PgPool) -> QuotaResult<()> {
+  let query = r"INSERT INTO stored_files (object_key, owner_id, project_id, size) VALUES ($1, $2, $3, $4)
+ON CONFLICT (object_key) DO UPDATE SET owner_id = $2, project_id = $3, size = $4";
+
+  sqlx::query(query)
+    .bind(object_key)
+    .bind(owner_id)
+    .bind(project_id)
+    .bind(size)
+    .execute(pool)
+    .await?;
+
+  Ok(())
+}
+
+pub async fn unregister_files(object_keys: &[String], pool: &PgPool) -> QuotaResult<()> {
+  sqlx::query(r"DELETE FROM stored_files WHERE object_key = ANY($1)")
+    .bind(object_keys)
+    .execute(pool)
+    .await?;
+
+  Ok(())
+}
+
+// Writes the object and counts it towards the quotas
+pub async fn store_file(
+  object_key: &str,
+  bytes: Vec<u8>,
+  content_type: &str,
+  owner_id: &Uuid,
+  project_id: Option<&Uuid>,
+  storage: &SharedStorage,
+  pool: &PgPool,
+) -> StorageResult<()> {
+  let size = bytes.len() as i64;
+  storage.put(object_key, bytes, content_type).await?;
+  register_file(object_key, owner_id, project_id, size, pool).await
+}
+
+// Same as store_file, but streams the file from the disk
+#[allow(clippy::too_many_arguments)]
+pub async fn store_file_from(
+  object_key: &str,
+  path: &Path,
+  size: i64,
+  content_type: &str,
+  owner_id: &Uuid,
+  project_id: Option<&Uuid>,
+  storage: &SharedStorage,
+  pool: &PgPool,
+) -> StorageResult<()> {
+  storage.put_file(object_key, path, content_type).await?;
+  register_file(object_key, owner_id, project_id, size, pool).await
+}
+
+// Removes the object and releases its space, failures are only logged
+pub async fn delete_file(object_key: &str, storage: &SharedStorage, pool: &PgPool) {
+  if let Err(err) = storage.delete(object_key).await {
+    warn!("Failed to remove {object_key}: {err}");
+    // Left registered, so the collector retries
+    return;
+  }
+  if let Err(err) = unregister_files(&[object_key.to_string()], pool).await {
+    warn!("Failed to unregister {object_key}: {err}");
+  }
+}
+
+// Moves the file to the project quota and the quota of its owner
+pub async fn assign_file(object_key: &str, project_id: &Uuid, owner_id: &Uuid, pool: &PgPool) -> QuotaResult<()> {
+  sqlx::query(r"UPDATE stored_files SET project_id = $2, owner_id = $3 WHERE object_key = $1")
+    .bind(object_key)
+    .bind(project_id)
+    .bind(owner_id)
+    .execute(pool)
+    .await?;
+
+  Ok(())
+}
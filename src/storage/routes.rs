@@ -0,0 +1,26 @@
+use sqlx::PgPool;
+use warp::Filter;
+
+use crate::{auth::header::with_auth, db::with_db};
+
+use super::{api, with_storage, SharedStorage};
+
+pub fn routes(db_pool: &PgPool, storage: &SharedStorage) -> impl Filter<Extract=impl warp::Reply, Error=warp::Rejection> + Clone {
+  let usage = warp::path!("storage" / "usage")
+    .and(warp::get())
+    .and(warp::path::end())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(api::get_usage);
+
+  let gc = warp::path!("storage" / "gc")
+    .and(warp::post())
+    .and(warp::path::end())
+    .and(with_auth())
+    .and(warp::query::<api::GcQuery>())
+    .and(with_db(db_pool.clone()))
+    .and(with_storage(storage.clone()))
+    .and_then(api::run_gc);
+
+  usage.or(gc)
+}
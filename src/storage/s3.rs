@@ -0,0 +1,407 @@
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::header::{CONTENT_LENGTH, CONTENT_TYPE, ETAG, LAST_MODIFIED};
+use reqwest::{Client, Method, StatusCode};
+use futures::StreamExt;
+use tokio::io::AsyncReadExt;
+
+use super::signature::{amz_date, scope, sha256_hex, sign, uri_encode, CanonicalRequest, ALGORITHM, UNSIGNED_PAYLOAD};
+use super::{ByteStream, ObjectInfo, Storage, StorageResult, StoredObject};
+
+// Presigned URLs cannot be valid for longer than a week
+const MAX_PRESIGN_EXPIRY: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+// S3 requires at least this size of all parts of a multipart upload but the last one
+const MIN_PART_SIZE: u64 = 5 * 1024 * 1024;
+// Larger files are uploaded in parts of this size
+const PART_SIZE: usize = 8 * 1024 * 1024;
+
+// Stores objects in a bucket of any S3 compatible server, e.g. AWS S3 or MinIO.
+// Uses path-style addressing ({endpoint}/{bucket}/{key}), which MinIO expects by default.
+pub struct S3Storage {
+  client: Client,
+  endpoint: String,
+  host: String,
+  region: String,
+  bucket: String,
+  access_key: String,
+  secret_key: String,
+}
+
+impl S3Storage {
+  pub fn new(endpoint: &str, region: &str, bucket: &str, access_key: &str, secret_key: &str) -> Self {
+    let endpoint = endpoint.trim_end_matches('/').to_string();
+    let url = reqwest::Url::parse(&endpoint).expect("Failed to parse S3_ENDPOINT.");
+    let host = match url.port() {
+      Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+      None => url.host_str().unwrap_or_default().to_string(),
+    };
+    S3Storage {
+      client: Client::new(),
+      endpoint,
+      host,
+      region: region.into(),
+      bucket: bucket.into(),
+      access_key: access_key.into(),
+      secret_key: secret_key.into(),
+    }
+  }
+
+  fn uri(&self, key: &str) -> String {
+    format!("/{}/{}", uri_encode(&self.bucket, false), uri_encode(key, true))
+  }
+
+  async fn send(&self, method: Method, key: &str, body: Option<(Vec<u8>, &str)>) -> StorageResult<reqwest::Response> {
+    self.send_to(method, &self.uri(key), &[], &[], body).await
+  }
+
+  // Extra headers are signed together with the default ones
+  async fn send_to(
+    &self,
+    method: Method,
+    uri: &str,
+    query: &[(&str, &str)],
+    extra_headers: &[(&str, &str)],
+    body: Option<(Vec<u8>, &str)>,
+  ) -> StorageResult<reqwest::Response> {
+    let now = Utc::now();
+    let date = amz_date(&now);
+    let mut query: Vec<String> = query
+      .iter()
+      .map(|(name, value)| format!("{}={}", uri_encode(name, false), uri_encode(value, false)))
+      .collect();
+    query.sort();
+    let query = query.join("&");
+    let payload_hash = sha256_hex(body.as_ref().map(|(bytes, _)| bytes.as_slice()).unwrap_or_default());
+
+    let mut headers = vec![
+      ("host", self.host.as_str()),
+      ("x-amz-content-sha256", payload_hash.as_str()),
+      ("x-amz-date", date.as_str()),
+    ];
+    headers.extend_from_slice(extra_headers);
+    headers.sort();
+    let request = CanonicalRequest {
+      method: method.as_str(),
+      uri,
+      query: &query,
+      headers: &headers,
+      payload_hash: &payload_hash,
+    };
+    let signature = sign(&request, &self.secret_key, &self.region, &now);
+    let authorization = format!(
+      "{} Credential={}/{}, SignedHeaders={}, Signature={}",
+      ALGORITHM,
+      self.access_key,
+      scope(&now, &self.region),
+      request.signed_headers(),
+      signature
+    );
+
+    let url = match query.is_empty() {
+      true => format!("{}{}", self.endpoint, uri),
+      false => format!("{}{}?{}", self.endpoint, uri, query),
+    };
+    let mut builder = self
+      .client
+      .request(method, url)
+      .header("x-amz-content-sha256", &payload_hash)
+      .header("x-amz-date", &date)
+      .header("authorization", authorization);
+    for (name, value) in extra_headers {
+      builder = builder.header(*name, *value);
+    }
+    if let Some((bytes, content_type)) = body {
+      builder = builder.header(CONTENT_TYPE, content_type).body(bytes);
+    }
+    Ok(builder.send().await?)
+  }
+
+  pub fn presign(&self, key: &str, expires_in: Duration, now: &DateTime<Utc>) -> String {
+    let uri = self.uri(key);
+    let credential = format!("{}/{}", self.access_key, scope(now, &self.region));
+    let expires = expires_in.min(MAX_PRESIGN_EXPIRY).as_secs().to_string();
+    // Already sorted by name
+    let query = format!(
+      "X-Amz-Algorithm={}&X-Amz-Credential={}&X-Amz-Date={}&X-Amz-Expires={}&X-Amz-SignedHeaders=host",
+      ALGORITHM,
+      uri_encode(&credential, false),
+      amz_date(now),
+      expires
+    );
+    let headers = [("host", self.host.as_str())];
+    let request = CanonicalRequest {
+      method: "GET",
+      uri: &uri,
+      query: &query,
+      headers: &headers,
+      payload_hash: UNSIGNED_PAYLOAD,
+    };
+    let signature = sign(&request, &self.secret_key, &self.region, now);
+    format!("{}{}?{}&X-Amz-Signature={}", self.endpoint, uri, query, signature)
+  }
+
+  async fn create_multipart_upload(&self, key: &str, content_type: &str) -> StorageResult<String> {
+    let query = [("uploads", "")];
+    let response = self.send_to(Method::POST, &self.uri(key), &query, &[], Some((Vec::new(), content_type))).await?;
+    check_status(&response, "multipart upload", key)?;
+    let xml = response.text().await?;
+    let upload_id = xml_elements(&xml, "UploadId").first().map(|id| xml_unescape(id));
+    upload_id.ok_or_else(|| format!("Missing UploadId for '{key}'").into())
+  }
+
+  // Returns the ETag of the part
+  async fn upload_part(&self, key: &str, upload_id: &str, number: usize, bytes: Vec<u8>) -> StorageResult<String> {
+    let number = number.to_string();
+    let query = [("partNumber", number.as_str()), ("uploadId", upload_id)];
+    let body = Some((bytes, "application/octet-stream"));
+    let response = self.send_to(Method::PUT, &self.uri(key), &query, &[], body).await?;
+    check_status(&response, "part upload", key)?;
+    let etag = response.headers().get(ETAG).and_then(|value| value.to_str().ok());
+    etag.map(String::from).ok_or_else(|| format!("Missing ETag of a part of '{key}'").into())
+  }
+
+  // Copies the whole object as a part within the bucket, returns the ETag of the part
+  async fn upload_part_copy(&self, key: &str, upload_id: &str, number: usize, source: &str) -> StorageResult<String> {
+    let number = number.to_string();
+    let query = [("partNumber", number.as_str()), ("uploadId", upload_id)];
+    let copy_source = self.uri(source);
+    let headers = [("x-amz-copy-source", copy_source.as_str())];
+    let response = self.send_to(Method::PUT, &self.uri(key), &query, &headers, None).await?;
+    check_status(&response, "part copy", key)?;
+    // A failed copy may answer 200 with an error instead of the ETag
+    let xml = response.text().await?;
+    let etag = xml_elements(&xml, "ETag").first().map(|etag| xml_unescape(etag));
+    etag.ok_or_else(|| format!("Missing ETag of a part of '{key}' copied from '{source}'").into())
+  }
+
+  async fn complete_multipart_upload(&self, key: &str, upload_id: &str, etags: &[String]) -> StorageResult<()> {
+    let parts: String = etags
+      .iter()
+      .enumerate()
+      .map(|(index, etag)| format!("<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>", index + 1, etag))
+      .collect();
+    let body = format!("<CompleteMultipartUpload>{parts}</CompleteMultipartUpload>").into_bytes();
+    let query = [("uploadId", upload_id)];
+    let response = self.send_to(Method::POST, &self.uri(key), &query, &[], Some((body, "application/xml"))).await?;
+    check_status(&response, "multipart upload", key)?;
+    // S3 may report a failure in the body of a successful response
+    if response.text().await?.contains("<Error>") {
+      return Err(format!("S3 multipart upload of '{key}' failed").into());
+    }
+    Ok(())
+  }
+
+  async fn abort_multipart_upload(&self, key: &str, upload_id: &str) -> StorageResult<()> {
+    let query = [("uploadId", upload_id)];
+    let response = self.send_to(Method::DELETE, &self.uri(key), &query, &[], None).await?;
+    check_status(&response, "multipart abort", key)
+  }
+}
+
+// Reads until the part is full or the file ends
+async fn read_part(file: &mut tokio::fs::File) -> std::io::Result<Vec<u8>> {
+  let mut part = Vec::with_capacity(PART_SIZE);
+  while part.len() < PART_SIZE {
+    let read = (&mut *file).take((PART_SIZE - part.len()) as u64).read_to_end(&mut part).await?;
+    if read == 0 {
+      break;
+    }
+  }
+  Ok(part)
+}
+
+// Responses of S3 are small and flat, so a full XML parser is not needed
+fn xml_elements<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+  let (open, close) = (format!("<{tag}>"), format!("</{tag}>"));
+  let mut elements = Vec::new();
+  let mut rest = xml;
+  while let Some(start) = rest.find(&open) {
+    let after = &rest[start + open.len()..];
+    let Some(end) = after.find(&close) else {
+      break;
+    };
+    elements.push(&after[..end]);
+    rest = &after[end + close.len()..];
+  }
+  elements
+}
+
+fn xml_unescape(text: &str) -> String {
+  text
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&quot;", "\"")
+    .replace("&apos;", "'")
+    .replace("&amp;", "&")
+}
+
+// Parses a page of ListObjectsV2, returns the objects and the continuation token
+pub fn parse_list_objects(xml: &str) -> StorageResult<(Vec<ObjectInfo>, Option<String>)> {
+  let mut objects = Vec::new();
+  for contents in xml_elements(xml, "Contents") {
+    let element = |tag| xml_elements(contents, tag).first().copied().ok_or(format!("Missing {tag} in S3 listing"));
+    let last_modified = DateTime::parse_from_rfc3339(element("LastModified")?)?;
+    objects.push(ObjectInfo {
+      key: xml_unescape(element("Key")?),
+      size: element("Size")?.parse()?,
+      last_modified: last_modified.to_utc().into(),
+    });
+  }
+  let truncated = xml_elements(xml, "IsTruncated").first() == Some(&"true");
+  let token = match truncated {
+    true => xml_elements(xml, "NextContinuationToken").first().map(|token| xml_unescape(token)),
+    false => None,
+  };
+  Ok((objects, token))
+}
+
+fn check_status(response: &reqwest::Response, action: &str, key: &str) -> StorageResult<()> {
+  if !response.status().is_success() {
+    return Err(format!("S3 {action} of '{key}' failed with {}", response.status()).into());
+  }
+  Ok(())
+}
+
+#[async_trait]
+impl Storage for S3Storage {
+  async fn put(&self, key: &str, bytes: Vec<u8>, content_type: &str) -> StorageResult<()> {
+    let response = self.send(Method::PUT, key, Some((bytes, content_type))).await?;
+    check_status(&response, "put", key)
+  }
+
+  async fn put_file(&self, key: &str, path: &Path, content_type: &str) -> StorageResult<()> {
+    let mut file = tokio::fs::File::open(path).await?;
+    if file.metadata().await?.len() <= PART_SIZE as u64 {
+      return self.put(key, read_part(&mut file).await?, content_type).await;
+    }
+
+    let upload_id = self.create_multipart_upload(key, content_type).await?;
+    let result = async {
+      let mut etags = Vec::new();
+      loop {
+        let part = read_part(&mut file).await?;
+        if part.is_empty() {
+          break;
+        }
+        etags.push(self.upload_part(key, &upload_id, etags.len() + 1, part).await?);
+      }
+      self.complete_multipart_upload(key, &upload_id, &etags).await
+    }
+    .await;
+    if result.is_err() {
+      let _ = self.abort_multipart_upload(key, &upload_id).await;
+    }
+    result
+  }
+
+  async fn head(&self, key: &str) -> StorageResult<Option<StoredObject>> {
+    let response = self.send(Method::HEAD, key, None).await?;
+    if response.status() == StatusCode::NOT_FOUND {
+      return Ok(None);
+    }
+    check_status(&response, "head", key)?;
+
+    let header = |name| response.headers().get(name).and_then(|value| value.to_str().ok());
+    let size = header(CONTENT_LENGTH)
+      .and_then(|value| value.parse().ok())
+      .ok_or_else(|| format!("Missing size of '{key}'"))?;
+    let content_type = header(CONTENT_TYPE).map(String::from);
+    let last_modified = header(LAST_MODIFIED)
+      .and_then(|value| httpdate::parse_http_date(value).ok())
+      .unwrap_or_else(SystemTime::now);
+
+    Ok(Some(StoredObject {
+      size,
+      content_type,
+      last_modified,
+    }))
+  }
+
+  async fn get_range(&self, key: &str, start: u64, end: u64) -> StorageResult<ByteStream> {
+    let range = format!("bytes={start}-{end}");
+    let response = self.send_to(Method::GET, &self.uri(key), &[], &[("range", &range)], None).await?;
+    check_status(&response, "get", key)?;
+
+    Ok(Box::pin(futures::stream::unfold(Some(response), |response| async move {
+      let mut response = response?;
+      match response.chunk().await {
+        Ok(Some(chunk)) => Some((Ok(chunk), Some(response))),
+        Ok(None) => None,
+        // Ends the stream after the error
+        Err(err) => Some((Err(err.into()), None)),
+      }
+    })))
+  }
+
+  async fn delete(&self, key: &str) -> StorageResult<()> {
+    // S3 answers 204 for missing objects as well
+    let response = self.send(Method::DELETE, key, None).await?;
+    check_status(&response, "delete", key)
+  }
+
+  async fn list(&self, prefix: &str) -> StorageResult<Vec<ObjectInfo>> {
+    let uri = format!("/{}", uri_encode(&self.bucket, false));
+    let mut objects = Vec::new();
+    let mut token: Option<String> = None;
+    loop {
+      let mut query = vec![("list-type", "2"), ("prefix", prefix)];
+      if let Some(token) = &token {
+        query.push(("continuation-token", token));
+      }
+      let response = self.send_to(Method::GET, &uri, &query, &[], None).await?;
+      check_status(&response, "list", prefix)?;
+
+      let (page, next) = parse_list_objects(&response.text().await?)?;
+      objects.extend(page);
+      match next {
+        Some(next) => token = Some(next),
+        None => return Ok(objects),
+      }
+    }
+  }
+
+  async fn presigned_url(&self, key: &str, expires_in: Duration) -> StorageResult<Option<String>> {
+    Ok(Some(self.presign(key, expires_in, &Utc::now())))
+  }
+
+  // Joins the parts with a multipart upload. Parts large enough are copied within the bucket,
+  // the smaller ones are streamed through a buffer and sent again once it fills up.
+  async fn concat(&self, key: &str, parts: &[String], content_type: &str) -> StorageResult<()> {
+    let upload_id = self.create_multipart_upload(key, content_type).await?;
+    let result = async {
+      let mut etags = Vec::new();
+      let mut buffer = Vec::new();
+      for part in parts {
+        let size = self.head(part).await?.ok_or_else(|| format!("Missing part '{part}'"))?.size;
+        if size == 0 {
+          continue;
+        }
+        // Copied parts must follow whole parts only
+        if buffer.is_empty() && size >= MIN_PART_SIZE {
+          etags.push(self.upload_part_copy(key, &upload_id, etags.len() + 1, part).await?);
+          continue;
+        }
+        let mut stream = self.get_range(part, 0, size - 1).await?;
+        while let Some(bytes) = stream.next().await {
+          buffer.extend_from_slice(&bytes?);
+          if buffer.len() >= PART_SIZE {
+            etags.push(self.upload_part(key, &upload_id, etags.len() + 1, std::mem::take(&mut buffer)).await?);
+          }
+        }
+      }
+      // The last part may be of any size, an upload needs at least one
+      if !buffer.is_empty() || etags.is_empty() {
+        etags.push(self.upload_part(key, &upload_id, etags.len() + 1, buffer).await?);
+      }
+      self.complete_multipart_upload(key, &upload_id, &etags).await
+    }
+    .await;
+    if result.is_err() {
+      let _ = self.abort_multipart_upload(key, &upload_id).await;
+    }
+    result
+  }
+}
@@ -0,0 +1,90 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+// AWS Signature Version 4, used by S3 and every S3-compatible server.
+
+pub const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+pub const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+pub struct CanonicalRequest<'a> {
+  pub method: &'a str,
+  // Already encoded path
+  pub uri: &'a str,
+  // Already encoded and sorted query
+  pub query: &'a str,
+  // Lowercase names, sorted by name
+  pub headers: &'a [(&'a str, &'a str)],
+  pub payload_hash: &'a str,
+}
+
+impl CanonicalRequest<'_> {
+  pub fn signed_headers(&self) -> String {
+    self.headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(";")
+  }
+
+  fn to_canonical_string(&self) -> String {
+    let headers: String = self
+      .headers
+      .iter()
+      .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+      .collect();
+    format!(
+      "{}\n{}\n{}\n{}\n{}\n{}",
+      self.method,
+      self.uri,
+      self.query,
+      headers,
+      self.signed_headers(),
+      self.payload_hash
+    )
+  }
+}
+
+pub fn amz_date(time: &DateTime<Utc>) -> String {
+  time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+pub fn scope(time: &DateTime<Utc>, region: &str) -> String {
+  format!("{}/{}/s3/aws4_request", time.format("%Y%m%d"), region)
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+  hex::encode(Sha256::digest(bytes))
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+  let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+  mac.update(data.as_bytes());
+  mac.finalize().into_bytes().to_vec()
+}
+
+pub fn sign(request: &CanonicalRequest, secret_key: &str, region: &str, time: &DateTime<Utc>) -> String {
+  let scope = scope(time, region);
+  let string_to_sign = format!(
+    "{}\n{}\n{}\n{}",
+    ALGORITHM,
+    amz_date(time),
+    scope,
+    sha256_hex(request.to_canonical_string().as_bytes())
+  );
+
+  let date_key = hmac(format!("AWS4{secret_key}").as_bytes(), &time.format("%Y%m%d").to_string());
+  let region_key = hmac(&date_key, region);
+  let service_key = hmac(&region_key, "s3");
+  let signing_key = hmac(&service_key, "aws4_request");
+  hex::encode(hmac(&signing_key, &string_to_sign))
+}
+
+// Percent-encodes everything except unreserved characters, optionally keeping '/'.
+pub fn uri_encode(value: &str, keep_slash: bool) -> String {
+  let mut encoded = String::with_capacity(value.len());
+  for byte in value.bytes() {
+    match byte {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+      b'/' if keep_slash => encoded.push('/'),
+      _ => encoded.push_str(&format!("%{byte:02X}")),
+    }
+  }
+  encoded
+}
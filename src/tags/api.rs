@@ -0,0 +1,178 @@
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::PgPool;
+use tracing::{info, warn};
+use uuid::Uuid;
+use warp::http::StatusCode;
+use warp::{reject, reply, Reply};
+
+use crate::error::Error;
+use crate::prelude::{web_json, WebResult};
+use crate::user::api::require_staff;
+
+use super::db;
+use super::models::{normalize_tag, validate_alias, validate_slug, validate_tag_name, TagCategory, PROJECT_TAGS_LIMIT};
+
+#[derive(Deserialize)]
+pub struct TagsQuery {
+  pub category: Option<TagCategory>,
+}
+
+#[derive(Deserialize)]
+pub struct PostTag {
+  pub slug: String,
+  pub name: String,
+  pub category: TagCategory,
+}
+
+#[derive(Deserialize)]
+pub struct AliasBody {
+  pub alias: String,
+}
+
+#[derive(Deserialize)]
+pub struct AliasQuery {
+  pub alias: String,
+}
+
+#[derive(Deserialize)]
+pub struct MergeBody {
+  pub into: String,
+}
+
+fn response(success: bool, message: &str, status: StatusCode) -> reply::WithStatus<reply::Json> {
+  reply::with_status(reply::json(&json!({ "success": success, "message": message })), status)
+}
+
+// Resolves tags and aliases of a project to slugs, the error is a message for the user
+pub async fn resolve_project_tags(tags: Vec<String>, db_pool: &PgPool) -> WebResult<Result<Vec<String>, String>> {
+  if tags.len() > PROJECT_TAGS_LIMIT {
+    return Ok(Err(format!("Projekt może mieć maksymalnie {PROJECT_TAGS_LIMIT} tagów.")));
+  }
+  let tags: Vec<String> = tags.iter().map(|tag| normalize_tag(tag)).collect();
+  let slugs = match db::resolve_tags(&tags, db_pool).await {
+    Ok(slugs) => slugs,
+    Err(err) => {
+      warn!("Failed to resolve tags {tags:?}: {err}");
+      return Err(reject::custom(Error::ServerProblem));
+    }
+  };
+
+  let mut resolved: Vec<String> = Vec::with_capacity(slugs.len());
+  for (tag, slug) in tags.into_iter().zip(slugs) {
+    match slug {
+      Some(slug) if resolved.contains(&slug) => {}
+      Some(slug) => resolved.push(slug),
+      None => return Ok(Err(format!("Nieznany tag: {tag}"))),
+    }
+  }
+  Ok(Ok(resolved))
+}
+
+async fn require_tag(slug: &str, db_pool: &PgPool) -> WebResult<String> {
+  let slug = normalize_tag(slug);
+  match db::has_tag(&slug, db_pool).await {
+    Ok(true) => Ok(slug),
+    Ok(false) => Err(reject::custom(Error::NotFound)),
+    Err(err) => {
+      warn!("Failed to check tag '{slug}': {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// GET v1/tags?category=CATEGORY
+pub async fn list_tags(query: TagsQuery, db_pool: PgPool) -> WebResult<impl Reply> {
+  match db::get_tags(query.category, &db_pool).await {
+    Ok(tags) => web_json(&tags),
+    Err(err) => {
+      warn!("Failed to get tags: {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// POST v1/tags
+pub async fn create_tag(auth: Option<Uuid>, body: PostTag, db_pool: PgPool) -> WebResult<impl Reply> {
+  let staff = require_staff(auth, &db_pool).await?;
+
+  let (slug, name) = match (validate_slug(&body.slug), validate_tag_name(&body.name)) {
+    (Ok(slug), Ok(name)) => (slug, name),
+    (Err(message), _) | (_, Err(message)) => return Ok(response(false, &message, StatusCode::BAD_REQUEST)),
+  };
+
+  match db::create_tag(&slug, &name, body.category, &db_pool).await {
+    Ok(true) => {
+      info!("Tag '{slug}' created by {}", &staff.name);
+      Ok(response(true, "Utworzono tag", StatusCode::CREATED))
+    }
+    Ok(false) => Ok(response(false, "Tag lub alias o tej nazwie już istnieje", StatusCode::CONFLICT)),
+    Err(err) => {
+      warn!("Failed to create tag '{slug}': {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// POST v1/tags/TAG/aliases
+pub async fn add_alias(slug: String, auth: Option<Uuid>, body: AliasBody, db_pool: PgPool) -> WebResult<impl Reply> {
+  let staff = require_staff(auth, &db_pool).await?;
+  let slug = require_tag(&slug, &db_pool).await?;
+
+  let alias = match validate_alias(&body.alias) {
+    Ok(alias) => alias,
+    Err(message) => return Ok(response(false, &message, StatusCode::BAD_REQUEST)),
+  };
+
+  match db::add_alias(&slug, &alias, &db_pool).await {
+    Ok(true) => {
+      info!("Alias '{alias}' of tag '{slug}' added by {}", &staff.name);
+      Ok(response(true, "Dodano alias", StatusCode::CREATED))
+    }
+    Ok(false) => Ok(response(false, "Tag lub alias o tej nazwie już istnieje", StatusCode::CONFLICT)),
+    Err(err) => {
+      warn!("Failed to add alias '{alias}' of tag '{slug}': {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// DELETE v1/tags/TAG/aliases?alias=ALIAS
+pub async fn delete_alias(slug: String, query: AliasQuery, auth: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  let staff = require_staff(auth, &db_pool).await?;
+  let slug = normalize_tag(&slug);
+  let alias = normalize_tag(&query.alias);
+
+  match db::delete_alias(&slug, &alias, &db_pool).await {
+    Ok(true) => {
+      info!("Alias '{alias}' of tag '{slug}' removed by {}", &staff.name);
+      Ok(response(true, "Usunięto alias", StatusCode::OK))
+    }
+    Ok(false) => Err(reject::custom(Error::NotFound)),
+    Err(err) => {
+      warn!("Failed to remove alias '{alias}' of tag '{slug}': {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+// POST v1/tags/TAG/merge
+pub async fn merge_tag(slug: String, auth: Option<Uuid>, body: MergeBody, db_pool: PgPool) -> WebResult<impl Reply> {
+  let staff = require_staff(auth, &db_pool).await?;
+  let from = require_tag(&slug, &db_pool).await?;
+  let into = require_tag(&body.into, &db_pool).await?;
+  if from == into {
+    return Ok(response(false, "Nie można scalić tagu z samym sobą", StatusCode::BAD_REQUEST));
+  }
+
+  match db::merge_tags(&from, &into, &db_pool).await {
+    Ok(()) => {
+      info!("Tag '{from}' merged into '{into}' by {}", &staff.name);
+      Ok(response(true, "Scalono tagi", StatusCode::OK))
+    }
+    Err(err) => {
+      warn!("Failed to merge tag '{from}' into '{into}': {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
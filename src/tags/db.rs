@@ -0,0 +1,111 @@
+use sqlx::PgPool;
+
+use super::models::{Tag, TagCategory};
+
+type DbResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+const GET_TAGS_QUERY: &str = r"SELECT tags.slug,
+  tags.name,
+  tags.category,
+  ARRAY(SELECT alias FROM tag_aliases WHERE tag = tags.slug ORDER BY alias) AS aliases,
+  (SELECT count(*)
+   FROM project_tags
+       INNER JOIN projects ON project_tags.project_id = projects.id
+   WHERE project_tags.tag = tags.slug AND projects.visibility = 'public') AS projects
+FROM tags
+WHERE ($1::tag_category IS NULL OR tags.category = $1)
+ORDER BY projects DESC, tags.slug";
+
+pub async fn get_tags(category: Option<TagCategory>, pool: &PgPool) -> DbResult<Vec<Tag>> {
+  let result: Vec<Tag> = sqlx::query_as(GET_TAGS_QUERY).bind(category).fetch_all(pool).await?;
+
+  Ok(result)
+}
+
+pub async fn has_tag(slug: &str, pool: &PgPool) -> DbResult<bool> {
+  let result: bool = sqlx::query_scalar(r"SELECT EXISTS(SELECT 1 FROM tags WHERE slug = $1)")
+    .bind(slug)
+    .fetch_one(pool)
+    .await?;
+
+  Ok(result)
+}
+
+// Slugs of the given tags or aliases in the same order, None for unknown ones
+pub async fn resolve_tags(tags: &[String], pool: &PgPool) -> DbResult<Vec<Option<String>>> {
+  let query = r"SELECT coalesce((SELECT slug FROM tags WHERE slug = input), (SELECT tag FROM tag_aliases WHERE alias = input))
+FROM unnest($1::varchar[]) WITH ORDINALITY AS inputs (input, position)
+ORDER BY position";
+
+  let result: Vec<Option<String>> = sqlx::query_scalar(query).bind(tags).fetch_all(pool).await?;
+
+  Ok(result)
+}
+
+// Returns false if the slug is already a tag or an alias
+pub async fn create_tag(slug: &str, name: &str, category: TagCategory, pool: &PgPool) -> DbResult<bool> {
+  let query = r"INSERT INTO tags (slug, name, category)
+SELECT $1, $2, $3 WHERE NOT EXISTS(SELECT 1 FROM tag_aliases WHERE alias = $1)
+ON CONFLICT DO NOTHING";
+
+  let result = sqlx::query(query).bind(slug).bind(name).bind(category).execute(pool).await?;
+
+  Ok(result.rows_affected() > 0)
+}
+
+// Returns false if the alias is already a tag or an alias
+pub async fn add_alias(tag: &str, alias: &str, pool: &PgPool) -> DbResult<bool> {
+  let query = r"INSERT INTO tag_aliases (alias, tag)
+SELECT $2, $1 WHERE NOT EXISTS(SELECT 1 FROM tags WHERE slug = $2)
+ON CONFLICT DO NOTHING";
+
+  let result = sqlx::query(query).bind(tag).bind(alias).execute(pool).await?;
+
+  Ok(result.rows_affected() > 0)
+}
+
+pub async fn delete_alias(tag: &str, alias: &str, pool: &PgPool) -> DbResult<bool> {
+  let result = sqlx::query(r"DELETE FROM tag_aliases WHERE alias = $2 AND tag = $1")
+    .bind(tag)
+    .bind(alias)
+    .execute(pool)
+    .await?;
+
+  Ok(result.rows_affected() > 0)
+}
+
+// Moves projects and aliases of the tag to another one and keeps its slug as an alias
+pub async fn merge_tags(from: &str, into: &str, pool: &PgPool) -> DbResult<()> {
+  let mut transaction = pool.begin().await?;
+
+  sqlx::query(
+    r"INSERT INTO project_tags (project_id, tag) SELECT project_id, $2 FROM project_tags WHERE tag = $1
+    ON CONFLICT DO NOTHING",
+  )
+  .bind(from)
+  .bind(into)
+  .execute(&mut *transaction)
+  .await?;
+  sqlx::query(r"DELETE FROM project_tags WHERE tag = $1")
+    .bind(from)
+    .execute(&mut *transaction)
+    .await?;
+  sqlx::query(r"UPDATE tag_aliases SET tag = $2 WHERE tag = $1")
+    .bind(from)
+    .bind(into)
+    .execute(&mut *transaction)
+    .await?;
+  sqlx::query(r"DELETE FROM tags WHERE slug = $1")
+    .bind(from)
+    .execute(&mut *transaction)
+    .await?;
+  sqlx::query(r"INSERT INTO tag_aliases (alias, tag) VALUES ($1, $2)")
+    .bind(from)
+    .bind(into)
+    .execute(&mut *transaction)
+    .await?;
+
+  transaction.commit().await?;
+
+  Ok(())
+}
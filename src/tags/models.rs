@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+pub const PROJECT_TAGS_LIMIT: usize = 8;
+const SLUG_MAX_LENGTH: usize = 32;
+const NAME_MAX_LENGTH: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "tag_category", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum TagCategory {
+  Language,
+  Framework,
+  Category,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct Tag {
+  pub slug: String,
+  pub name: String,
+  pub category: TagCategory,
+  pub aliases: Vec<String>,
+  // Number of public projects with the tag
+  pub projects: i64,
+}
+
+// Tags and aliases are matched lowercase, with dashes between words
+pub fn normalize_tag(tag: &str) -> String {
+  tag.split_whitespace().collect::<Vec<_>>().join("-").to_lowercase()
+}
+
+pub fn validate_slug(slug: &str) -> Result<String, String> {
+  let slug = normalize_tag(slug);
+  if slug.is_empty() || slug.len() > SLUG_MAX_LENGTH {
+    return Err(format!("Identyfikator tagu musi mieć od 1 do {SLUG_MAX_LENGTH} znaków."));
+  }
+  if !slug.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-') {
+    return Err("Identyfikator tagu może zawierać tylko małe litery, cyfry oraz znak -".into());
+  }
+  Ok(slug)
+}
+
+// Aliases may contain any characters, such as "c++" or "c#"
+pub fn validate_alias(alias: &str) -> Result<String, String> {
+  let alias = normalize_tag(alias);
+  if alias.is_empty() || alias.chars().count() > SLUG_MAX_LENGTH {
+    return Err(format!("Alias musi mieć od 1 do {SLUG_MAX_LENGTH} znaków."));
+  }
+  Ok(alias)
+}
+
+pub fn validate_tag_name(name: &str) -> Result<String, String> {
+  let name = name.trim();
+  if name.is_empty() || name.chars().count() > NAME_MAX_LENGTH {
+    return Err(format!("Nazwa tagu musi mieć od 1 do {NAME_MAX_LENGTH} znaków."));
+  }
+  Ok(name.to_string())
+}
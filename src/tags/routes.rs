@@ -0,0 +1,53 @@
+use sqlx::PgPool;
+use warp::Filter;
+
+use crate::{auth::header::with_auth, db::with_db};
+
+use super::api;
+
+pub fn routes(db_pool: &PgPool) -> impl Filter<Extract=impl warp::Reply, Error=warp::Rejection> + Clone {
+  let list = warp::path!("tags")
+    .and(warp::get())
+    .and(warp::query::<api::TagsQuery>())
+    .and(with_db(db_pool.clone()))
+    .and_then(api::list_tags);
+
+  let create = warp::path!("tags")
+    .and(warp::post())
+    .and(with_auth())
+    .and(warp::body::content_length_limit(1024 * 16))
+    .and(warp::body::json())
+    .and(with_db(db_pool.clone()))
+    .and_then(api::create_tag);
+
+  let add_alias = warp::path!("tags" / String / "aliases")
+    .and(warp::post())
+    .and(with_auth())
+    .and(warp::body::content_length_limit(1024 * 16))
+    .and(warp::body::json())
+    .and(with_db(db_pool.clone()))
+    .and_then(api::add_alias);
+
+  let delete_alias = warp::path!("tags" / String / "aliases")
+    .and(warp::delete())
+    .and(warp::query::<api::AliasQuery>())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(api::delete_alias);
+
+  let merge = warp::path!("tags" / String / "merge")
+    .and(warp::post())
+    .and(with_auth())
+    .and(warp::body::content_length_limit(1024 * 16))
+    .and(warp::body::json())
+    .and(with_db(db_pool.clone()))
+    .and_then(api::merge_tag);
+
+  list
+    .or(create)
+    .or(add_alias)
+    .or(delete_alias)
+    .or(merge)
+    // Keeps the combined filter future off the stack
+    .boxed()
+}
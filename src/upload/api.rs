@@ -1,37 +1,124 @@
-use futures::{StreamExt, TryStreamExt};
+use std::path::Path;
+
 use multipart::FormData;
+use serde_json::json;
+use sqlx::PgPool;
+use tracing::{info, warn};
 use uuid::Uuid;
-use warp::{multipart, reject, Reply};
-use warp::Buf;
-use warp::multipart::Part;
-
-use crate::{error, WebResult};
-
-async fn save_part_to_file(path: &str, part: Part) -> Result<(), tokio::io::Error> {
-  let data = part.stream().try_fold(Vec::new(), |mut acc, buf| async move {
-    acc.extend_from_slice(buf.chunk());
-    Ok(acc)
-  }).await.expect("Folding error");
-  tokio::fs::write(path, data).await
+use warp::http::StatusCode;
+use warp::{multipart, reject, reply, Reply};
+
+use crate::storage::quota::{check_quota, delete_file, store_file};
+use crate::storage::SharedStorage;
+use crate::{error, user, WebResult};
+
+use super::avatar::{avatar_object_key, process_avatar, AVATAR_MAX_SIZE, AVATAR_SIZES};
+use super::receive::{process_blocking, receive_image, temp_upload_path, UploadError};
+
+// Keys are content addressed, so variants are visible only once the user points at them.
+async fn store_variants(
+  key: &str,
+  mime: &str,
+  variants: Vec<(u32, Vec<u8>)>,
+  user_uid: &Uuid,
+  db_pool: &PgPool,
+  storage: &SharedStorage,
+) -> Result<(), UploadError> {
+  let bytes = variants.iter().map(|(_, bytes)| bytes.len() as i64).sum();
+  match check_quota(user_uid, None, bytes, db_pool).await {
+    Ok(true) => {}
+    Ok(false) => return Err(UploadError::QuotaExceeded),
+    Err(err) => {
+      warn!("Failed to check quota of {user_uid}: {err}");
+      return Err(UploadError::Server);
+    }
+  }
+
+  for (size, bytes) in variants {
+    store_file(&avatar_object_key(key, size, mime), bytes, mime, user_uid, None, storage, db_pool)
+      .await
+      .map_err(|err| {
+        warn!("Failed to store avatar {key}: {err}");
+        UploadError::Server
+      })?;
+  }
+  Ok(())
 }
 
-pub async fn upload_profile_picture(user_uid: Option<Uuid>, form: FormData) -> WebResult<impl Reply> {
-  // Reject unauthorized
-  if user_uid.is_none() {
-    return Err(reject::custom(error::Error::Unauthorized));
+async fn delete_variants(key: &str, mime: &str, db_pool: &PgPool, storage: &SharedStorage) {
+  for size in AVATAR_SIZES {
+    delete_file(&avatar_object_key(key, size, mime), storage, db_pool).await;
   }
-  // Collect stream
-  tokio::task::spawn(async move {
-    let mut parts = form.into_stream();
+}
+
+async fn upload_avatar(
+  form: FormData,
+  temp_path: &Path,
+  user_uid: &Uuid,
+  db_pool: &PgPool,
+  storage: &SharedStorage,
+) -> Result<(String, &'static str), UploadError> {
+  // The avatar belongs to the user, so the same image uploaded by someone else gets a separate key
+  let received = receive_image(form, temp_path, user_uid.as_bytes(), AVATAR_MAX_SIZE).await?;
+  let key = received.hash;
 
-    while let Ok(p) = parts.next().await.unwrap() {
-      let filepath = format!("uploads/{}", Uuid::new_v4());
+  let path = temp_path.to_path_buf();
+  let processed = process_blocking(move || process_avatar(&path, received.kind)).await?;
 
-      tokio::fs::create_dir_all("uploads").await.unwrap();
+  store_variants(&key, processed.mime, processed.variants, user_uid, db_pool, storage).await?;
 
-      save_part_to_file(&filepath, p).await.unwrap()
+  let previous = user::db::set_avatar(user_uid, &key, processed.mime, db_pool)
+    .await
+    .map_err(|err| {
+      warn!("Failed to set avatar of {user_uid}: {err}");
+      UploadError::Server
+    })?;
+
+  // Only now nothing points at the previous avatar
+  if let Some((previous, previous_mime)) = previous.filter(|(previous, _)| *previous != key) {
+    delete_variants(&previous, &previous_mime, db_pool, storage).await;
+  }
+
+  Ok((key, processed.mime))
+}
+
+// POST v1/upload/avatar
+pub async fn upload_profile_picture(
+  user_uid: Option<Uuid>,
+  form: FormData,
+  db_pool: PgPool,
+  storage: SharedStorage,
+) -> WebResult<impl Reply> {
+  // Reject unauthorized
+  let user_uid = user_uid.ok_or(error::Error::Unauthorized)?;
+  let name = match user::db::get_username(&user_uid, &db_pool).await {
+    Ok(name) => name.ok_or(error::Error::Unauthorized)?.0,
+    Err(err) => {
+      warn!("Failed to get username of {user_uid}: {err}");
+      return Err(reject::custom(error::Error::ServerProblem));
     }
-  });
+  };
+
+  let temp_path = temp_upload_path();
+  let result = upload_avatar(form, &temp_path, &user_uid, &db_pool, &storage).await;
+  // The original is never kept
+  let _ = tokio::fs::remove_file(&temp_path).await;
 
-  Ok("Uploaded!")
-}
\ No newline at end of file
+  match result {
+    Ok((key, mime)) => {
+      info!("Uploaded avatar {key} of {name}");
+      Ok(reply::with_status(
+        reply::json(&json!({
+          "success": true,
+          "message": "Pomyślnie zaktualizowano awatar.",
+          "avatar": key,
+          "mime": mime,
+          "url": format!("/v1/avatars/{name}/{key}"),
+          "sizes": AVATAR_SIZES,
+        })),
+        StatusCode::OK,
+      ))
+    }
+    Err(err) => Ok(err.reply()),
+  }
+}
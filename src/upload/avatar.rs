@@ -0,0 +1,47 @@
+use std::path::Path;
+
+use image::ImageResult;
+
+use super::image::{crop_to_ratio, decode_image, encode_image, extension_for_mime, output_mime, resize_exact, ImageKind};
+
+pub const AVATAR_MAX_SIZE: u64 = 5 * 1024 * 1024;
+// Largest first
+pub const AVATAR_SIZES: [u32; 4] = [512, 256, 128, 64];
+pub const AVATAR_DEFAULT_SIZE: u32 = 256;
+
+// Picks the smallest variant that is not smaller than requested
+pub fn pick_avatar_size(requested: Option<u32>) -> u32 {
+  match requested {
+    Some(requested) => AVATAR_SIZES
+      .iter()
+      .rev()
+      .copied()
+      .find(|size| *size >= requested)
+      .unwrap_or(AVATAR_SIZES[0]),
+    None => AVATAR_DEFAULT_SIZE,
+  }
+}
+
+// Storage key of the given avatar variant
+pub fn avatar_object_key(key: &str, size: u32, mime: &str) -> String {
+  format!("avatars/{}/{}.{}", key, size, extension_for_mime(mime))
+}
+
+pub struct ProcessedAvatar {
+  pub mime: &'static str,
+  // (size, encoded image) for every size of AVATAR_SIZES
+  pub variants: Vec<(u32, Vec<u8>)>,
+}
+
+// Decodes, crops to a square and resizes the avatar.
+pub fn process_avatar(path: &Path, kind: ImageKind) -> ImageResult<ProcessedAvatar> {
+  let square = crop_to_ratio(&decode_image(path, kind)?, 1, 1);
+  let mime = output_mime(&square);
+
+  let mut variants = Vec::with_capacity(AVATAR_SIZES.len());
+  for size in AVATAR_SIZES {
+    variants.push((size, encode_image(&resize_exact(&square, size, size), mime)?));
+  }
+
+  Ok(ProcessedAvatar { mime, variants })
+}
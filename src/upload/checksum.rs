@@ -0,0 +1,84 @@
+use sha2::compress256;
+use sha2::digest::generic_array::GenericArray;
+
+const BLOCK_SIZE: usize = 64;
+const STATE_SIZE: usize = 32;
+// Initial hash values of SHA-256
+const INITIAL_STATE: [u32; 8] = [
+  0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+// SHA-256 that can be saved between the chunks of an upload and continued later,
+// so the file never has to be read again to get its digest
+pub struct ResumableSha256 {
+  state: [u32; 8],
+  // Bytes not filling a whole block yet
+  pending: Vec<u8>,
+  length: u64,
+}
+
+impl ResumableSha256 {
+  // Continues from the saved state after length bytes, empty for nothing hashed yet
+  pub fn restore(saved: &[u8], length: u64) -> Option<Self> {
+    if saved.is_empty() {
+      return (length == 0).then(|| ResumableSha256 {
+        state: INITIAL_STATE,
+        pending: Vec::new(),
+        length,
+      });
+    }
+    let (state, pending) = saved.split_at_checked(STATE_SIZE)?;
+    if pending.len() as u64 != length % BLOCK_SIZE as u64 {
+      return None;
+    }
+    let mut words = [0; 8];
+    for (word, bytes) in words.iter_mut().zip(state.chunks_exact(4)) {
+      *word = u32::from_be_bytes(bytes.try_into().ok()?);
+    }
+    Some(ResumableSha256 {
+      state: words,
+      pending: pending.to_vec(),
+      length,
+    })
+  }
+
+  // The words of the state followed by the pending bytes
+  pub fn save(&self) -> Vec<u8> {
+    let mut saved: Vec<u8> = self.state.iter().flat_map(|word| word.to_be_bytes()).collect();
+    saved.extend_from_slice(&self.pending);
+    saved
+  }
+
+  pub fn update(&mut self, mut bytes: &[u8]) {
+    self.length += bytes.len() as u64;
+    if !self.pending.is_empty() {
+      let missing = (BLOCK_SIZE - self.pending.len()).min(bytes.len());
+      self.pending.extend_from_slice(&bytes[..missing]);
+      bytes = &bytes[missing..];
+      if self.pending.len() < BLOCK_SIZE {
+        return;
+      }
+      compress256(&mut self.state, &[*GenericArray::from_slice(&self.pending)]);
+      self.pending.clear();
+    }
+    let whole = bytes.len() - bytes.len() % BLOCK_SIZE;
+    let blocks: Vec<_> = bytes[..whole].chunks_exact(BLOCK_SIZE).map(|block| *GenericArray::from_slice(block)).collect();
+    compress256(&mut self.state, &blocks);
+    self.pending.extend_from_slice(&bytes[whole..]);
+  }
+
+  pub fn finalize(mut self) -> [u8; 32] {
+    let bits = self.length * 8;
+    // A single one bit, zeros up to the last 8 bytes of a block and the length in bits
+    let mut padding = vec![0x80];
+    padding.resize((BLOCK_SIZE * 2 - 8 - 1 - self.pending.len()) % BLOCK_SIZE + 1, 0);
+    padding.extend_from_slice(&bits.to_be_bytes());
+    self.update(&padding);
+
+    let mut digest = [0; 32];
+    for (bytes, word) in digest.chunks_exact_mut(4).zip(self.state) {
+      bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+  }
+}
@@ -0,0 +1,189 @@
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+use super::models::ResumableUpload;
+
+type DbResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+const UPLOAD_COLUMNS: &str =
+  r"id, owner_id, file_name, content_type, length, received, sha256_state, object_key, sha256, created_at, expires_at";
+
+pub async fn create_upload(upload: &ResumableUpload, pool: &PgPool) -> DbResult<()> {
+  sqlx::query(
+    r"INSERT INTO resumable_uploads (id, owner_id, file_name, content_type, length, expires_at)
+    VALUES ($1, $2, $3, $4, $5, $6)",
+  )
+  .bind(upload.id)
+  .bind(upload.owner_id)
+  .bind(&upload.file_name)
+  .bind(&upload.content_type)
+  .bind(upload.length)
+  .bind(upload.expires_at)
+  .execute(pool)
+  .await?;
+
+  Ok(())
+}
+
+// Expired uploads are treated as gone, before the collector removes them
+pub async fn get_upload(id: &Uuid, owner_id: &Uuid, pool: &PgPool) -> DbResult<Option<ResumableUpload>> {
+  let query = format!("SELECT {UPLOAD_COLUMNS} FROM resumable_uploads WHERE id = $1 AND owner_id = $2 AND expires_at > now()");
+  let result: Option<ResumableUpload> = sqlx::query_as(&query).bind(id).bind(owner_id).fetch_optional(pool).await?;
+
+  Ok(result)
+}
+
+// Returns false if the offset is no longer the end of the upload
+#[allow(clippy::too_many_arguments)]
+pub async fn add_chunk(
+  id: &Uuid,
+  offset: i64,
+  size: i64,
+  object_key: &str,
+  sha256_state: &[u8],
+  expires_at: &DateTime<Utc>,
+  pool: &PgPool,
+) -> DbResult<bool> {
+  let mut transaction = pool.begin().await?;
+
+  let result = sqlx::query(
+    r"UPDATE resumable_uploads SET received = received + $3, expires_at = $4, sha256_state = $5
+    WHERE id = $1 AND received = $2 AND received + $3 <= length AND object_key IS NULL",
+  )
+  .bind(id)
+  .bind(offset)
+  .bind(size)
+  .bind(expires_at)
+  .bind(sha256_state)
+  .execute(&mut *transaction)
+  .await?;
+  if result.rows_affected() == 0 {
+    return Ok(false);
+  }
+
+  sqlx::query(r#"INSERT INTO resumable_chunks (upload_id, "offset", size, object_key) VALUES ($1, $2, $3, $4)"#)
+    .bind(id)
+    .bind(offset)
+    .bind(size)
+    .bind(object_key)
+    .execute(&mut *transaction)
+    .await?;
+
+  transaction.commit().await?;
+
+  Ok(true)
+}
+
+// Storage keys of the chunks in order
+pub async fn get_chunks(id: &Uuid, pool: &PgPool) -> DbResult<Vec<String>> {
+  let result: Vec<String> = sqlx::query_scalar(r#"SELECT object_key FROM resumable_chunks WHERE upload_id = $1 ORDER BY "offset""#)
+    .bind(id)
+    .fetch_all(pool)
+    .await?;
+
+  Ok(result)
+}
+
+// Returns storage keys of the joined chunks or None if the upload was already committed
+pub async fn commit_upload(
+  id: &Uuid,
+  object_key: &str,
+  sha256: &str,
+  expires_at: &DateTime<Utc>,
+  pool: &PgPool,
+) -> DbResult<Option<Vec<String>>> {
+  let mut transaction = pool.begin().await?;
+
+  let result = sqlx::query(
+    r"UPDATE resumable_uploads SET object_key = $2, sha256 = $3, expires_at = $4
+    WHERE id = $1 AND object_key IS NULL AND received = length",
+  )
+  .bind(id)
+  .bind(object_key)
+  .bind(sha256)
+  .bind(expires_at)
+  .execute(&mut *transaction)
+  .await?;
+  if result.rows_affected() == 0 {
+    return Ok(None);
+  }
+
+  let chunks: Vec<String> = sqlx::query_scalar(r"DELETE FROM resumable_chunks WHERE upload_id = $1 RETURNING object_key")
+    .bind(id)
+    .fetch_all(&mut *transaction)
+    .await?;
+
+  transaction.commit().await?;
+
+  Ok(Some(chunks))
+}
+
+// Removes the committed upload, so its file can be used elsewhere
+pub async fn take_committed_upload(id: &Uuid, owner_id: &Uuid, pool: &PgPool) -> DbResult<Option<ResumableUpload>> {
+  let query = format!(
+    "DELETE FROM resumable_uploads WHERE id = $1 AND owner_id = $2 AND object_key IS NOT NULL AND expires_at > now()
+RETURNING {UPLOAD_COLUMNS}"
+  );
+  let result: Option<ResumableUpload> = sqlx::query_as(&query).bind(id).bind(owner_id).fetch_optional(pool).await?;
+
+  Ok(result)
+}
+
+// Removes chunks of the deleted uploads, returns storage keys of both
+async fn delete_chunks(deleted: Vec<(Uuid, Option<String>)>, connection: &mut PgConnection) -> DbResult<Vec<String>> {
+  let ids: Vec<Uuid> = deleted.iter().map(|(id, _)| *id).collect();
+  let chunks: Vec<String> = sqlx::query_scalar(r"DELETE FROM resumable_chunks WHERE upload_id = ANY($1) RETURNING object_key")
+    .bind(&ids)
+    .fetch_all(connection)
+    .await?;
+
+  Ok(deleted.into_iter().filter_map(|(_, object_key)| object_key).chain(chunks).collect())
+}
+
+// Returns storage keys of the upload
+pub async fn delete_upload(id: &Uuid, pool: &PgPool) -> DbResult<Vec<String>> {
+  let mut transaction = pool.begin().await?;
+
+  let deleted: Vec<(Uuid, Option<String>)> =
+    sqlx::query_as(r"DELETE FROM resumable_uploads WHERE id = $1 RETURNING id, object_key")
+      .bind(id)
+      .fetch_all(&mut *transaction)
+      .await?;
+  let object_keys = delete_chunks(deleted, &mut transaction).await?;
+
+  transaction.commit().await?;
+
+  Ok(object_keys)
+}
+
+// Returns the number of expired uploads and their storage keys
+pub async fn delete_expired_uploads(pool: &PgPool) -> DbResult<(usize, Vec<String>)> {
+  let mut transaction = pool.begin().await?;
+
+  let deleted: Vec<(Uuid, Option<String>)> =
+    sqlx::query_as(r"DELETE FROM resumable_uploads WHERE expires_at <= now() RETURNING id, object_key")
+      .fetch_all(&mut *transaction)
+      .await?;
+  let count = deleted.len();
+  let object_keys = delete_chunks(deleted, &mut transaction).await?;
+
+  transaction.commit().await?;
+
+  Ok((count, object_keys))
+}
+
+pub async fn count_expired_uploads(pool: &PgPool) -> DbResult<i64> {
+  let result: i64 = sqlx::query_scalar(r"SELECT count(*) FROM resumable_uploads WHERE expires_at <= now()")
+    .fetch_one(pool)
+    .await?;
+
+  Ok(result)
+}
+
+// Ids of uploads still in progress or waiting to be attached
+pub async fn get_upload_ids(pool: &PgPool) -> DbResult<Vec<Uuid>> {
+  let result: Vec<Uuid> = sqlx::query_scalar(r"SELECT id FROM resumable_uploads").fetch_all(pool).await?;
+
+  Ok(result)
+}
@@ -0,0 +1,111 @@
+use image::{Rgb, RgbImage};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use super::image::encode_image;
+
+// Generated for users without an uploaded avatar
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DefaultAvatarFormat {
+  // Identicon
+  #[default]
+  Png,
+  // Initials
+  Svg,
+}
+
+const BACKGROUND: [u8; 3] = [240, 240, 240];
+const GRID: u32 = 5;
+
+fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> [u8; 3] {
+  let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+  let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+  let (r, g, b) = match hue as u32 {
+    0..=59 => (chroma, x, 0.0),
+    60..=119 => (x, chroma, 0.0),
+    120..=179 => (0.0, chroma, x),
+    180..=239 => (0.0, x, chroma),
+    240..=299 => (x, 0.0, chroma),
+    _ => (chroma, 0.0, x),
+  };
+  let m = lightness - chroma / 2.0;
+  [r, g, b].map(|channel| ((channel + m) * 255.0).round() as u8)
+}
+
+// Every user keeps the same colour, whatever their name is
+pub fn avatar_colour(user_id: &Uuid) -> [u8; 3] {
+  let hash = Sha256::digest(user_id.as_bytes());
+  let hue = u16::from_be_bytes([hash[0], hash[1]]) % 360;
+  hsl_to_rgb(hue as f32, 0.55, 0.45)
+}
+
+// First letters of the first and the last word
+pub fn initials(display_name: &str, name: &str) -> String {
+  let words: Vec<&str> = display_name.split_whitespace().collect();
+  let letters: String = match words.as_slice() {
+    [] => name.chars().take(1).collect(),
+    [word] => word.chars().take(1).collect(),
+    [first, .., last] => first.chars().take(1).chain(last.chars().take(1)).collect(),
+  };
+  letters.to_uppercase()
+}
+
+// Symmetric 5x5 pattern, the left half and the middle column come from the hash
+fn identicon_cells(user_id: &Uuid) -> [[bool; GRID as usize]; GRID as usize] {
+  let hash = Sha256::digest(user_id.as_bytes());
+  let mut cells = [[false; GRID as usize]; GRID as usize];
+  for (row, cells) in cells.iter_mut().enumerate() {
+    for column in 0..3 {
+      let filled = hash[2 + row * 3 + column] % 2 == 0;
+      cells[column] = filled;
+      cells[GRID as usize - 1 - column] = filled;
+    }
+  }
+  cells
+}
+
+pub fn identicon_png(user_id: &Uuid, size: u32) -> image::ImageResult<Vec<u8>> {
+  let cells = identicon_cells(user_id);
+  // Half a cell of margin on every side
+  let cell = size / (GRID + 1);
+  let offset = (size - cell * GRID) / 2;
+  let colour = Rgb(avatar_colour(user_id));
+
+  let image = RgbImage::from_fn(size, size, |x, y| {
+    let (column, row) = (x.wrapping_sub(offset) / cell.max(1), y.wrapping_sub(offset) / cell.max(1));
+    match x >= offset && y >= offset && column < GRID && row < GRID && cells[row as usize][column as usize] {
+      true => colour,
+      false => Rgb(BACKGROUND),
+    }
+  });
+  encode_image(&image::DynamicImage::ImageRgb8(image), "image/png")
+}
+
+fn escape_xml(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+    .replace('\'', "&apos;")
+}
+
+pub fn initials_svg(user_id: &Uuid, display_name: &str, name: &str, size: u32) -> String {
+  let [r, g, b] = avatar_colour(user_id);
+  format!(
+    concat!(
+      r#"<svg xmlns="http://www.w3.org/2000/svg" width="{size}" height="{size}" viewBox="0 0 {size} {size}">"#,
+      r##"<rect width="100%" height="100%" fill="#{r:02x}{g:02x}{b:02x}"/>"##,
+      r##"<text x="50%" y="50%" dy=".1em" fill="#fff" font-family="sans-serif" font-size="{font}" "##,
+      r#"text-anchor="middle" dominant-baseline="middle">{initials}</text></svg>"#
+    ),
+    size = size,
+    r = r,
+    g = g,
+    b = b,
+    font = size * 2 / 5,
+    initials = escape_xml(&initials(display_name, name))
+  )
+}
@@ -0,0 +1,118 @@
+use std::io::Cursor;
+use std::path::Path;
+
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, ImageResult, Limits};
+
+const MAX_DIMENSION: u32 = 8192;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageKind {
+  Png,
+  Jpeg,
+  Gif,
+  WebP,
+}
+
+impl ImageKind {
+  // Detects the real type by magic bytes, the declared content type is not trusted.
+  pub fn detect(header: &[u8]) -> Option<ImageKind> {
+    if header.starts_with(b"\x89PNG\r\n\x1a\n") {
+      return Some(ImageKind::Png);
+    }
+    if header.starts_with(&[0xFF, 0xD8, 0xFF]) {
+      return Some(ImageKind::Jpeg);
+    }
+    if header.starts_with(b"GIF87a") || header.starts_with(b"GIF89a") {
+      return Some(ImageKind::Gif);
+    }
+    if header.len() >= 12 && header.starts_with(b"RIFF") && &header[8..12] == b"WEBP" {
+      return Some(ImageKind::WebP);
+    }
+    None
+  }
+
+  fn image_format(&self) -> ImageFormat {
+    match self {
+      ImageKind::Png => ImageFormat::Png,
+      ImageKind::Jpeg => ImageFormat::Jpeg,
+      ImageKind::Gif => ImageFormat::Gif,
+      ImageKind::WebP => ImageFormat::WebP,
+    }
+  }
+}
+
+pub fn extension_for_mime(mime: &str) -> &'static str {
+  match mime {
+    "image/jpeg" => "jpg",
+    _ => "png",
+  }
+}
+
+pub fn mime_for_extension(extension: &str) -> Option<&'static str> {
+  match extension {
+    "jpg" => Some("image/jpeg"),
+    "png" => Some("image/png"),
+    _ => None,
+  }
+}
+
+// Decodes the image and rotates it by its EXIF orientation.
+pub fn decode_image(path: &Path, kind: ImageKind) -> ImageResult<DynamicImage> {
+  let mut reader = ImageReader::open(path)?;
+  reader.set_format(kind.image_format());
+  let mut limits = Limits::default();
+  limits.max_image_width = Some(MAX_DIMENSION);
+  limits.max_image_height = Some(MAX_DIMENSION);
+  reader.limits(limits);
+
+  let mut decoder = reader.into_decoder()?;
+  let orientation = decoder.orientation()?;
+  let mut image = DynamicImage::from_decoder(decoder)?;
+  image.apply_orientation(orientation);
+  Ok(image)
+}
+
+// Cuts out the centered part with the given aspect ratio
+pub fn crop_to_ratio(image: &DynamicImage, ratio_width: u32, ratio_height: u32) -> DynamicImage {
+  let (width, height) = (image.width() as u64, image.height() as u64);
+  let (ratio_width, ratio_height) = (ratio_width as u64, ratio_height as u64);
+  let (crop_width, crop_height) = match width * ratio_height > height * ratio_width {
+    true => (height * ratio_width / ratio_height, height),
+    false => (width, width * ratio_height / ratio_width),
+  };
+  let x = (width - crop_width) / 2;
+  let y = (height - crop_height) / 2;
+  image.crop_imm(x as u32, y as u32, crop_width.max(1) as u32, crop_height.max(1) as u32)
+}
+
+pub fn resize_exact(image: &DynamicImage, width: u32, height: u32) -> DynamicImage {
+  image.resize_exact(width, height, FilterType::Lanczos3)
+}
+
+// Scales down to fit the bounds, smaller images are kept as they are
+pub fn fit_within(image: DynamicImage, max_width: u32, max_height: u32) -> DynamicImage {
+  if image.width() <= max_width && image.height() <= max_height {
+    return image;
+  }
+  image.resize(max_width, max_height, FilterType::Lanczos3)
+}
+
+// Transparent images are kept as PNG, everything else is smaller as JPEG
+pub fn output_mime(image: &DynamicImage) -> &'static str {
+  match image.color().has_alpha() {
+    true => "image/png",
+    false => "image/jpeg",
+  }
+}
+
+// Encodes from pixels only, so EXIF and other metadata are dropped.
+pub fn encode_image(image: &DynamicImage, mime: &str) -> ImageResult<Vec<u8>> {
+  let (image, format) = match mime {
+    "image/png" => (DynamicImage::ImageRgba8(image.to_rgba8()), ImageFormat::Png),
+    _ => (DynamicImage::ImageRgb8(image.to_rgb8()), ImageFormat::Jpeg),
+  };
+  let mut buffer = Vec::new();
+  image.write_to(&mut Cursor::new(&mut buffer), format)?;
+  Ok(buffer)
+}
@@ -1 +1,10 @@
 pub mod api;
+pub mod avatar;
+pub mod checksum;
+pub mod db;
+pub mod default_avatar;
+pub mod image;
+pub mod models;
+pub mod receive;
+pub mod resumable;
+pub mod routes;
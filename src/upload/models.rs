@@ -0,0 +1,27 @@
+use chrono::{DateTime, Utc};
+use chrono::serde::ts_milliseconds;
+use serde::Serialize;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(FromRow, Serialize, Clone)]
+pub struct ResumableUpload {
+  pub id: Uuid,
+  #[serde(skip)]
+  pub owner_id: Uuid,
+  pub file_name: String,
+  pub content_type: String,
+  pub length: i64,
+  pub received: i64,
+  #[serde(skip)]
+  pub sha256_state: Vec<u8>,
+  // Set once committed
+  #[serde(skip)]
+  pub object_key: Option<String>,
+  pub sha256: Option<String>,
+
+  #[serde(with = "ts_milliseconds")]
+  pub created_at: DateTime<Utc>,
+  #[serde(with = "ts_milliseconds")]
+  pub expires_at: DateTime<Utc>,
+}
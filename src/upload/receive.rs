@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use futures::TryStreamExt;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+use uuid::Uuid;
+use warp::http::StatusCode;
+use warp::multipart::{FormData, Part};
+use warp::{reply, Buf};
+
+use super::image::ImageKind;
+
+// Text fields sent along with the file, e.g. a caption
+const MAX_FIELD_SIZE: usize = 1024;
+
+pub enum UploadError {
+  MissingFile,
+  TooLarge(u64),
+  UnsupportedType,
+  InvalidImage,
+  InvalidField(String),
+  LimitReached(&'static str),
+  NameTaken(&'static str),
+  QuotaExceeded,
+  OffsetMismatch,
+  ChecksumMismatch,
+  Incomplete,
+  Server,
+}
+
+impl UploadError {
+  pub fn reply(&self) -> reply::WithStatus<reply::Json> {
+    let (status, code, message) = match self {
+      UploadError::MissingFile => (StatusCode::BAD_REQUEST, "missing_file", "Nie przesłano pliku.".to_string()),
+      UploadError::TooLarge(max_size) => (
+        StatusCode::PAYLOAD_TOO_LARGE,
+        "too_large",
+        format!("Plik nie może przekraczać {} MB.", max_size / 1024 / 1024),
+      ),
+      UploadError::UnsupportedType => (
+        StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        "unsupported_type",
+        "Dozwolone formaty to PNG, JPEG, GIF i WebP.".to_string(),
+      ),
+      UploadError::InvalidImage => (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "invalid_image",
+        "Nie udało się odczytać obrazu.".to_string(),
+      ),
+      UploadError::InvalidField(message) => (StatusCode::BAD_REQUEST, "invalid_field", message.clone()),
+      UploadError::LimitReached(message) => (StatusCode::CONFLICT, "limit_reached", message.to_string()),
+      UploadError::NameTaken(message) => (StatusCode::CONFLICT, "name_taken", message.to_string()),
+      UploadError::QuotaExceeded => (
+        StatusCode::PAYLOAD_TOO_LARGE,
+        "quota_exceeded",
+        "Przekroczono limit miejsca na pliki.".to_string(),
+      ),
+      UploadError::OffsetMismatch => (
+        StatusCode::CONFLICT,
+        "offset_mismatch",
+        "Przesunięcie nie odpowiada otrzymanej części pliku.".to_string(),
+      ),
+      // Defined by the checksum extension of tus
+      UploadError::ChecksumMismatch => (
+        StatusCode::from_u16(460).unwrap(),
+        "checksum_mismatch",
+        "Suma kontrolna nie zgadza się z przesłanymi danymi.".to_string(),
+      ),
+      UploadError::Incomplete => (
+        StatusCode::CONFLICT,
+        "incomplete",
+        "Plik nie został jeszcze w całości przesłany.".to_string(),
+      ),
+      UploadError::Server => (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "server_problem",
+        "Wystąpił problem serwera.".to_string(),
+      ),
+    };
+    reply::with_status(
+      reply::json(&json!({ "success": false, "code": code, "message": message })),
+      status,
+    )
+  }
+}
+
+impl From<std::io::Error> for UploadError {
+  fn from(err: std::io::Error) -> Self {
+    warn!("Upload failed on IO: {err}");
+    UploadError::Server
+  }
+}
+
+pub struct ReceivedImage {
+  pub kind: ImageKind,
+  // Hex of the first 16 bytes of sha256(salt, file)
+  pub hash: String,
+  pub fields: HashMap<String, String>,
+}
+
+pub struct ReceivedFile {
+  // As sent by the client, not validated
+  pub file_name: String,
+  pub content_type: Option<String>,
+  pub size: u64,
+  // Hex of sha256(file)
+  pub sha256: String,
+  pub fields: HashMap<String, String>,
+}
+
+// Uploads are processed locally before they are moved to the storage
+pub fn temp_upload_path() -> PathBuf {
+  std::env::temp_dir().join("codefest-uploads").join(Uuid::new_v4().to_string())
+}
+
+async fn read_field(part: Part) -> Result<String, UploadError> {
+  let mut bytes = Vec::new();
+  let mut stream = part.stream();
+  while let Some(chunk) = stream.try_next().await.map_err(|_| UploadError::MissingFile)? {
+    bytes.extend_from_slice(chunk.chunk());
+    if bytes.len() > MAX_FIELD_SIZE {
+      return Err(UploadError::TooLarge(MAX_FIELD_SIZE as u64));
+    }
+  }
+  String::from_utf8(bytes).map_err(|_| UploadError::MissingFile)
+}
+
+struct ReceivedPart {
+  file_name: String,
+  content_type: Option<String>,
+  // The first HEADER_SIZE bytes
+  header: Vec<u8>,
+  size: u64,
+  digest: Vec<u8>,
+}
+
+const HEADER_SIZE: usize = 12;
+
+// Streams the first file part to disk while checking its size, the rest of parts are text fields.
+// `accept` is asked about the file header as soon as it arrives.
+async fn receive_form(
+  form: FormData,
+  temp_path: &Path,
+  salt: &[u8],
+  max_size: u64,
+  accept: fn(&[u8]) -> bool,
+) -> Result<(ReceivedPart, HashMap<String, String>), UploadError> {
+  if let Some(parent) = temp_path.parent() {
+    tokio::fs::create_dir_all(parent).await?;
+  }
+
+  let mut parts = form.into_stream();
+  let mut fields = HashMap::new();
+  let mut received = None;
+
+  while let Some(part) = parts.try_next().await.map_err(|err| {
+    warn!("Failed to read multipart: {err}");
+    UploadError::MissingFile
+  })? {
+    let file_name = match part.filename() {
+      Some(file_name) => file_name.to_string(),
+      None => {
+        let name = part.name().to_string();
+        fields.insert(name, read_field(part).await?);
+        continue;
+      }
+    };
+    // Only the first file is taken
+    if received.is_some() {
+      continue;
+    }
+    let content_type = part.content_type().map(str::to_string);
+
+    let mut file = tokio::fs::File::create(temp_path).await?;
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+
+    let mut header = Vec::with_capacity(HEADER_SIZE);
+    let mut written: u64 = 0;
+
+    let mut stream = part.stream();
+    while let Some(chunk) = stream.try_next().await.map_err(|err| {
+      warn!("Failed to read upload stream: {err}");
+      UploadError::MissingFile
+    })? {
+      let chunk = chunk.chunk();
+      written += chunk.len() as u64;
+      if written > max_size {
+        return Err(UploadError::TooLarge(max_size));
+      }
+
+      if header.len() < HEADER_SIZE {
+        let missing = (HEADER_SIZE - header.len()).min(chunk.len());
+        header.extend_from_slice(&chunk[..missing]);
+        if header.len() == HEADER_SIZE && !accept(&header) {
+          return Err(UploadError::UnsupportedType);
+        }
+      }
+
+      hasher.update(chunk);
+      file.write_all(chunk).await?;
+    }
+    file.flush().await?;
+
+    if written == 0 {
+      return Err(UploadError::MissingFile);
+    }
+    // Files shorter than the header
+    if header.len() < HEADER_SIZE && !accept(&header) {
+      return Err(UploadError::UnsupportedType);
+    }
+    received = Some(ReceivedPart {
+      file_name,
+      content_type,
+      header,
+      size: written,
+      digest: hasher.finalize().to_vec(),
+    });
+  }
+
+  Ok((received.ok_or(UploadError::MissingFile)?, fields))
+}
+
+// Receives an image in PNG, JPEG, GIF or WebP, detected from its content.
+// The salt makes the hash differ between owners of the same image.
+pub async fn receive_image(form: FormData, temp_path: &Path, salt: &[u8], max_size: u64) -> Result<ReceivedImage, UploadError> {
+  let (received, fields) =
+    receive_form(form, temp_path, salt, max_size, |header| ImageKind::detect(header).is_some()).await?;
+  let kind = ImageKind::detect(&received.header).ok_or(UploadError::UnsupportedType)?;
+  Ok(ReceivedImage { kind, hash: hex::encode(&received.digest[..16]), fields })
+}
+
+// Receives a file of any type
+pub async fn receive_file(form: FormData, temp_path: &Path, max_size: u64) -> Result<ReceivedFile, UploadError> {
+  let (received, fields) = receive_form(form, temp_path, &[], max_size, |_| true).await?;
+  Ok(ReceivedFile {
+    file_name: received.file_name,
+    content_type: received.content_type,
+    size: received.size,
+    sha256: hex::encode(received.digest),
+    fields,
+  })
+}
+
+// Runs CPU heavy image processing outside of the async runtime
+pub async fn process_blocking<T, F>(process: F) -> Result<T, UploadError>
+where
+  F: FnOnce() -> image::ImageResult<T> + Send + 'static,
+  T: Send + 'static,
+{
+  tokio::task::spawn_blocking(process)
+    .await
+    .map_err(|err| {
+      warn!("Image processing task failed: {err}");
+      UploadError::Server
+    })?
+    .map_err(|err| {
+      warn!("Rejected image: {err}");
+      UploadError::InvalidImage
+    })
+}
@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+use base64::prelude::{Engine, BASE64_STANDARD};
+use bytes::Bytes;
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use tracing::{info, warn};
+use uuid::Uuid;
+use warp::http::header::{CACHE_CONTROL, CONTENT_TYPE, LOCATION};
+use warp::http::{HeaderMap, HeaderValue, Response, StatusCode};
+use warp::hyper::Body;
+use warp::reply::json;
+use warp::{reject, Reply};
+
+use crate::error::Error;
+use crate::project::releases::{asset_content_type, validate_asset_name};
+use crate::storage::quota::{check_quota, delete_file, register_file, store_file};
+use crate::storage::SharedStorage;
+use crate::WebResult;
+
+use super::checksum::ResumableSha256;
+use super::db;
+use super::models::ResumableUpload;
+use super::receive::UploadError;
+
+// Implements the core protocol of tus 1.0.0 with a few extensions, see https://tus.io/protocols/resumable-upload
+pub const TUS_VERSION: &str = "1.0.0";
+const TUS_EXTENSIONS: &str = "creation,expiration,checksum,termination";
+pub const RESUMABLE_MAX_SIZE: u64 = 500 * 1024 * 1024;
+pub const CHUNK_MAX_SIZE: u64 = 16 * 1024 * 1024;
+// Counted from the last received chunk
+const UPLOAD_EXPIRY: Duration = Duration::hours(24);
+const DEFAULT_FILE_NAME: &str = "file";
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+  headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+fn with_tus_headers(reply: impl Reply) -> Response<Body> {
+  let mut response = reply.into_response();
+  response.headers_mut().insert("Tus-Resumable", HeaderValue::from_static(TUS_VERSION));
+  response
+}
+
+fn expires_header(upload: &ResumableUpload) -> String {
+  httpdate::fmt_http_date(SystemTime::from(upload.expires_at))
+}
+
+// Clients of other versions are told which one is supported
+fn unsupported_version(headers: &HeaderMap) -> Option<Response<Body>> {
+  match header_str(headers, "Tus-Resumable") {
+    Some(version) if version != TUS_VERSION => Some(
+      Response::builder()
+        .status(StatusCode::PRECONDITION_FAILED)
+        .header("Tus-Version", TUS_VERSION)
+        .body(Body::empty())
+        .unwrap(),
+    ),
+    _ => None,
+  }
+}
+
+// Comma separated pairs of a key and a base64 encoded value, the value is optional
+pub fn parse_metadata(header: &str) -> Option<HashMap<String, String>> {
+  let mut metadata = HashMap::new();
+  for pair in header.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+    let (key, value) = match pair.split_once(' ') {
+      Some((key, value)) => (key, String::from_utf8(BASE64_STANDARD.decode(value.trim()).ok()?).ok()?),
+      None => (pair, String::new()),
+    };
+    metadata.insert(key.to_string(), value);
+  }
+  Some(metadata)
+}
+
+// "sha256 {base64 digest}", the only supported algorithm
+pub fn parse_checksum(header: &str) -> Result<Vec<u8>, UploadError> {
+  let invalid = || UploadError::InvalidField("Nagłówek Upload-Checksum musi mieć postać 'sha256 {base64}'.".into());
+  match header.trim().split_once(' ') {
+    Some((algorithm, digest)) if algorithm.eq_ignore_ascii_case("sha256") => {
+      let digest = BASE64_STANDARD.decode(digest.trim()).map_err(|_| invalid())?;
+      if digest.len() != 32 {
+        return Err(invalid());
+      }
+      Ok(digest)
+    }
+    _ => Err(invalid()),
+  }
+}
+
+async fn find_upload(id: &Uuid, user_uid: &Uuid, db_pool: &PgPool) -> WebResult<ResumableUpload> {
+  match db::get_upload(id, user_uid, db_pool).await {
+    Ok(Some(upload)) => Ok(upload),
+    // Uploads of other users are not revealed
+    Ok(None) => Err(reject::custom(Error::NotFound)),
+    Err(err) => {
+      warn!("Failed to get upload {id}: {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+async fn check_user_quota(user_uid: &Uuid, bytes: i64, db_pool: &PgPool) -> Result<(), UploadError> {
+  match check_quota(user_uid, None, bytes, db_pool).await {
+    Ok(true) => Ok(()),
+    Ok(false) => Err(UploadError::QuotaExceeded),
+    Err(err) => {
+      warn!("Failed to check quota of {user_uid}: {err}");
+      Err(UploadError::Server)
+    }
+  }
+}
+
+// OPTIONS v1/uploads
+pub async fn get_capabilities() -> WebResult<impl Reply> {
+  Ok(
+    Response::builder()
+      .status(StatusCode::NO_CONTENT)
+      .header("Tus-Resumable", TUS_VERSION)
+      .header("Tus-Version", TUS_VERSION)
+      .header("Tus-Extension", TUS_EXTENSIONS)
+      .header("Tus-Max-Size", RESUMABLE_MAX_SIZE)
+      .header("Tus-Checksum-Algorithm", "sha256")
+      .body(Body::empty())
+      .unwrap(),
+  )
+}
+
+async fn start_upload(headers: &HeaderMap, user_uid: &Uuid, db_pool: &PgPool) -> Result<ResumableUpload, UploadError> {
+  let length: u64 = header_str(headers, "Upload-Length")
+    .and_then(|length| length.parse().ok())
+    .ok_or_else(|| UploadError::InvalidField("Nagłówek Upload-Length jest wymagany.".into()))?;
+  if length == 0 {
+    return Err(UploadError::MissingFile);
+  }
+  if length > RESUMABLE_MAX_SIZE {
+    return Err(UploadError::TooLarge(RESUMABLE_MAX_SIZE));
+  }
+
+  let metadata = parse_metadata(header_str(headers, "Upload-Metadata").unwrap_or_default())
+    .ok_or_else(|| UploadError::InvalidField("Nieprawidłowy nagłówek Upload-Metadata.".into()))?;
+  let file_name = match metadata.get("filename") {
+    Some(file_name) => validate_asset_name(file_name).map_err(UploadError::InvalidField)?,
+    None => DEFAULT_FILE_NAME.into(),
+  };
+
+  // The whole file has to fit from the start
+  check_user_quota(user_uid, length as i64, db_pool).await?;
+
+  let now = Utc::now();
+  let upload = ResumableUpload {
+    id: Uuid::new_v4(),
+    owner_id: *user_uid,
+    file_name,
+    content_type: asset_content_type(metadata.get("filetype").map(String::as_str)),
+    length: length as i64,
+    received: 0,
+    sha256_state: Vec::new(),
+    object_key: None,
+    sha256: None,
+    created_at: now,
+    expires_at: now + UPLOAD_EXPIRY,
+  };
+  db::create_upload(&upload, db_pool).await.map_err(|err| {
+    warn!("Failed to create upload of {user_uid}: {err}");
+    UploadError::Server
+  })?;
+  Ok(upload)
+}
+
+// POST v1/uploads
+pub async fn create_upload(user_uid: Option<Uuid>, headers: HeaderMap, db_pool: PgPool) -> WebResult<impl Reply> {
+  let user_uid = user_uid.ok_or(Error::Unauthorized)?;
+  if let Some(response) = unsupported_version(&headers) {
+    return Ok(response);
+  }
+
+  match start_upload(&headers, &user_uid, &db_pool).await {
+    Ok(upload) => {
+      info!("Started upload {} of {} bytes by {user_uid}", upload.id, upload.length);
+      Ok(with_tus_headers(
+        Response::builder()
+          .status(StatusCode::CREATED)
+          .header(LOCATION, format!("/v1/uploads/{}", upload.id))
+          .header("Upload-Offset", 0)
+          .header("Upload-Expires", expires_header(&upload))
+          .body(Body::empty())
+          .unwrap(),
+      ))
+    }
+    Err(err) => Ok(with_tus_headers(err.reply())),
+  }
+}
+
+// HEAD v1/uploads/ID
+pub async fn get_upload_offset(id: Uuid, user_uid: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  let user_uid = user_uid.ok_or(Error::Unauthorized)?;
+  let upload = find_upload(&id, &user_uid, &db_pool).await?;
+
+  Ok(with_tus_headers(
+    Response::builder()
+      .status(StatusCode::OK)
+      .header("Upload-Offset", upload.received)
+      .header("Upload-Length", upload.length)
+      .header("Upload-Expires", expires_header(&upload))
+      .header(CACHE_CONTROL, "no-store")
+      .body(Body::empty())
+      .unwrap(),
+  ))
+}
+
+async fn receive_chunk(
+  upload: &ResumableUpload,
+  headers: &HeaderMap,
+  body: Bytes,
+  db_pool: &PgPool,
+  storage: &SharedStorage,
+) -> Result<ResumableUpload, UploadError> {
+  if header_str(headers, CONTENT_TYPE.as_str()) != Some("application/offset+octet-stream") {
+    return Err(UploadError::UnsupportedType);
+  }
+  let offset: i64 = header_str(headers, "Upload-Offset")
+    .and_then(|offset| offset.parse().ok())
+    .ok_or_else(|| UploadError::InvalidField("Nagłówek Upload-Offset jest wymagany.".into()))?;
+  if upload.object_key.is_some() || offset != upload.received {
+    return Err(UploadError::OffsetMismatch);
+  }
+  let size = body.len() as i64;
+  if offset + size > upload.length {
+    return Err(UploadError::InvalidField("Część przekracza zadeklarowany rozmiar pliku.".into()));
+  }
+  if let Some(checksum) = header_str(headers, "Upload-Checksum") {
+    if parse_checksum(checksum)? != Sha256::digest(&body).as_slice() {
+      return Err(UploadError::ChecksumMismatch);
+    }
+  }
+
+  let expires_at = Utc::now() + UPLOAD_EXPIRY;
+  let mut upload = ResumableUpload { expires_at, ..upload.clone() };
+  if size == 0 {
+    return Ok(upload);
+  }
+  check_user_quota(&upload.owner_id, size, db_pool).await?;
+
+  let mut hasher = ResumableSha256::restore(&upload.sha256_state, offset as u64).ok_or_else(|| {
+    warn!("Invalid digest state of upload {}", upload.id);
+    UploadError::Server
+  })?;
+  hasher.update(&body);
+  let sha256_state = hasher.save();
+
+  // Retried chunks never overwrite the accepted ones
+  let object_key = format!("resumable/{}/chunks/{}-{}", upload.id, offset, Uuid::new_v4());
+  store_file(&object_key, body.to_vec(), "application/octet-stream", &upload.owner_id, None, storage, db_pool)
+    .await
+    .map_err(|err| {
+      warn!("Failed to store chunk {object_key}: {err}");
+      UploadError::Server
+    })?;
+
+  match db::add_chunk(&upload.id, offset, size, &object_key, &sha256_state, &expires_at, db_pool).await {
+    Ok(true) => {
+      upload.received += size;
+      upload.sha256_state = sha256_state;
+      Ok(upload)
+    }
+    // Another request appended at the same offset first
+    Ok(false) => {
+      delete_file(&object_key, storage, db_pool).await;
+      Err(UploadError::OffsetMismatch)
+    }
+    Err(err) => {
+      warn!("Failed to add chunk to upload {}: {err}", upload.id);
+      delete_file(&object_key, storage, db_pool).await;
+      Err(UploadError::Server)
+    }
+  }
+}
+
+// PATCH v1/uploads/ID
+pub async fn patch_upload(
+  id: Uuid,
+  user_uid: Option<Uuid>,
+  headers: HeaderMap,
+  body: Bytes,
+  db_pool: PgPool,
+  storage: SharedStorage,
+) -> WebResult<impl Reply> {
+  let user_uid = user_uid.ok_or(Error::Unauthorized)?;
+  if let Some(response) = unsupported_version(&headers) {
+    return Ok(response);
+  }
+  let upload = find_upload(&id, &user_uid, &db_pool).await?;
+
+  match receive_chunk(&upload, &headers, body, &db_pool, &storage).await {
+    Ok(upload) => Ok(with_tus_headers(
+      Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .header("Upload-Offset", upload.received)
+        .header("Upload-Expires", expires_header(&upload))
+        .body(Body::empty())
+        .unwrap(),
+    )),
+    Err(err) => Ok(with_tus_headers(err.reply())),
+  }
+}
+
+// DELETE v1/uploads/ID
+pub async fn delete_upload(id: Uuid, user_uid: Option<Uuid>, db_pool: PgPool, storage: SharedStorage) -> WebResult<impl Reply> {
+  let user_uid = user_uid.ok_or(Error::Unauthorized)?;
+  find_upload(&id, &user_uid, &db_pool).await?;
+
+  match db::delete_upload(&id, &db_pool).await {
+    Ok(object_keys) => {
+      for object_key in object_keys {
+        delete_file(&object_key, &storage, &db_pool).await;
+      }
+      Ok(with_tus_headers(StatusCode::NO_CONTENT))
+    }
+    Err(err) => {
+      warn!("Failed to delete upload {id}: {err}");
+      Err(reject::custom(Error::ServerProblem))
+    }
+  }
+}
+
+#[derive(Deserialize)]
+pub struct CommitUpload {
+  // Hex of the whole file digest, verified when given
+  pub sha256: Option<String>,
+}
+
+// Joins the chunks into a single file
+async fn finish_upload(
+  upload: &ResumableUpload,
+  expected: Option<String>,
+  db_pool: &PgPool,
+  storage: &SharedStorage,
+) -> Result<(), UploadError> {
+  if upload.received != upload.length {
+    return Err(UploadError::Incomplete);
+  }
+  let server = |err: Box<dyn std::error::Error + Send + Sync>| {
+    warn!("Failed to commit upload {}: {err}", upload.id);
+    UploadError::Server
+  };
+
+  // Hashed as the chunks arrived, so they are not read again
+  let hasher = ResumableSha256::restore(&upload.sha256_state, upload.received as u64)
+    .ok_or_else(|| server(format!("Invalid digest state of upload {}", upload.id).into()))?;
+  let sha256 = hex::encode(hasher.finalize());
+  if expected.is_some_and(|expected| !expected.trim().eq_ignore_ascii_case(&sha256)) {
+    return Err(UploadError::ChecksumMismatch);
+  }
+
+  let chunks = db::get_chunks(&upload.id, db_pool).await.map_err(server)?;
+  let object_key = format!("resumable/{}/{}", upload.id, upload.file_name);
+  storage.concat(&object_key, &chunks, &upload.content_type).await.map_err(server)?;
+  register_file(&object_key, &upload.owner_id, None, upload.length, db_pool).await.map_err(server)?;
+
+  let expires_at = Utc::now() + UPLOAD_EXPIRY;
+  // None when a concurrent commit won, it joined the same chunks
+  if let Some(chunks) = db::commit_upload(&upload.id, &object_key, &sha256, &expires_at, db_pool).await.map_err(server)? {
+    for chunk in chunks {
+      delete_file(&chunk, storage, db_pool).await;
+    }
+  }
+  Ok(())
+}
+
+// POST v1/uploads/ID/commit
+pub async fn commit_upload(
+  id: Uuid,
+  user_uid: Option<Uuid>,
+  body: CommitUpload,
+  db_pool: PgPool,
+  storage: SharedStorage,
+) -> WebResult<impl Reply> {
+  let user_uid = user_uid.ok_or(Error::Unauthorized)?;
+  let upload = find_upload(&id, &user_uid, &db_pool).await?;
+
+  // Committing again only returns the upload
+  if upload.object_key.is_none() {
+    if let Err(err) = finish_upload(&upload, body.sha256, &db_pool, &storage).await {
+      return Ok(with_tus_headers(err.reply()));
+    }
+    info!("Committed upload {id} of {user_uid}");
+  }
+  let upload = find_upload(&id, &user_uid, &db_pool).await?;
+
+  Ok(with_tus_headers(json(&json!({
+    "success": true,
+    "message": "Przesłano plik.",
+    "upload": upload,
+  }))))
+}
+
+// Removes uploads nobody finished or attached in time, returns the number of them
+pub async fn expire_uploads(storage: &SharedStorage, db_pool: &PgPool) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+  let (count, object_keys) = db::delete_expired_uploads(db_pool).await?;
+  for object_key in object_keys {
+    delete_file(&object_key, storage, db_pool).await;
+  }
+  Ok(count)
+}
@@ -0,0 +1,62 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+use warp::Filter;
+
+use crate::storage::{with_storage, SharedStorage};
+use crate::{auth::header::with_auth, db::with_db};
+
+use super::resumable;
+
+pub fn routes(db_pool: &PgPool, storage: &SharedStorage) -> impl Filter<Extract=impl warp::Reply, Error=warp::Rejection> + Clone {
+  let capabilities = warp::path!("uploads")
+    .and(warp::options())
+    .and_then(resumable::get_capabilities);
+
+  let create = warp::path!("uploads")
+    .and(warp::post())
+    .and(with_auth())
+    .and(warp::header::headers_cloned())
+    .and(with_db(db_pool.clone()))
+    .and_then(resumable::create_upload);
+
+  let offset = warp::path!("uploads" / Uuid)
+    .and(warp::head())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(resumable::get_upload_offset);
+
+  let patch = warp::path!("uploads" / Uuid)
+    .and(warp::patch())
+    .and(with_auth())
+    .and(warp::header::headers_cloned())
+    .and(warp::body::content_length_limit(resumable::CHUNK_MAX_SIZE))
+    .and(warp::body::bytes())
+    .and(with_db(db_pool.clone()))
+    .and(with_storage(storage.clone()))
+    .and_then(resumable::patch_upload);
+
+  let delete = warp::path!("uploads" / Uuid)
+    .and(warp::delete())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and(with_storage(storage.clone()))
+    .and_then(resumable::delete_upload);
+
+  let commit = warp::path!("uploads" / Uuid / "commit")
+    .and(warp::post())
+    .and(with_auth())
+    .and(warp::body::content_length_limit(1024 * 16))
+    .and(warp::body::json())
+    .and(with_db(db_pool.clone()))
+    .and(with_storage(storage.clone()))
+    .and_then(resumable::commit_upload);
+
+  capabilities
+    .or(create)
+    .or(offset)
+    .or(patch)
+    .or(delete)
+    .or(commit)
+    // Keeps the combined filter future off the stack
+    .boxed()
+}
@@ -5,8 +5,13 @@ use warp::{reject, Reply};
 use warp::reply::json;
 
 use crate::{error, WebResult};
+use crate::badges::rules::{spawn_evaluate, BadgeEvent};
+use crate::pagination::{decode_time_cursor, CursorQuery};
+use crate::posts::api::PostWithOwner;
+use crate::prelude::web_json;
 use crate::user::db;
-use crate::user::responses::{UpdateBioBody, UpdateBioResponse, UpdateDisplayNameBody};
+use crate::user::models::{ProfileAccess, User};
+use crate::user::responses::{UpdateBioBody, UpdateBioResponse, UpdateDisplayNameBody, UpdatePrivacyBody};
 use crate::utils::current_millis;
 
 use super::responses::PaginationQuery;
@@ -70,6 +75,22 @@ pub async fn patch_user(username: String, _body: PatchUserBody, user_uid: Option
   Ok("PATCHED")
 }
 
+// Resolves the user behind the session and rejects everyone except staff
+pub async fn require_staff(user_uid: Option<Uuid>, db_pool: &PgPool) -> WebResult<User> {
+  let user_uid = user_uid.ok_or(error::Error::Unauthorized)?;
+  let user = match db::get_user_by_id(&user_uid, db_pool).await {
+    Ok(user) => user.ok_or(error::Error::NoPermission)?,
+    Err(err) => {
+      warn!("Failed to get user {user_uid}: {err}");
+      return Err(reject::custom(error::Error::ServerProblem));
+    }
+  };
+  if !user.is_staff() {
+    return Err(reject::custom(error::Error::NoPermission));
+  }
+  Ok(user)
+}
+
 pub async fn is_authorized(user_uid: &Option<Uuid>, username: &String, db_pool: &PgPool) -> bool {
   if user_uid.is_none() {
     return false;
@@ -90,12 +111,30 @@ pub async fn is_authorized(user_uid: &Option<Uuid>, username: &String, db_pool:
   false
 }
 
+// Resolves the profile and rejects viewers excluded by its privacy settings
+async fn profile_access(username: &String, viewer: Option<Uuid>, db_pool: &PgPool) -> WebResult<ProfileAccess> {
+  let access = match db::get_profile_access(username, viewer, db_pool).await {
+    Ok(access) => access,
+    Err(err) => {
+      warn!("Failed to check profile access: {err}");
+      return Err(reject::custom(error::Error::ServerProblem));
+    }
+  };
+  let access = access.ok_or(error::Error::UserNotFound)?;
+  if !access.can_view(viewer.is_some()) {
+    return Err(reject::custom(error::Error::ProfileRestricted));
+  }
+  Ok(access)
+}
+
+// GET v1/profile/USERNAME
 pub async fn get_profile(username: String, auth: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
   let username = username.to_lowercase().trim().to_string();
 
-  let is_authorized = is_authorized(&auth, &username, &db_pool).await;
+  let access = profile_access(&username, auth, &db_pool).await?;
+  let is_authorized = auth == Some(access.id);
 
-  match db::get_profile(auth, &username, is_authorized, &db_pool).await {
+  match db::get_profile(&access, auth, is_authorized, &db_pool).await {
     Ok(response) => {
       if let Some(profile) = response {
         return Ok(json(&profile));
@@ -109,6 +148,274 @@ pub async fn get_profile(username: String, auth: Option<Uuid>, db_pool: PgPool)
   Err(reject::custom(error::Error::UserNotFound))
 }
 
+// GET v1/profile/USERNAME/projects?cursor=CURSOR
+pub async fn get_profile_projects(username: String, query: CursorQuery, auth: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  let username = username.to_lowercase().trim().to_string();
+
+  let access = profile_access(&username, auth, &db_pool).await?;
+  let is_authorized = auth == Some(access.id);
+
+  let cursor = match &query.cursor {
+    Some(cursor) => Some(decode_time_cursor(cursor).ok_or(error::Error::ParameterProblem)?),
+    None => None,
+  };
+
+  match db::get_profile_projects(&access.id, auth, is_authorized, cursor, query.limit(), &db_pool).await {
+    Ok(page) => web_json(&page),
+    Err(err) => {
+      warn!("Failed to get projects page of '{username}': {err}");
+      Err(reject::custom(error::Error::ServerProblem))
+    }
+  }
+}
+
+// GET v1/profile/USERNAME/posts?cursor=CURSOR
+pub async fn get_profile_posts(username: String, query: CursorQuery, auth: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  let username = username.to_lowercase().trim().to_string();
+
+  let access = profile_access(&username, auth, &db_pool).await?;
+  if access.hide_posts && auth != Some(access.id) {
+    return Err(reject::custom(error::Error::ProfileRestricted));
+  }
+
+  let before_id = match &query.cursor {
+    Some(cursor) => Some(cursor.parse::<i32>().map_err(|_| error::Error::ParameterProblem)?),
+    None => None,
+  };
+
+  match db::get_profile_posts(&access.id, auth, before_id, query.limit(), &db_pool).await {
+    Ok(page) => web_json(&page),
+    Err(err) => {
+      warn!("Failed to get posts page of '{username}': {err}");
+      Err(reject::custom(error::Error::ServerProblem))
+    }
+  }
+}
+
+// GET v1/profile/USERNAME/likes
+pub async fn get_liked_posts(username: String, auth: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  let username = username.to_lowercase().trim().to_string();
+
+  let access = profile_access(&username, auth, &db_pool).await?;
+  if access.hide_likes && auth != Some(access.id) {
+    return Err(reject::custom(error::Error::ProfileRestricted));
+  }
+
+  match db::get_liked_posts(&access.id, auth, &db_pool).await {
+    Ok(posts) => {
+      let posts = posts
+        .into_iter()
+        .map(PostWithOwner::from)
+        .collect::<Vec<_>>();
+      web_json(&posts)
+    }
+    Err(err) => {
+      warn!("Failed to get liked posts of '{username}': {err}");
+      Err(reject::custom(error::Error::ServerProblem))
+    }
+  }
+}
+
+// GET v1/users/USERNAME/follow
+pub async fn follow_user(username: String, auth: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  set_follow(username, auth, db_pool, true).await
+}
+
+// GET v1/users/USERNAME/unfollow
+pub async fn unfollow_user(username: String, auth: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  set_follow(username, auth, db_pool, false).await
+}
+
+async fn set_follow(username: String, auth: Option<Uuid>, db_pool: PgPool, follow: bool) -> WebResult<impl Reply> {
+  let user_uid = auth.ok_or(error::Error::Unauthorized)?;
+  let username = username.to_lowercase().trim().to_string();
+
+  let followed = match db::get_user(&username, &db_pool).await {
+    Ok(followed) => followed.ok_or(error::Error::UserNotFound)?,
+    Err(err) => {
+      warn!("Failed to get user '{username}': {err}");
+      return Err(reject::custom(error::Error::ServerProblem));
+    }
+  };
+
+  if followed.id == user_uid {
+    return Ok(json(&UpdateBioResponse {
+      success: false,
+      message: "Nie możesz obserwować samego siebie.".into(),
+    }));
+  }
+
+  // Blocked users cannot follow the blocker
+  if follow {
+    match db::has_blocked(&followed.id, &user_uid, &db_pool).await {
+      Ok(false) => {}
+      Ok(true) => return Err(reject::custom(error::Error::ProfileRestricted)),
+      Err(err) => {
+        warn!("Failed to check block {} -> {user_uid}: {err}", &followed.id);
+        return Err(reject::custom(error::Error::ServerProblem));
+      }
+    }
+  }
+
+  let accepted = match db::get_privacy(&followed.id, &db_pool).await {
+    Ok(privacy) => !privacy.is_some_and(|privacy| privacy.profile_visibility.approves_followers()),
+    Err(err) => {
+      warn!("Failed to get privacy of {}: {err}", &followed.id);
+      return Err(reject::custom(error::Error::ServerProblem));
+    }
+  };
+
+  let changed = match db::set_follow(&user_uid, &followed.id, follow, accepted, &db_pool).await {
+    Ok(changed) => changed,
+    Err(err) => {
+      warn!("Failed to set follow {user_uid} -> {}: {err}", &followed.id);
+      return Err(reject::custom(error::Error::ServerProblem));
+    }
+  };
+
+  let message = match (follow, changed, accepted) {
+    (true, true, true) => "Zaobserwowano użytkownika.",
+    (true, true, false) => "Wysłano prośbę o obserwowanie.",
+    (true, false, _) => "Już obserwujesz tego użytkownika.",
+    (false, true, _) => "Przestano obserwować użytkownika.",
+    (false, false, _) => "Nie obserwujesz tego użytkownika.",
+  };
+
+  if follow && changed && accepted {
+    spawn_evaluate(followed.id, BadgeEvent::Followed, &db_pool);
+  }
+
+  info!("User {user_uid} follow status of {} changed to {follow}", &followed.id);
+  Ok(json(&UpdateBioResponse {
+    success: changed,
+    message: message.into(),
+  }))
+}
+
+// GET v1/users/USERNAME/block
+pub async fn block_user(username: String, auth: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  set_block(username, auth, db_pool, true).await
+}
+
+// GET v1/users/USERNAME/unblock
+pub async fn unblock_user(username: String, auth: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  set_block(username, auth, db_pool, false).await
+}
+
+async fn set_block(username: String, auth: Option<Uuid>, db_pool: PgPool, block: bool) -> WebResult<impl Reply> {
+  let user_uid = auth.ok_or(error::Error::Unauthorized)?;
+  let username = username.to_lowercase().trim().to_string();
+
+  let blocked = match db::get_user(&username, &db_pool).await {
+    Ok(blocked) => blocked.ok_or(error::Error::UserNotFound)?,
+    Err(err) => {
+      warn!("Failed to get user '{username}': {err}");
+      return Err(reject::custom(error::Error::ServerProblem));
+    }
+  };
+
+  if blocked.id == user_uid {
+    return Ok(json(&UpdateBioResponse {
+      success: false,
+      message: "Nie możesz zablokować samego siebie.".into(),
+    }));
+  }
+
+  let changed = match db::set_block(&user_uid, &blocked.id, block, &db_pool).await {
+    Ok(changed) => changed,
+    Err(err) => {
+      warn!("Failed to set block {user_uid} -> {}: {err}", &blocked.id);
+      return Err(reject::custom(error::Error::ServerProblem));
+    }
+  };
+
+  let message = match (block, changed) {
+    (true, true) => "Zablokowano użytkownika.",
+    (true, false) => "Użytkownik jest już zablokowany.",
+    (false, true) => "Odblokowano użytkownika.",
+    (false, false) => "Użytkownik nie jest zablokowany.",
+  };
+
+  info!("User {user_uid} block status of {} changed to {block}", &blocked.id);
+  Ok(json(&UpdateBioResponse {
+    success: changed,
+    message: message.into(),
+  }))
+}
+
+// GET v1/blocks
+pub async fn list_blocks(auth: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  let user_uid = auth.ok_or(error::Error::Unauthorized)?;
+
+  match db::get_blocked_users(&user_uid, &db_pool).await {
+    Ok(blocked) => web_json(&blocked),
+    Err(err) => {
+      warn!("Failed to get blocked users of {user_uid}: {err}");
+      Err(reject::custom(error::Error::ServerProblem))
+    }
+  }
+}
+
+// GET v1/followers/requests
+pub async fn list_follow_requests(auth: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  let user_uid = auth.ok_or(error::Error::Unauthorized)?;
+
+  match db::get_follow_requests(&user_uid, &db_pool).await {
+    Ok(requests) => web_json(&requests),
+    Err(err) => {
+      warn!("Failed to get follow requests of {user_uid}: {err}");
+      Err(reject::custom(error::Error::ServerProblem))
+    }
+  }
+}
+
+// POST v1/followers/requests/USERNAME
+pub async fn accept_follow_request(username: String, auth: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  answer_follow_request(username, auth, db_pool, true).await
+}
+
+// DELETE v1/followers/requests/USERNAME
+pub async fn decline_follow_request(username: String, auth: Option<Uuid>, db_pool: PgPool) -> WebResult<impl Reply> {
+  answer_follow_request(username, auth, db_pool, false).await
+}
+
+async fn answer_follow_request(username: String, auth: Option<Uuid>, db_pool: PgPool, accept: bool) -> WebResult<impl Reply> {
+  let user_uid = auth.ok_or(error::Error::Unauthorized)?;
+  let username = username.to_lowercase().trim().to_string();
+
+  let follower = match db::get_user(&username, &db_pool).await {
+    Ok(follower) => follower.ok_or(error::Error::UserNotFound)?,
+    Err(err) => {
+      warn!("Failed to get user '{username}': {err}");
+      return Err(reject::custom(error::Error::ServerProblem));
+    }
+  };
+
+  let answered = match db::answer_follow_request(&user_uid, &follower.id, accept, &db_pool).await {
+    Ok(answered) => answered,
+    Err(err) => {
+      warn!("Failed to answer follow request {} -> {user_uid}: {err}", &follower.id);
+      return Err(reject::custom(error::Error::ServerProblem));
+    }
+  };
+
+  let message = match (accept, answered) {
+    (true, true) => "Zaakceptowano prośbę o obserwowanie.",
+    (false, true) => "Odrzucono prośbę o obserwowanie.",
+    (_, false) => "Brak prośby o obserwowanie od tego użytkownika.",
+  };
+
+  if accept && answered {
+    spawn_evaluate(user_uid, BadgeEvent::Followed, &db_pool);
+  }
+
+  info!("User {user_uid} answered follow request of {} with {accept}", &follower.id);
+  Ok(json(&UpdateBioResponse {
+    success: answered,
+    message: message.into(),
+  }))
+}
+
 // v1/update/user/bio
 pub async fn update_bio(user_uid: Option<Uuid>, body: UpdateBioBody, db_pool: PgPool) -> WebResult<impl Reply> {
   if user_uid.is_none() {
@@ -184,3 +491,19 @@ pub async fn update_displayname(user_uid: Option<Uuid>, body: UpdateDisplayNameB
     }
   }
 }
+
+// v1/update/user/privacy
+pub async fn update_privacy(user_uid: Option<Uuid>, body: UpdatePrivacyBody, db_pool: PgPool) -> WebResult<impl Reply> {
+  let user_uid = user_uid.ok_or(error::Error::Unauthorized)?;
+
+  match db::update_privacy(&user_uid, &db_pool, &body).await {
+    Ok(settings) => {
+      info!("Updated privacy settings of {}", &user_uid);
+      web_json(&settings)
+    }
+    Err(err) => {
+      warn!("Failed to update user ({}) privacy: {}", &user_uid, err);
+      Err(reject::custom(error::Error::ServerProblem))
+    }
+  }
+}
@@ -1,11 +1,13 @@
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use sqlx::PgPool;
 use uuid::Uuid;
 
-use crate::posts::api::PostWithLiked;
+use crate::badges;
+use crate::pagination::{encode_time_cursor, Page, DEFAULT_PAGE_SIZE};
+use crate::posts::api::{PostWithLiked, PostWithOwnerRow};
 use crate::project::models::ProjectCard;
-use crate::user::models::User;
-use crate::user::responses::ProfileResponse;
+use crate::user::models::{BlockedUser, FollowRequest, PrivacySettings, ProfileAccess, User, UserAvatar};
+use crate::user::responses::{ProfileResponse, UpdatePrivacyBody};
 
 const GET_USER_QUERY: &str = r"SELECT name, display_name, id, bio, created_at, updated_at, flags FROM users WHERE name = $1 LIMIT 1";
 const GET_USER_BY_ID_QUERY: &str = r"SELECT * FROM users WHERE id = $1 LIMIT 1";
@@ -55,22 +57,54 @@ pub async fn get_username(
     Ok(result)
 }
 
-pub async fn get_user_avatar_url(
+pub async fn get_user_avatar(
     name: &String,
     pool: &PgPool,
-) -> Result<Option<(Option<String>,)>, Box<dyn std::error::Error>> {
-    let result: Option<(Option<String>,)> =
-        sqlx::query_as(r"SELECT avatar FROM users WHERE name = $1 LIMIT 1")
+) -> Result<Option<UserAvatar>, Box<dyn std::error::Error>> {
+    let result: Option<UserAvatar> = sqlx::query_as(
+        r"SELECT id, name, display_name, avatar, avatar_mime, updated_at FROM users WHERE name = $1 LIMIT 1",
+    )
             .bind(name)
             .fetch_optional(pool)
             .await?;
     Ok(result)
 }
 
+// Returns the previous avatar with its MIME type
+pub async fn set_avatar(
+    uid: &Uuid,
+    key: &String,
+    mime: &str,
+    pool: &PgPool,
+) -> Result<Option<(String, String)>, Box<dyn std::error::Error>> {
+    let mut transaction = pool.begin().await?;
+
+    let previous: Option<(Option<String>, Option<String>)> =
+        sqlx::query_as(r"SELECT avatar, avatar_mime FROM users WHERE id = $1 FOR UPDATE")
+            .bind(uid)
+            .fetch_optional(&mut *transaction)
+            .await?;
+
+    sqlx::query(r"UPDATE users SET avatar = $1, avatar_mime = $2, updated_at = $3 WHERE id = $4")
+        .bind(key)
+        .bind(mime)
+        .bind(Utc::now())
+        .bind(uid)
+        .execute(&mut *transaction)
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(match previous {
+        Some((Some(key), Some(mime))) => Some((key, mime)),
+        _ => None,
+    })
+}
+
 pub async fn get_user_by_id(
     id: &Uuid,
     pool: &PgPool,
-) -> Result<Option<User>, Box<dyn std::error::Error>> {
+) -> Result<Option<User>, Box<dyn std::error::Error + Send + Sync>> {
     let result: Option<User> = sqlx::query_as(GET_USER_BY_ID_QUERY)
         .bind(id)
         .fetch_optional(pool)
@@ -80,61 +114,359 @@ pub async fn get_user_by_id(
 }
 
 pub async fn get_profile(
-    receiver: Option<Uuid>,
-    username: &String,
+    access: &ProfileAccess,
+    viewer: Option<Uuid>,
     is_authorized: bool,
     pool: &PgPool,
-) -> Result<Option<ProfileResponse>, Box<dyn std::error::Error>> {
-    let query = "SELECT * FROM users WHERE name = $1 LIMIT 1";
-    let query_projects = "SELECT tournament, projects.id, projects.name, projects.display_name, projects.owner_id, projects.private, projects.description, projects.likes, projects.created_at, projects.updated_at, users.id AS userid, users.name AS username  FROM projects INNER JOIN users ON projects.owner_id = users.id WHERE users.name = $1 AND (projects.private = false OR projects.private = $2) ORDER BY projects.updated_at DESC";
-    let query_posts = r#"
-    SELECT
-      posts.id, 
-      posts.owner_id,
-      posts.content,
-      posts.created_at,
-      posts.likes,
-      EXISTS(SELECT 1 FROM posts_likes WHERE post_id = posts.id AND $2 IS NOT NULL AND user_id = $2) as "is_liked_by_user"
-    FROM posts
-    INNER JOIN users ON posts.owner_id = users.id
-    WHERE users.name = $1
-    ORDER BY posts.updated_at DESC"#;
+) -> Result<Option<ProfileResponse>, Box<dyn std::error::Error + Send + Sync>> {
+    let limit = DEFAULT_PAGE_SIZE;
+    let show_posts = !access.hide_posts || is_authorized;
+
+    // The user is already resolved, so everything else can be fetched concurrently
+    let (user, badges, privacy, projects, posts) = tokio::try_join!(
+        get_user_by_id(&access.id, pool),
+        badges::db::get_user_badges(&access.id, pool),
+        async {
+            match is_authorized {
+                true => get_privacy(&access.id, pool).await,
+                false => Ok(None),
+            }
+        },
+        get_profile_projects(&access.id, viewer, is_authorized, None, limit, pool),
+        async {
+            match show_posts {
+                true => get_profile_posts(&access.id, viewer, None, limit, pool).await,
+                false => Ok(Page { items: Vec::new(), cursor: None }),
+            }
+        },
+    )?;
+
+    // Deleted in the meantime
+    let user = match user {
+        Some(user) => user,
+        None => return Ok(None),
+    };
 
-    let result: Option<User> = sqlx::query_as(query)
-        .bind(username)
-        .fetch_optional(pool)
+    let response = ProfileResponse {
+        name: user.name,
+        display_name: user.display_name,
+        id: user.id,
+        bio: user.bio,
+        projects: projects.items,
+        projects_cursor: projects.cursor,
+        posts: posts.items,
+        posts_cursor: posts.cursor,
+        created_at: user.created_at,
+        updated_at: user.updated_at,
+        flags: user.flags,
+        badges,
+        privacy,
+    };
+
+    Ok(Some(response))
+}
+
+// Includes projects of other users the profile owner is a member of, the owner sees unlisted and private ones too
+const GET_PROFILE_PROJECTS_QUERY: &str = r#"SELECT projects.tournament,
+  projects.id,
+  projects.name,
+  projects.display_name,
+  projects.owner_id,
+  users.name AS owner_name,
+  projects.visibility,
+  projects.description,
+  '/v1/media/' || projects.thumbnail AS thumbnail,
+  projects.likes,
+  projects.created_at,
+  projects.updated_at,
+  EXISTS(SELECT 1 FROM projects_likes WHERE project_id = projects.id AND $6 IS NOT NULL AND user_id = $6) as "liked"
+FROM projects
+    INNER JOIN users ON projects.owner_id = users.id
+WHERE projects.id IN (SELECT project_id FROM project_members WHERE user_id = $1 AND accepted = true)
+  AND (is_project_listed(projects.visibility, $6) OR $2 OR is_project_member(projects.id, $6))
+  AND (projects.owner_id = $1 OR can_view_profile(users.id, $6))
+  AND ($3::timestamptz IS NULL OR (projects.updated_at, projects.id) < ($3, $4))
+ORDER BY projects.updated_at DESC, projects.id DESC
+LIMIT $5"#;
+
+pub async fn get_profile_projects(
+    owner_id: &Uuid,
+    viewer: Option<Uuid>,
+    can_see_hidden: bool,
+    cursor: Option<(DateTime<Utc>, Uuid)>,
+    limit: i64,
+    pool: &PgPool,
+) -> Result<Page<ProjectCard>, Box<dyn std::error::Error + Send + Sync>> {
+    let rows: Vec<ProjectCard> = sqlx::query_as(GET_PROFILE_PROJECTS_QUERY)
+        .bind(owner_id)
+        .bind(can_see_hidden)
+        .bind(cursor.map(|(time, _)| time))
+        .bind(cursor.map(|(_, id)| id))
+        .bind(limit + 1)
+        .bind(viewer)
+        .fetch_all(pool)
         .await?;
 
-    let projects: Vec<ProjectCard> = sqlx::query_as(query_projects)
-        .bind(username)
-        .bind(is_authorized)
+    Ok(Page::from_rows(rows, limit, |project| {
+        encode_time_cursor(&project.updated_at, &project.id)
+    }))
+}
+
+const GET_PROFILE_POSTS_QUERY: &str = r#"SELECT
+  id,
+  owner_id,
+  content,
+  created_at,
+  likes,
+  EXISTS(SELECT 1 FROM posts_likes WHERE post_id = posts.id AND $2 IS NOT NULL AND user_id = $2) as "is_liked_by_user"
+FROM posts
+WHERE owner_id = $1 AND ($3::integer IS NULL OR id < $3)
+ORDER BY id DESC
+LIMIT $4"#;
+
+pub async fn get_profile_posts(
+    owner_id: &Uuid,
+    viewer: Option<Uuid>,
+    before_id: Option<i32>,
+    limit: i64,
+    pool: &PgPool,
+) -> Result<Page<PostWithLiked>, Box<dyn std::error::Error + Send + Sync>> {
+    let rows: Vec<PostWithLiked> = sqlx::query_as(GET_PROFILE_POSTS_QUERY)
+        .bind(owner_id)
+        .bind(viewer)
+        .bind(before_id)
+        .bind(limit + 1)
         .fetch_all(pool)
         .await?;
 
-    let posts: Vec<PostWithLiked> = sqlx::query_as(query_posts)
+    Ok(Page::from_rows(rows, limit, |post| post.id.to_string()))
+}
+
+const GET_PROFILE_ACCESS_QUERY: &str = r"SELECT id,
+  profile_visibility,
+  coalesce(id = $2, false) AS is_owner,
+  is_follower(id, $2)      AS is_follower,
+  has_blocked(id, $2)      AS is_blocked,
+  hide_posts,
+  hide_likes
+FROM users
+WHERE name = $1
+LIMIT 1";
+
+pub async fn get_profile_access(
+    username: &String,
+    viewer: Option<Uuid>,
+    pool: &PgPool,
+) -> Result<Option<ProfileAccess>, Box<dyn std::error::Error>> {
+    let result: Option<ProfileAccess> = sqlx::query_as(GET_PROFILE_ACCESS_QUERY)
         .bind(username)
-        .bind(receiver)
-        .fetch_all(pool)
+        .bind(viewer)
+        .fetch_optional(pool)
         .await?;
 
-    if result.is_none() {
-        return Ok(None);
+    Ok(result)
+}
+
+pub async fn get_privacy(
+    uid: &Uuid,
+    pool: &PgPool,
+) -> Result<Option<PrivacySettings>, Box<dyn std::error::Error + Send + Sync>> {
+    let result: Option<PrivacySettings> = sqlx::query_as(
+        r"SELECT profile_visibility, hide_posts, hide_likes FROM users WHERE id = $1 LIMIT 1",
+    )
+    .bind(uid)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(result)
+}
+
+pub async fn update_privacy(
+    uid: &Uuid,
+    pool: &PgPool,
+    body: &UpdatePrivacyBody,
+) -> Result<PrivacySettings, Box<dyn std::error::Error>> {
+    let query = r"UPDATE users SET
+      profile_visibility = coalesce($1, profile_visibility),
+      hide_posts = coalesce($2, hide_posts),
+      hide_likes = coalesce($3, hide_likes),
+      updated_at = $4
+    WHERE id = $5
+    RETURNING profile_visibility, hide_posts, hide_likes";
+
+    let result: PrivacySettings = sqlx::query_as(query)
+        .bind(body.profile_visibility)
+        .bind(body.hide_posts)
+        .bind(body.hide_likes)
+        .bind(Utc::now())
+        .bind(uid)
+        .fetch_one(pool)
+        .await?;
+
+    // Pending requests are accepted once the profile is open to more than followers
+    if !result.profile_visibility.approves_followers() {
+        sqlx::query(r"UPDATE users_follows SET accepted = true WHERE followed_id = $1 AND accepted = false")
+            .bind(uid)
+            .execute(pool)
+            .await?;
     }
-    let user = result.unwrap();
 
-    let response = ProfileResponse {
-        name: user.name,
-        display_name: user.display_name,
-        id: user.id,
-        bio: user.bio,
-        projects,
-        posts,
-        created_at: user.created_at,
-        updated_at: user.updated_at,
-        flags: user.flags,
+    Ok(result)
+}
+
+// Unaccepted follows are pending requests, unfollowing withdraws them as well
+pub async fn set_follow(
+    follower_id: &Uuid,
+    followed_id: &Uuid,
+    follow: bool,
+    accepted: bool,
+    pool: &PgPool,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let query = match follow {
+        true => r"INSERT INTO users_follows (follower_id, followed_id, accepted) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+        false => r"DELETE FROM users_follows WHERE follower_id = $1 AND followed_id = $2",
     };
 
-    Ok(Some(response))
+    let result = sqlx::query(query)
+        .bind(follower_id)
+        .bind(followed_id)
+        .bind(accepted)
+        .execute(pool)
+        .await?;
+
+    // False when nothing changed (already followed/not followed)
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn has_blocked(
+    blocker_id: &Uuid,
+    blocked_id: &Uuid,
+    pool: &PgPool,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let result: bool = sqlx::query_scalar(r"SELECT has_blocked($1, $2)")
+        .bind(blocker_id)
+        .bind(blocked_id)
+        .fetch_one(pool)
+        .await?;
+
+    Ok(result)
+}
+
+pub async fn set_block(
+    blocker_id: &Uuid,
+    blocked_id: &Uuid,
+    block: bool,
+    pool: &PgPool,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut transaction = pool.begin().await?;
+
+    let query = match block {
+        true => r"INSERT INTO user_blocks (blocker_id, blocked_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        false => r"DELETE FROM user_blocks WHERE blocker_id = $1 AND blocked_id = $2",
+    };
+
+    let result = sqlx::query(query)
+        .bind(blocker_id)
+        .bind(blocked_id)
+        .execute(&mut *transaction)
+        .await?;
+
+    // Blocking ends following in both directions
+    if block {
+        sqlx::query(r"DELETE FROM users_follows WHERE (follower_id = $1 AND followed_id = $2) OR (follower_id = $2 AND followed_id = $1)")
+            .bind(blocker_id)
+            .bind(blocked_id)
+            .execute(&mut *transaction)
+            .await?;
+    }
+
+    transaction.commit().await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn get_blocked_users(
+    blocker_id: &Uuid,
+    pool: &PgPool,
+) -> Result<Vec<BlockedUser>, Box<dyn std::error::Error>> {
+    let query = r"SELECT users.id, users.name, users.display_name, user_blocks.blocked_at FROM user_blocks INNER JOIN users ON user_blocks.blocked_id = users.id WHERE user_blocks.blocker_id = $1 ORDER BY user_blocks.blocked_at DESC";
+
+    let result: Vec<BlockedUser> = sqlx::query_as(query)
+        .bind(blocker_id)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(result)
+}
+
+// Accepts or declines the pending request, false if there is none
+pub async fn answer_follow_request(
+    followed_id: &Uuid,
+    follower_id: &Uuid,
+    accept: bool,
+    pool: &PgPool,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let query = match accept {
+        true => r"UPDATE users_follows SET accepted = true, followed_at = now() WHERE follower_id = $1 AND followed_id = $2 AND accepted = false",
+        false => r"DELETE FROM users_follows WHERE follower_id = $1 AND followed_id = $2 AND accepted = false",
+    };
+
+    let result = sqlx::query(query)
+        .bind(follower_id)
+        .bind(followed_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn get_follow_requests(
+    followed_id: &Uuid,
+    pool: &PgPool,
+) -> Result<Vec<FollowRequest>, Box<dyn std::error::Error>> {
+    let query = r"SELECT users.id, users.name, users.display_name, users_follows.followed_at AS requested_at FROM users_follows INNER JOIN users ON users_follows.follower_id = users.id WHERE users_follows.followed_id = $1 AND users_follows.accepted = false ORDER BY users_follows.followed_at DESC";
+
+    let result: Vec<FollowRequest> = sqlx::query_as(query)
+        .bind(followed_id)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(result)
+}
+
+pub async fn get_liked_posts(
+    user_id: &Uuid,
+    viewer: Option<Uuid>,
+    pool: &PgPool,
+) -> Result<Vec<PostWithOwnerRow>, Box<dyn std::error::Error>> {
+    let query = r#"
+    SELECT
+      posts.id,
+      posts.content,
+      posts.created_at,
+      posts.likes,
+      users.id,
+      users.name,
+      users.display_name,
+      users.flags,
+      coalesce((SELECT array_agg(badge_id ORDER BY awarded_at) FROM user_badges WHERE user_id = users.id), '{}') as "badges",
+      EXISTS(SELECT 1 FROM posts_likes WHERE post_id = posts.id AND $2 IS NOT NULL AND user_id = $2) as "liked"
+    FROM posts_likes
+    INNER JOIN posts ON posts_likes.post_id = posts.id
+    INNER JOIN users ON posts.owner_id = users.id
+    WHERE posts_likes.user_id = $1
+      AND can_view_profile(users.id, $2)
+      AND (users.hide_posts = false OR users.id = $2)
+      AND NOT has_blocked($2, users.id)
+    ORDER BY posts_likes.liked_at DESC"#;
+
+    let result: Vec<PostWithOwnerRow> = sqlx::query_as(query)
+        .bind(user_id)
+        .bind(viewer)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(result)
 }
 
 pub async fn update_bio(
@@ -11,6 +11,96 @@ pub const USER_DEVELOPER: i32 = 1 << 1;
 #[allow(dead_code)]
 pub const USER_TEACHER: i32 = 1 << 2;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "profile_visibility", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ProfileVisibility {
+  Everyone,
+  School,
+  Followers,
+}
+
+// Relation of the viewer to the profile
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileViewer {
+  Guest,
+  User,
+  Follower,
+  Blocked,
+  Owner,
+}
+
+impl ProfileVisibility {
+  // Mirrors `can_view_profile` in the database
+  pub fn can_view(&self, viewer: ProfileViewer) -> bool {
+    match (self, viewer) {
+      (_, ProfileViewer::Owner) => true,
+      (_, ProfileViewer::Blocked) => false,
+      (ProfileVisibility::Everyone, _) => true,
+      (ProfileVisibility::School, viewer) => viewer != ProfileViewer::Guest,
+      (ProfileVisibility::Followers, viewer) => viewer == ProfileViewer::Follower,
+    }
+  }
+
+  // Follows of profiles visible only to followers wait for the owner
+  pub fn approves_followers(&self) -> bool {
+    matches!(self, ProfileVisibility::Followers)
+  }
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct PrivacySettings {
+  pub profile_visibility: ProfileVisibility,
+  pub hide_posts: bool,
+  pub hide_likes: bool,
+}
+
+// Resolved against the viewer, who is a follower only once accepted
+#[derive(Debug, FromRow)]
+pub struct ProfileAccess {
+  pub id: Uuid,
+  pub profile_visibility: ProfileVisibility,
+  pub is_owner: bool,
+  pub is_follower: bool,
+  pub is_blocked: bool,
+  pub hide_posts: bool,
+  pub hide_likes: bool,
+}
+
+impl ProfileAccess {
+  pub fn viewer(&self, signed_in: bool) -> ProfileViewer {
+    match (self.is_owner, self.is_blocked, self.is_follower, signed_in) {
+      (true, _, _, _) => ProfileViewer::Owner,
+      (_, true, _, _) => ProfileViewer::Blocked,
+      (_, _, true, _) => ProfileViewer::Follower,
+      (_, _, _, true) => ProfileViewer::User,
+      _ => ProfileViewer::Guest,
+    }
+  }
+
+  pub fn can_view(&self, signed_in: bool) -> bool {
+    self.profile_visibility.can_view(self.viewer(signed_in))
+  }
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct FollowRequest {
+  pub id: Uuid,
+  pub name: String,
+  pub display_name: String,
+  #[serde(with = "ts_milliseconds")]
+  pub requested_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct BlockedUser {
+  pub id: Uuid,
+  pub name: String,
+  pub display_name: String,
+  #[serde(with = "ts_milliseconds")]
+  pub blocked_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct User {
   pub name: String,
@@ -30,7 +120,7 @@ pub struct User {
 #[allow(dead_code)]
 impl User {
   pub fn is_flag_set(&self, index: u32) -> bool {
-    1 == (self.flags & 1 << index)
+    0 != (self.flags & 1 << index)
   }
 
   pub fn set_flag(&mut self, index: u32, turn_on: bool) {
@@ -68,3 +158,14 @@ impl User {
     self.set_flag(2, turn_on)
   }
 }
+
+#[derive(FromRow)]
+pub struct UserAvatar {
+  pub id: Uuid,
+  pub name: String,
+  pub display_name: String,
+  // Storage key, None if the user has not uploaded an avatar
+  pub avatar: Option<String>,
+  pub avatar_mime: Option<String>,
+  pub updated_at: DateTime<Utc>,
+}
@@ -3,8 +3,10 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::badges::models::UserBadge;
 use crate::posts::api::PostWithLiked;
 use crate::project::models::ProjectCard;
+use crate::user::models::{PrivacySettings, ProfileVisibility};
 
 #[derive(Serialize)]
 pub struct ProfileResponse {
@@ -14,8 +16,11 @@ pub struct ProfileResponse {
 
     pub bio: Option<String>,
 
+    // First pages, continued by v1/profile/USERNAME/projects and v1/profile/USERNAME/posts
     pub projects: Vec<ProjectCard>,
+    pub projects_cursor: Option<String>,
     pub posts: Vec<PostWithLiked>,
+    pub posts_cursor: Option<String>,
 
     #[serde(with = "ts_milliseconds")]
     pub created_at: DateTime<Utc>,
@@ -23,6 +28,11 @@ pub struct ProfileResponse {
     pub updated_at: DateTime<Utc>,
 
     pub flags: i32,
+    pub badges: Vec<UserBadge>,
+
+    // Only present when the owner is looking at their own profile
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub privacy: Option<PrivacySettings>,
 }
 
 #[derive(Deserialize)]
@@ -41,6 +51,13 @@ pub struct UpdateBioBody {
     pub bio: String,
 }
 
+#[derive(Deserialize)]
+pub struct UpdatePrivacyBody {
+    pub profile_visibility: Option<ProfileVisibility>,
+    pub hide_posts: Option<bool>,
+    pub hide_likes: Option<bool>,
+}
+
 #[derive(Deserialize)]
 pub struct UpdateDisplayNameBody {
     pub displayname: String,
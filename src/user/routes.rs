@@ -26,7 +26,71 @@ pub fn routes(db_pool: &PgPool) -> impl Filter<Extract=impl warp::Reply, Error=w
     .and(with_db(db_pool.clone()))
     .and_then(api::patch_user);
 
+  let follow = warp::path!("users" / String / "follow")
+    .and(warp::get())
+    .and(warp::path::end())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(api::follow_user);
+
+  let unfollow = warp::path!("users" / String / "unfollow")
+    .and(warp::get())
+    .and(warp::path::end())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(api::unfollow_user);
+
+  let block = warp::path!("users" / String / "block")
+    .and(warp::get())
+    .and(warp::path::end())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(api::block_user);
+
+  let unblock = warp::path!("users" / String / "unblock")
+    .and(warp::get())
+    .and(warp::path::end())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(api::unblock_user);
+
+  let blocks = warp::path!("blocks")
+    .and(warp::get())
+    .and(warp::path::end())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(api::list_blocks);
+
+  let follow_requests = warp::path!("followers" / "requests")
+    .and(warp::get())
+    .and(warp::path::end())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(api::list_follow_requests);
+
+  let accept_follow_request = warp::path!("followers" / "requests" / String)
+    .and(warp::post())
+    .and(warp::path::end())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(api::accept_follow_request);
+
+  let decline_follow_request = warp::path!("followers" / "requests" / String)
+    .and(warp::delete())
+    .and(warp::path::end())
+    .and(with_auth())
+    .and(with_db(db_pool.clone()))
+    .and_then(api::decline_follow_request);
+
   list
     .or(get)
     .or(patch)
+    .or(follow)
+    .or(unfollow)
+    .or(follow_requests)
+    .or(accept_follow_request)
+    .or(decline_follow_request)
+    .or(block)
+    .or(unblock)
+    .or(blocks)
 }
@@ -2,6 +2,8 @@ use std::{collections::HashMap, net::SocketAddr};
 
 use chrono::Utc;
 
+use crate::github::parse_repo_url;
+
 pub const CKZIU_MAIL_DOMAIN: &str = "ckziu.elodz.edu.pl";
 
 pub fn current_millis() -> i64 {
@@ -153,3 +155,17 @@ pub fn validate_description(description: Option<String>) -> Result<Option<String
 
   Ok(Some(description))
 }
+
+pub fn validate_github_url(github_url: String) -> Result<String, String> {
+  let github_url = github_url.trim();
+  // An empty link removes the repository
+  if github_url.is_empty() {
+    return Ok(String::new());
+  }
+
+  match parse_repo_url(github_url) {
+    Some(repo) => Ok(repo.url()),
+    // The link must lead to a GitHub repository.
+    None => Err("Link musi prowadzić do repozytorium na GitHubie.".into()),
+  }
+}